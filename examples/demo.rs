@@ -0,0 +1,650 @@
+//! Walkthrough of the analyses on a small doublet, writing its plots to `../images`.
+
+use light::{analysis, compute, optimization, query, stock, system, tolerance, utils};
+
+/// using MMGS as the units of measurement
+#[tokio::main]
+async fn main() {
+    let system = system::System {
+        object: system::Object {
+            distance: 2.0,
+            semi_diameter: 1.0,
+            material: system::Material::Constant(1.0),
+        },
+        stop_index: 3u32,
+        surfaces: vec![
+            system::Surface {
+                thickness: 1.05,
+                material: system::Material::Model {
+                    nd: 1.517,
+                    vd: 64.2,
+                },
+                curvature: 1.0 / 7.3895,
+                conic: 0.0,
+                semi_diameter: 2.0,
+                mount_expansion: 23.6e-6,
+                decenter: nalgebra::Vector2::zeros(),
+                tilt: nalgebra::Vector2::zeros(),
+            },
+            system::Surface {
+                thickness: 0.40,
+                material: system::Material::Model {
+                    nd: 1.649,
+                    vd: 33.8,
+                },
+                curvature: 1.0 / -5.1784,
+                conic: 0.0,
+                semi_diameter: 2.0,
+                mount_expansion: 23.6e-6,
+                decenter: nalgebra::Vector2::zeros(),
+                tilt: nalgebra::Vector2::zeros(),
+            },
+            system::Surface {
+                thickness: 10.55,
+                material: system::Material::Constant(1.0),
+                curvature: 1.0 / -16.2225,
+                conic: 0.0,
+                semi_diameter: 2.0,
+                mount_expansion: 23.6e-6,
+                decenter: nalgebra::Vector2::zeros(),
+                tilt: nalgebra::Vector2::zeros(),
+            },
+            system::Surface {
+                thickness: 0.0,
+                material: system::Material::Constant(1.0),
+                curvature: 0.0,
+                conic: 0.0,
+                semi_diameter: 1.0,
+                mount_expansion: 23.6e-6,
+                decenter: nalgebra::Vector2::zeros(),
+                tilt: nalgebra::Vector2::zeros(),
+            },
+        ],
+        fields: vec![
+            system::Field::new(0.0, 0.0),
+            system::Field::new(0.0, 0.7),
+            system::Field::new(0.0, 1.0),
+        ],
+        wavelengths: system::Wavelength::visible(),
+        primary: 1,
+        environment: system::Environment::default(),
+        variables: Vec::new(),
+        constraints: Vec::new(),
+        solves: Vec::new(),
+        configurations: Vec::new(),
+    };
+
+    let origin = system.object.top();
+
+    let directions = {
+        let entry_limits = system.surface_edges(0).unwrap();
+
+        (
+            (entry_limits.0 - origin).normalize(),
+            (entry_limits.1 - origin).normalize(),
+        )
+    };
+
+    {
+        let mut query = compute::fan::Query {
+            origin: origin.coords,
+            dir_a: directions.0,
+            dir_b: directions.1,
+            resolution: 4,
+            wavelength: system.primary_wavelength(),
+        };
+
+        let mut i = 0;
+        let result = loop {
+            {
+                let mut view = utils::View::new();
+                let rays = (0..query.resolution)
+                    .map(|i| {
+                        system::Ray::new(
+                            query.origin.into(),
+                            query
+                                .dir_a
+                                .lerp(&query.dir_b, i as f32 / (query.resolution - 1) as f32)
+                                .normalize(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                {
+                    let query = compute::raytracing::Query {
+                        rays: system.polychromatic_rays(&rays),
+                    };
+                    let response = system.trace(&query).await.unwrap();
+
+                    view.draw_intersections(&response.intersections);
+                }
+
+                view.draw_system(&system);
+                view.finish();
+                view.save(format!("../images/raytracing-{i}.svg")).unwrap();
+            }
+
+            let response = system.fan(&query).await.unwrap();
+
+            let mut result = response
+                .heights
+                .array_windows::<2>()
+                .enumerate()
+                .filter_map(|(i, [a, b])| ((a * b) <= 0.0).then_some(i));
+
+            if let Some(index) = result.next() {
+                if result.next().is_some() {
+                    panic!("Unexpected");
+                }
+
+                query = compute::fan::Query {
+                    dir_a: query
+                        .dir_a
+                        .lerp(&query.dir_b, index as f32 / (query.resolution - 1) as f32)
+                        .normalize(),
+                    dir_b: query
+                        .dir_a
+                        .lerp(
+                            &query.dir_b,
+                            (index + 1) as f32 / (query.resolution - 1) as f32,
+                        )
+                        .normalize(),
+                    ..query
+                };
+
+                let height = response.heights[index];
+                if height.abs() < 1e-6 || i > 128 {
+                    dbg!(i, height);
+                    break query.dir_a;
+                }
+            } else {
+                dbg!(i);
+                break query.dir_a.lerp(&query.dir_b, 0.5).normalize();
+            }
+
+            i += 1;
+        };
+
+        {
+            let mut view = utils::View::new();
+            for i in 0..query.resolution {
+                view.draw_ray(&system::Ray::new(
+                    query.origin.into(),
+                    query
+                        .dir_a
+                        .lerp(&query.dir_b, i as f32 / (query.resolution - 1) as f32)
+                        .normalize(),
+                ));
+            }
+
+            {
+                let rays = vec![system::Ray::new(origin, result)];
+
+                let query = compute::raytracing::Query { rays };
+                let response = system.trace(&query).await.unwrap();
+
+                view.draw_intersections(&response.intersections);
+
+                dbg!(response.intersections.last().unwrap().point());
+            }
+
+            view.draw_system(&system);
+            view.finish();
+            view.save(format!("../images/raytracing-{i}.svg")).unwrap();
+        }
+    }
+    {
+        let query = analysis::grid_distortion::Query {
+            half_width: system.object.semi_diameter,
+            half_height: system.object.semi_diameter,
+            resolution: 9,
+        };
+        let response = system.grid_distortion(&query).await.unwrap();
+
+        println!("{response}");
+        response
+            .plot()
+            .save("../images/grid-distortion.svg")
+            .unwrap();
+    }
+
+    {
+        let query = analysis::longitudinal::Query {
+            wavelengths: vec![
+                system::FRAUNHOFER_F,
+                system::FRAUNHOFER_D,
+                system::FRAUNHOFER_C,
+            ],
+            reference: 1,
+            resolution: 16,
+        };
+        let response = system.longitudinal_aberration(&query).await.unwrap();
+
+        println!("{response}");
+        response
+            .plot()
+            .save("../images/longitudinal-aberration.svg")
+            .unwrap();
+    }
+
+    {
+        let query = analysis::chromatic_focal_shift::Query {
+            min_wavelength: 0.4,
+            max_wavelength: 0.7,
+            reference: system::FRAUNHOFER_D,
+            resolution: 31,
+        };
+        let response = system.chromatic_focal_shift(&query);
+
+        println!("{response}");
+        response
+            .plot()
+            .save("../images/chromatic-focal-shift.svg")
+            .unwrap();
+    }
+
+    {
+        let query = analysis::lateral_color::Query {
+            short: system::FRAUNHOFER_F,
+            primary: system::FRAUNHOFER_D,
+            long: system::FRAUNHOFER_C,
+            resolution: 11,
+        };
+        let response = system.lateral_color(&query).await.unwrap();
+
+        println!("{response}");
+        response.plot().save("../images/lateral-color.svg").unwrap();
+    }
+
+    {
+        let query = analysis::seidel::Query {
+            short: system::FRAUNHOFER_F,
+            primary: system::FRAUNHOFER_D,
+            long: system::FRAUNHOFER_C,
+        };
+        let response = system.seidel(&query);
+
+        println!("{response}");
+        response.plot().save("../images/seidel.svg").unwrap();
+    }
+
+    {
+        let query = analysis::vignetting::Query {
+            wavelength: system::FRAUNHOFER_D,
+            resolution: 11,
+            pupil_resolution: 64,
+        };
+        let response = system.vignetting(&query).await.unwrap();
+
+        println!("{response}");
+        response
+            .plot()
+            .save("../images/relative-illumination.svg")
+            .unwrap();
+
+        let mut system = system.clone();
+        system
+            .solve_vignetting(system::FRAUNHOFER_D, 256)
+            .await
+            .unwrap();
+
+        for field in &system.fields {
+            println!("{:?}", field.vignetting);
+        }
+    }
+
+    {
+        for temperature in [-40.0, 20.0, 70.0] {
+            let mut system = system.clone();
+            system.environment.temperature = temperature;
+            let perturbed = system.perturbed();
+
+            println!(
+                "{temperature:>6.1} °C: paraxial focus {:.6}",
+                perturbed.paraxial_focus(perturbed.primary_wavelength())
+            );
+        }
+    }
+
+    {
+        // F/4 solve on the last lens surface, then the image distance solved to paraxial focus
+        let mut system = system.clone();
+        system.stop_index = 0;
+        system.solves = vec![
+            system::Solve::f_number(2, 4.0),
+            system::Solve::MarginalHeight {
+                surface: 2,
+                height: 0.0,
+            },
+        ];
+
+        let solved = system.solved();
+        println!(
+            "solved radius {:.6}, image distance {:.6}, paraxial focus {:.6}",
+            solved.surfaces[2].curvature.recip(),
+            solved.surfaces[2].thickness,
+            solved.paraxial_focus(solved.primary_wavelength())
+        );
+    }
+
+    {
+        use query::merit::{MeritFunction, Operand, Target};
+
+        // Focus positions for a distant and a close object, sharing the lens
+        let mut system = system.clone();
+        system.stop_index = 0;
+        system.configurations = vec![
+            system::Configuration::new(
+                "infinity",
+                vec![
+                    system::Override::ObjectDistance(1000.0),
+                    system::Override::Thickness {
+                        surface: 2,
+                        value: 11.2,
+                    },
+                ],
+            ),
+            system::Configuration::new(
+                "close",
+                vec![
+                    system::Override::ObjectDistance(100.0),
+                    system::Override::Thickness {
+                        surface: 2,
+                        value: 12.6,
+                    },
+                ],
+            ),
+        ];
+
+        let configurations = system.all_configurations().unwrap();
+        for (configuration, system) in system.configurations.iter().zip(&configurations) {
+            println!(
+                "{}: paraxial focus {:.6}",
+                configuration.name,
+                system.paraxial_focus(system.primary_wavelength())
+            );
+        }
+
+        let merit_function = MeritFunction::new(vec![
+            Operand::new(
+                query::spot::SpotRms {
+                    field: 0,
+                    resolution: 16,
+                },
+                Target::Equal(0.0),
+                1.0,
+            ),
+            Operand::new(query::efl::EffectiveFocalLength, Target::Equal(12.0), 1.0)
+                .in_configuration(0),
+        ]);
+        let merit = merit_function.evaluate(&system).await.unwrap();
+        println!("{}", merit_function.report(&merit));
+
+        system.save("../images/focus.toml").unwrap();
+        let reloaded = system::System::load("../images/focus.toml", &[]).unwrap();
+        for (configuration, system) in reloaded
+            .configurations
+            .iter()
+            .zip(&reloaded.all_configurations().unwrap())
+        {
+            println!(
+                "reloaded {}: paraxial focus {:.6}",
+                configuration.name,
+                system.paraxial_focus(system.primary_wavelength())
+            );
+        }
+    }
+
+    {
+        let system = system::System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        let wavelength = system.primary_wavelength();
+
+        println!(
+            "imported ZMX: EFL {:.6}, entrance pupil {:.6}, F/{:.3}, paraxial focus {:.6}",
+            system.effective_focal_length(wavelength),
+            system.entrance_pupil().semi_diameter,
+            system.working_f_number(wavelength),
+            system.paraxial_focus(wavelength)
+        );
+        for field in &system.fields {
+            println!("  field at {:.6}", field.point.y);
+        }
+
+        system.save_zmx("../images/doublet.zmx").unwrap();
+        system.save_seq("../images/doublet.seq").unwrap();
+    }
+
+    {
+        use query::merit::{MeritFunction, Operand, Target};
+
+        // Closest stock achromats to the doublet, refocused on axis
+        let mut system = system.clone();
+        system.stop_index = 0;
+        let catalog = stock::StockCatalog::embedded();
+        for lens in catalog.search(12.0, 3.0, 4.0) {
+            println!(
+                "stock {}: {} EFL {:.4}, diameter {}",
+                lens.part,
+                lens.description,
+                lens.focal_length(),
+                lens.diameter
+            );
+        }
+
+        let merit_function = MeritFunction::new(vec![Operand::new(
+            query::spot::SpotRms {
+                field: 0,
+                resolution: 16,
+            },
+            Target::Equal(0.0),
+            1.0,
+        )]);
+        let query = stock::matching::Query {
+            candidates: 2,
+            iterations: 5,
+            ..stock::matching::Query::new(0..3, merit_function)
+        };
+        let response = system.catalog_match(&catalog, &query).await.unwrap();
+        println!("{response}");
+    }
+
+    {
+        use query::merit::{MeritFunction, Operand, Target};
+
+        let merit_function = MeritFunction::new(vec![
+            Operand::new(query::efl::EffectiveFocalLength, Target::Equal(10.0), 1.0),
+            Operand::new(
+                query::spot::SpotRms {
+                    field: 0,
+                    resolution: 16,
+                },
+                Target::Equal(0.0),
+                1.0,
+            ),
+            Operand::new(
+                query::spot::SpotRms {
+                    field: 2,
+                    resolution: 16,
+                },
+                Target::Equal(0.0),
+                1.0,
+            ),
+            Operand::new(
+                query::ray_height::RayHeight {
+                    field: 0,
+                    pupil: nalgebra::Point2::new(0.0, 1.0),
+                    surface: 2,
+                },
+                Target::AtMost(1.8),
+                1.0,
+            ),
+            Operand::new(
+                query::thickness::Thickness {
+                    from: 0,
+                    to: 1,
+                    origin: nalgebra::Point2::new(0.0, 2.0),
+                },
+                Target::AtLeast(0.5),
+                10.0,
+            ),
+            Operand::new(
+                query::seidel::Seidel {
+                    query: analysis::seidel::Query {
+                        short: system::FRAUNHOFER_F,
+                        primary: system::FRAUNHOFER_D,
+                        long: system::FRAUNHOFER_C,
+                    },
+                    surface: None,
+                    term: 0,
+                },
+                Target::Equal(0.0),
+                1.0,
+            ),
+        ]);
+
+        let merit = merit_function.evaluate(&system).await.unwrap();
+        println!("{}", merit_function.report(&merit));
+        println!(
+            "cpu merit: {:.6}",
+            merit_function.evaluate_cpu(&system).unwrap().value
+        );
+    }
+
+    {
+        use optimization::{Bounds, Constraint, Parameter, Variable};
+        use query::merit::{MeritFunction, Operand, Target};
+
+        // Distant object imaged at ±5°, aperture stop on the front lens
+        let mut system = system.clone();
+        system.object.distance = 1000.0;
+        system.object.semi_diameter = 87.5;
+        system.stop_index = 0;
+
+        let mut operands = vec![Operand::new(
+            query::efl::EffectiveFocalLength,
+            Target::Equal(12.0),
+            1.0,
+        )];
+        operands.extend((0..system.fields.len()).map(|field| {
+            Operand::new(
+                query::spot::SpotRms {
+                    field,
+                    resolution: 16,
+                },
+                Target::Equal(0.0),
+                1.0,
+            )
+        }));
+
+        system.variables = vec![
+            Variable::new(0, Parameter::Curvature),
+            Variable::new(1, Parameter::Curvature),
+            Variable::new(2, Parameter::Curvature),
+            Variable::new(2, Parameter::Thickness).bounded(Bounds::new(5.0, 20.0)),
+        ];
+        system.constraints = vec![
+            Constraint::EdgeThickness {
+                surface: 0,
+                bounds: Bounds::at_least(0.5),
+            },
+            Constraint::TotalTrack {
+                bounds: Bounds::at_most(13.0),
+            },
+        ];
+
+        let query = optimization::Query::new(MeritFunction::new(operands));
+
+        // Residual derivatives from the dual number tracer, finite differences for the focal length
+        let (_, jacobian) = query.merit_function.jacobian(&system).unwrap();
+        println!("jacobian: {jacobian:.6}");
+
+        let response = system.optimize(&query).await.unwrap();
+
+        println!("{response}");
+        println!("{}", query.merit_function.report(&response.merit));
+        response.plot().save("../images/optimization.svg").unwrap();
+
+        {
+            use tolerance::{Criterion, Perturbation, Tolerance};
+
+            let tolerances = (0..3)
+                .flat_map(|surface| {
+                    [
+                        Tolerance::new(surface, Perturbation::Radius, 0.05),
+                        Tolerance::new(surface, Perturbation::DecenterY, 0.01),
+                        Tolerance::new(surface, Perturbation::TiltX, 1e-3),
+                    ]
+                })
+                .chain((0..2).flat_map(|surface| {
+                    [
+                        Tolerance::new(surface, Perturbation::Thickness, 0.02),
+                        Tolerance::new(surface, Perturbation::Index, 1e-3),
+                        Tolerance::new(surface, Perturbation::Abbe, 0.5),
+                    ]
+                }))
+                .collect::<Vec<_>>();
+
+            for criterion in [
+                Criterion::RmsSpot,
+                Criterion::RmsWavefront,
+                Criterion::Mtf { frequency: 50.0 },
+            ] {
+                let sensitivity = response
+                    .system
+                    .sensitivity(&tolerance::Query {
+                        criterion,
+                        tolerances: tolerances.clone(),
+                        resolution: 16,
+                    })
+                    .unwrap();
+                println!("{sensitivity}");
+            }
+
+            let inverse = response
+                .system
+                .inverse_tolerance(&tolerance::inverse::Query {
+                    criterion: Criterion::RmsSpot,
+                    tolerances: tolerances.clone(),
+                    budget: 0.002,
+                    resolution: 16,
+                })
+                .unwrap();
+            println!("{inverse}");
+            inverse.save("../images/tolerances.csv").unwrap();
+
+            // Refocused on every trial
+            let back_focus = response.system.surfaces.len() - 2;
+            let monte_carlo = response
+                .system
+                .monte_carlo(&tolerance::monte_carlo::Query {
+                    distribution: tolerance::monte_carlo::Distribution::Normal { sigmas: 2.0 },
+                    compensators: vec![
+                        Variable::new(back_focus, Parameter::Thickness)
+                            .bounded(Bounds::new(5.0, 20.0)),
+                    ],
+                    trials: 16,
+                    resolution: 8,
+                    ..tolerance::monte_carlo::Query::new(Criterion::RmsSpot, tolerances.clone())
+                })
+                .unwrap();
+            println!("{monte_carlo}");
+            monte_carlo
+                .plot()
+                .save("../images/monte_carlo.svg")
+                .unwrap();
+        }
+
+        let search = system.spawn_global_optimization(optimization::global::Query {
+            local: query,
+            strategy: optimization::global::Strategy::SimulatedAnnealing {
+                steps: 50,
+                proposals: 32,
+                temperature: 1.0,
+                cooling: 0.9,
+            },
+            keep: 5,
+            seed: 0,
+        });
+        println!("{}", search.join().await.unwrap());
+    }
+}
@@ -0,0 +1,44 @@
+//! Loads a prescription and traces a tangential fan of every field with [`System::trace`],
+//! printing where the rays land on the image surface.
+
+use light::{
+    compute::raytracing::Query,
+    system::{Ray, System},
+};
+use nalgebra::Point2;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "prescriptions/doublet.toml".to_string());
+    let system = System::load(&path, &[])?;
+
+    let pupil = [-1.0, -0.5, 0.0, 0.5, 1.0];
+    let rays = system
+        .fields
+        .iter()
+        .flat_map(|field| {
+            pupil
+                .iter()
+                .map(|y| system.field_ray(field, Point2::new(0.0, *y)))
+        })
+        .collect::<Vec<Ray>>();
+    let response = system.trace(&Query { rays }).await?;
+
+    let n_surfaces = system.surfaces.len();
+    println!("{:>12} {:>8} {:>12}", "field", "pupil", "image y");
+    for (i, path) in response.intersections.chunks(n_surfaces).enumerate() {
+        let field = &system.fields[i / pupil.len()];
+        let point = path[n_surfaces - 1].point();
+
+        println!(
+            "{:>12.6} {:>8.3} {:>12.6}",
+            field.point.y,
+            pupil[i % pupil.len()],
+            point.y
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Paraxial focus against the wavelength.
+
+use std::fmt;
+
+use crate::{
+    system::System,
+    utils::{self, Plot},
+};
+
+/// Wavelength range the paraxial focus is computed over.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Shortest wavelength, in micrometres.
+    pub min_wavelength: f32,
+    /// Longest wavelength, in micrometres.
+    pub max_wavelength: f32,
+    /// Wavelength whose paraxial focus is the reference.
+    pub reference: f32,
+    /// Number of wavelengths sampled across the range.
+    pub resolution: u32,
+}
+
+/// Paraxial focus shift of every sampled wavelength.
+#[derive(Debug)]
+pub struct Response {
+    /// Reference wavelength, in micrometres.
+    pub reference: f32,
+    /// `(wavelength, shift)` pairs, the shift being the paraxial focus minus the reference one.
+    pub points: Vec<(f32, f32)>,
+}
+
+impl Response {
+    /// Spread of the paraxial focus over the wavelength range.
+    pub fn max_shift(&self) -> f32 {
+        let shifts = self.points.iter().map(|(_, shift)| *shift);
+
+        shifts.clone().fold(f32::NEG_INFINITY, f32::max) - shifts.fold(f32::INFINITY, f32::min)
+    }
+
+    /// Plots the focal shift against the wavelength.
+    pub fn plot(&self) -> Plot {
+        let mut plot = Plot::new(
+            "Chromatic focal shift",
+            "Wavelength (µm)",
+            "Focal shift (mm)",
+        );
+
+        plot.line(
+            format!("ref. {:.4} µm", self.reference),
+            self.points.clone(),
+            "white",
+        );
+        for &(wavelength, shift) in &self.points {
+            plot.marker(wavelength, shift, utils::wavelength_color(wavelength));
+        }
+
+        plot
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>12} {:>14}", "wavelength", "focal shift")?;
+        for (wavelength, shift) in &self.points {
+            writeln!(f, "{wavelength:>12.4} {shift:>14.6}")?;
+        }
+        write!(f, "Maximum focal shift range: {:.6}", self.max_shift())
+    }
+}
+
+impl System {
+    /// Paraxial focus position over a wavelength range, relative to the reference focus.
+    pub fn chromatic_focal_shift(&self, query: &Query) -> Response {
+        let system = &*self.solved();
+        let reference = system.paraxial_focus(query.reference);
+        let n = query.resolution.max(2);
+
+        let points = (0..n)
+            .map(|i| {
+                let t = i as f32 / (n - 1) as f32;
+                let wavelength =
+                    query.min_wavelength + t * (query.max_wavelength - query.min_wavelength);
+
+                (wavelength, system.paraxial_focus(wavelength) - reference)
+            })
+            .collect();
+
+        Response {
+            reference: query.reference,
+            points,
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Distortion of a grid of object points.
+
+use std::fmt;
+
+use nalgebra::Point2;
+
+use crate::{
+    compute,
+    system::System,
+    utils::{Plot, Series},
+};
+
+/// Grid of object points whose chief rays are traced.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Half extent of the object grid along x.
+    pub half_width: f32,
+    /// Half extent of the object grid along y.
+    pub half_height: f32,
+    /// Number of grid points on each side.
+    pub resolution: u32,
+}
+
+/// Object point along with its paraxial and real images.
+#[derive(Debug, Clone, Copy)]
+pub struct GridPoint {
+    /// Point on the object plane.
+    pub object: Point2<f32>,
+    /// Paraxial image position.
+    pub ideal: Point2<f32>,
+    /// Chief ray intersection with the last surface.
+    pub real: Point2<f32>,
+}
+
+impl GridPoint {
+    /// Radial distortion in percent, zero on axis.
+    pub fn distortion(&self) -> f32 {
+        let ideal = self.ideal.coords.norm();
+
+        if ideal <= f32::EPSILON {
+            0.0
+        } else {
+            100.0 * (self.real.coords.norm() - ideal) / ideal
+        }
+    }
+}
+
+/// Images of every grid point.
+#[derive(Debug)]
+pub struct Response {
+    /// Number of grid points on each side.
+    pub resolution: u32,
+    /// Grid points in row-major order, rows going from -y to +y and columns from -x to +x.
+    pub points: Vec<GridPoint>,
+}
+
+impl Response {
+    /// Grid points row by row.
+    pub fn rows(&self) -> impl Iterator<Item = &[GridPoint]> {
+        self.points.chunks(self.resolution as usize)
+    }
+
+    /// Grid points column by column.
+    pub fn columns(&self) -> impl Iterator<Item = Vec<GridPoint>> + '_ {
+        let n = self.resolution as usize;
+
+        (0..n).map(move |i| self.points.iter().skip(i).step_by(n).copied().collect())
+    }
+
+    /// Largest radial distortion of the grid, in percent, keeping its sign.
+    pub fn max_distortion(&self) -> f32 {
+        self.points
+            .iter()
+            .map(GridPoint::distortion)
+            .filter(|distortion| distortion.is_finite())
+            .fold(0.0, |max, distortion| {
+                if distortion.abs() > max.abs() {
+                    distortion
+                } else {
+                    max
+                }
+            })
+    }
+
+    /// Difference between the top and bottom image widths over their mean, in percent.
+    pub fn keystone(&self) -> f32 {
+        let width = |row: &[GridPoint]| row[row.len() - 1].real.x - row[0].real.x;
+
+        let (Some(bottom), Some(top)) = (self.rows().next(), self.rows().last()) else {
+            return 0.0;
+        };
+        let (bottom, top) = (width(bottom), width(top));
+
+        200.0 * (top - bottom) / (top + bottom)
+    }
+
+    /// Largest bow of an image row over the full image height, in percent.
+    pub fn smile(&self) -> f32 {
+        let n = self.resolution as usize;
+        let Some(height) = self
+            .columns()
+            .nth((n - 1) / 2)
+            .map(|column| (column[n - 1].ideal.y - column[0].ideal.y).abs())
+        else {
+            return 0.0;
+        };
+
+        self.rows()
+            .map(|row| {
+                let ends = 0.5 * (row[0].real.y + row[n - 1].real.y);
+                let middle = 0.5 * (row[(n - 1) / 2].real.y + row[n / 2].real.y);
+
+                100.0 * (middle - ends) / height
+            })
+            .filter(|smile| smile.is_finite())
+            .fold(
+                0.0,
+                |max, smile| {
+                    if smile.abs() > max.abs() { smile } else { max }
+                },
+            )
+    }
+
+    /// Draws the real grid over the ideal one.
+    pub fn plot(&self) -> Plot {
+        let mut plot = Plot::new("Grid distortion", "x (mm)", "y (mm)");
+        plot.equal_aspect = true;
+
+        let lines = self.rows().map(<[GridPoint]>::to_vec).chain(self.columns());
+
+        for line in lines {
+            plot.add_series(Series {
+                label: None,
+                points: line.iter().map(|p| (p.ideal.x, p.ideal.y)).collect(),
+                color: "gray",
+                dashed: true,
+            });
+            plot.add_series(Series {
+                label: None,
+                points: line.iter().map(|p| (p.real.x, p.real.y)).collect(),
+                color: "cyan",
+                dashed: false,
+            });
+        }
+
+        plot
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>10} {:>10} {:>12} {:>12} {:>12} {:>12} {:>12}",
+            "x", "y", "ideal x", "ideal y", "real x", "real y", "distortion"
+        )?;
+
+        for point in &self.points {
+            writeln!(
+                f,
+                "{:>10.4} {:>10.4} {:>12.6} {:>12.6} {:>12.6} {:>12.6} {:>11.4}%",
+                point.object.x,
+                point.object.y,
+                point.ideal.x,
+                point.ideal.y,
+                point.real.x,
+                point.real.y,
+                point.distortion()
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Max distortion: {:>10.4}%", self.max_distortion())?;
+        writeln!(f, "Keystone:       {:>10.4}%", self.keystone())?;
+        write!(f, "Smile:          {:>10.4}%", self.smile())
+    }
+}
+
+impl System {
+    /// Traces the chief ray of every point of a rectangular object grid and compares the image
+    /// positions on the last surface with the paraxial ones.
+    pub async fn grid_distortion(&self, query: &Query) -> anyhow::Result<Response> {
+        let system = &*self.solved();
+        anyhow::ensure!(!system.surfaces.is_empty(), "the system has no surfaces");
+        anyhow::ensure!(query.resolution >= 2, "grid resolution must be at least 2");
+
+        let n = query.resolution as usize;
+        let step = |i: usize| 2.0 * i as f32 / (n - 1) as f32 - 1.0;
+
+        let objects = (0..n)
+            .flat_map(|j| {
+                (0..n).map(move |i| {
+                    Point2::new(step(i) * query.half_width, step(j) * query.half_height)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let rays = objects
+            .iter()
+            .map(|object| system.pupil_ray(*object, Point2::origin()))
+            .collect();
+
+        let response = system.trace(&compute::raytracing::Query { rays }).await?;
+
+        let magnification = system.paraxial_magnification();
+        let n_surfaces = system.surfaces.len();
+
+        let points = objects
+            .into_iter()
+            .zip(response.intersections.chunks(n_surfaces))
+            .map(|(object, intersections)| {
+                let image = intersections[n_surfaces - 1].point();
+
+                GridPoint {
+                    object,
+                    ideal: object * magnification,
+                    real: image.xy(),
+                }
+            })
+            .collect();
+
+        Ok(Response {
+            resolution: query.resolution,
+            points,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::Solve;
+
+    #[tokio::test]
+    async fn uses_solved_back_focus() {
+        let mut system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        system.surfaces[2].thickness = 15.0;
+        system.solves = vec![Solve::MarginalHeight {
+            surface: 2,
+            height: 0.0,
+        }];
+        let mut explicit = system.solved().into_owned();
+        explicit.solves.clear();
+        assert!((explicit.surfaces[2].thickness - 15.0).abs() > 1.0);
+
+        let query = Query {
+            half_width: 600.0,
+            half_height: 600.0,
+            resolution: 3,
+        };
+        let solved = system.grid_distortion(&query).await.unwrap();
+        let expected = explicit.grid_distortion(&query).await.unwrap();
+        for (point, expected) in solved.points.iter().zip(&expected.points) {
+            assert!((point.ideal - expected.ideal).norm() < 1e-4, "{point:?}");
+            assert!((point.real - expected.real).norm() < 1e-4, "{point:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn thin_lens_is_distortion_free() {
+        // The chief rays cross a thin lens with the stop on it at its vertex, undeviated, and
+        // the image is inverted with a magnification of -50 / 200
+        let system = System::from_toml(
+            r#"
+            stop = 0
+
+            [object]
+            distance = 200.0
+            semi_diameter = 10.0
+            material = 1.0
+
+            [[wavelengths]]
+            value = 0.5875618
+            weight = 1.0
+
+            [[fields]]
+            x = 0.0
+            y = 0.0
+
+            [[surfaces]]
+            radius = 20.0
+            thickness = 0.0
+            semi_diameter = 5.0
+            material = 1.5
+
+            [[surfaces]]
+            radius = -50.0
+            thickness = 50.0
+            semi_diameter = 5.0
+            material = 1.0
+
+            [[surfaces]]
+            thickness = 0.0
+            semi_diameter = 10.0
+            material = 1.0
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        let response = system
+            .grid_distortion(&Query {
+                half_width: 10.0,
+                half_height: 10.0,
+                resolution: 5,
+            })
+            .await
+            .unwrap();
+        let corner = response.points[0];
+        assert!((corner.ideal.x - 2.5).abs() < 1e-4, "{corner:?}");
+        assert!(response.max_distortion().abs() < 1e-3, "{response}");
+        assert!(response.keystone().abs() < 1e-3, "{response}");
+        assert!(response.smile().abs() < 1e-3, "{response}");
+    }
+}
@@ -0,0 +1,208 @@
+//! Lateral color over the field and paraxial longitudinal color.
+
+use std::fmt;
+
+use nalgebra::Point2;
+
+use crate::{
+    compute,
+    system::System,
+    utils::{self, Plot, Series},
+};
+
+/// Wavelengths compared, in micrometres.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Short wavelength.
+    pub short: f32,
+    /// Primary wavelength.
+    pub primary: f32,
+    /// Long wavelength.
+    pub long: f32,
+    /// Number of field heights sampled between the axis and the object edge.
+    pub resolution: u32,
+}
+
+/// Chief ray heights of one field height.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldPoint {
+    /// Normalized field height.
+    pub field: f32,
+    /// Chief ray height on the last surface at the primary wavelength.
+    pub height: f32,
+    /// Short wavelength chief ray height minus the primary one.
+    pub short: f32,
+    /// Long wavelength chief ray height minus the primary one.
+    pub long: f32,
+}
+
+impl FieldPoint {
+    /// Difference between the short and long wavelength image heights.
+    pub fn lateral_color(&self) -> f32 {
+        self.short - self.long
+    }
+}
+
+/// Lateral color over the field, along with the paraxial color.
+#[derive(Debug)]
+pub struct Response {
+    /// Query the response answers.
+    pub query: Query,
+    /// Sampled field heights, from the axis to the object edge.
+    pub fields: Vec<FieldPoint>,
+    /// Airy disk radius at the primary wavelength.
+    pub airy_radius: f32,
+    /// Paraxial focus of the short wavelength minus the long one.
+    pub primary_spectrum: f32,
+    /// Paraxial focus of the primary wavelength minus the mean of the short and long ones.
+    pub secondary_spectrum: f32,
+}
+
+impl Response {
+    /// Largest lateral color over the field, keeping its sign.
+    pub fn max_lateral_color(&self) -> f32 {
+        self.fields
+            .iter()
+            .map(FieldPoint::lateral_color)
+            .filter(|value| value.is_finite())
+            .fold(
+                0.0,
+                |max, value| if value.abs() > max.abs() { value } else { max },
+            )
+    }
+
+    /// Whether the lateral color stays inside the Airy disk over the whole field.
+    pub fn within_airy_disk(&self) -> bool {
+        self.max_lateral_color().abs() <= self.airy_radius
+    }
+
+    /// Plots the chief ray height differences, in micrometres, against the field.
+    pub fn plot(&self) -> Plot {
+        let mut plot = Plot::new("Lateral color", "Height difference (µm)", "Field");
+        let curve = |value: fn(&FieldPoint) -> f32| {
+            self.fields
+                .iter()
+                .map(|point| (1e3 * value(point), point.field))
+                .collect::<Vec<_>>()
+        };
+
+        plot.line(
+            format!("{:.4} µm", self.query.short),
+            curve(|point| point.short),
+            utils::wavelength_color(self.query.short),
+        );
+        plot.line(
+            format!("{:.4} µm", self.query.long),
+            curve(|point| point.long),
+            utils::wavelength_color(self.query.long),
+        );
+        plot.line("short - long", curve(FieldPoint::lateral_color), "white");
+
+        // Both edges of the Airy disk, sharing one legend entry
+        let edge = |sign: f32| {
+            self.fields
+                .iter()
+                .map(|point| (sign * 1e3 * self.airy_radius, point.field))
+                .collect()
+        };
+        plot.dashed_line("Airy disk", edge(-1.0), "gray");
+        plot.add_series(Series {
+            label: None,
+            points: edge(1.0),
+            color: "gray",
+            dashed: true,
+        });
+
+        plot
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>12} {:>12} {:>12} {:>12}",
+            "field", "height", "short", "long", "lateral"
+        )?;
+
+        for point in &self.fields {
+            writeln!(
+                f,
+                "{:>8.4} {:>12.6} {:>12.6} {:>12.6} {:>12.6}",
+                point.field,
+                point.height,
+                point.short,
+                point.long,
+                point.lateral_color()
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Airy disk radius:   {:>12.6}", self.airy_radius)?;
+        writeln!(f, "Max lateral color:  {:>12.6}", self.max_lateral_color())?;
+        writeln!(f, "Primary spectrum:   {:>12.6}", self.primary_spectrum)?;
+        write!(f, "Secondary spectrum: {:>12.6}", self.secondary_spectrum)
+    }
+}
+
+impl System {
+    /// Traces the chief ray of several field heights at three wavelengths and compares the image
+    /// heights on the last surface.
+    pub async fn lateral_color(&self, query: &Query) -> anyhow::Result<Response> {
+        let system = &*self.solved();
+        anyhow::ensure!(!system.surfaces.is_empty(), "the system has no surfaces");
+        let n = query.resolution.max(2);
+        let fields = (0..n)
+            .map(|i| i as f32 / (n - 1) as f32)
+            .collect::<Vec<_>>();
+        let n_surfaces = system.surfaces.len();
+
+        // All three wavelengths in a single dispatch, grouped by wavelength
+        let rays = [query.short, query.primary, query.long]
+            .into_iter()
+            .flat_map(|wavelength| {
+                fields.iter().map(move |field| {
+                    let object = Point2::new(0.0, field * system.object.semi_diameter);
+                    system
+                        .pupil_ray(object, Point2::origin())
+                        .with_wavelength(wavelength)
+                })
+            })
+            .collect();
+
+        let response = system.trace(&compute::raytracing::Query { rays }).await?;
+
+        let heights = response
+            .intersections
+            .chunks(n_surfaces * fields.len())
+            .map(|group| {
+                group
+                    .chunks(n_surfaces)
+                    .map(|intersections| intersections[n_surfaces - 1].point().y)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let fields = fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| FieldPoint {
+                field: *field,
+                height: heights[1][i],
+                short: heights[0][i] - heights[1][i],
+                long: heights[2][i] - heights[1][i],
+            })
+            .collect();
+
+        let [short, primary, long] = [query.short, query.primary, query.long]
+            .map(|wavelength| system.paraxial_focus(wavelength));
+
+        Ok(Response {
+            query: query.clone(),
+            fields,
+            airy_radius: 1.22e-3 * query.primary * system.working_f_number(query.primary),
+            primary_spectrum: short - long,
+            secondary_spectrum: primary - 0.5 * (short + long),
+        })
+    }
+}
@@ -0,0 +1,191 @@
+//! Longitudinal aberration against the pupil zone.
+
+use std::fmt;
+
+use nalgebra::Point2;
+
+use crate::{
+    compute,
+    system::System,
+    utils::{self, Plot},
+};
+
+/// Wavelengths and pupil zones the aberration is traced at.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Wavelengths traced, in micrometres.
+    pub wavelengths: Vec<f32>,
+    /// Index of the wavelength whose paraxial focus is the reference.
+    pub reference: usize,
+    /// Number of pupil zones traced between the axis and the pupil edge.
+    pub resolution: u32,
+}
+
+/// Longitudinal aberration of one wavelength.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    /// Wavelength, in micrometres.
+    pub wavelength: f32,
+    /// Paraxial focus relative to the reference focus.
+    pub paraxial_focus: f32,
+    /// `(zone, aberration)` pairs, zones being normalized pupil heights.
+    pub points: Vec<(f32, f32)>,
+}
+
+/// Longitudinal aberration of every wavelength.
+#[derive(Debug)]
+pub struct Response {
+    /// Curves of the wavelengths, in query order.
+    pub curves: Vec<Curve>,
+}
+
+impl Response {
+    /// Plots the focus position against the pupil zone for every wavelength.
+    pub fn plot(&self) -> Plot {
+        let mut plot = Plot::new(
+            "Longitudinal aberration",
+            "Longitudinal aberration (mm)",
+            "Pupil zone",
+        );
+
+        for curve in &self.curves {
+            plot.line(
+                format!("{:.4} µm", curve.wavelength),
+                curve
+                    .points
+                    .iter()
+                    .map(|&(zone, lsa)| (lsa, zone))
+                    .collect(),
+                utils::wavelength_color(curve.wavelength),
+            );
+        }
+
+        plot
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>8}", "zone")?;
+        for curve in &self.curves {
+            write!(f, " {:>12}", format!("{:.4} µm", curve.wavelength))?;
+        }
+        writeln!(f)?;
+
+        let n_zones = self.curves.first().map_or(0, |curve| curve.points.len());
+        for i in 0..n_zones {
+            write!(f, "{:>8.4}", self.curves[0].points[i].0)?;
+            for curve in &self.curves {
+                write!(f, " {:>12.6}", curve.points[i].1)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl System {
+    /// Traces real marginal rays of the axial field and returns where they cross the axis,
+    /// relative to the paraxial focus of the reference wavelength.
+    pub async fn longitudinal_aberration(&self, query: &Query) -> anyhow::Result<Response> {
+        let system = &*self.solved();
+        anyhow::ensure!(!system.surfaces.is_empty(), "the system has no surfaces");
+        let reference = query
+            .wavelengths
+            .get(query.reference)
+            .ok_or_else(|| anyhow::anyhow!("reference wavelength out of range"))?;
+        let reference = system.paraxial_focus(*reference);
+
+        let n = query.resolution.max(1);
+        let zones = (1..=n).map(|i| i as f32 / n as f32).collect::<Vec<_>>();
+
+        let n_surfaces = system.surfaces.len();
+        let last = system.surfaces[..n_surfaces - 1]
+            .iter()
+            .map(|surface| surface.thickness)
+            .sum::<f32>();
+
+        // Every wavelength in a single dispatch, grouped by wavelength
+        let rays = query
+            .wavelengths
+            .iter()
+            .flat_map(|&wavelength| {
+                zones.iter().map(move |zone| {
+                    system
+                        .pupil_ray(Point2::origin(), Point2::new(0.0, *zone))
+                        .with_wavelength(wavelength)
+                })
+            })
+            .collect();
+
+        let response = system.trace(&compute::raytracing::Query { rays }).await?;
+
+        let mut curves = Vec::with_capacity(query.wavelengths.len());
+        for (&wavelength, group) in query
+            .wavelengths
+            .iter()
+            .zip(response.intersections.chunks(n_surfaces * zones.len()))
+        {
+            let paraxial_focus = system.paraxial_focus(wavelength) - reference;
+            let points =
+                std::iter::once((0.0, paraxial_focus))
+                    .chain(zones.iter().zip(group.chunks(n_surfaces)).map(
+                        |(zone, intersections)| {
+                            let ray = &intersections[n_surfaces - 1].ray;
+                            let crossing =
+                                ray.origin.z - ray.origin.y * ray.direction.z / ray.direction.y;
+
+                            (*zone, crossing - last - reference)
+                        },
+                    ))
+                    .collect();
+
+            curves.push(Curve {
+                wavelength,
+                paraxial_focus,
+                points,
+            });
+        }
+
+        Ok(Response { curves })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{grid_distortion, lateral_color};
+
+    #[tokio::test]
+    async fn rejects_empty_systems() {
+        let mut system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        system.surfaces.clear();
+        let message = |error: anyhow::Error| format!("{error:#}");
+
+        let query = Query {
+            wavelengths: vec![0.5875618],
+            reference: 0,
+            resolution: 4,
+        };
+        let error = system.longitudinal_aberration(&query).await.unwrap_err();
+        assert_eq!(message(error), "the system has no surfaces");
+
+        let query = grid_distortion::Query {
+            half_width: 1.0,
+            half_height: 1.0,
+            resolution: 3,
+        };
+        let error = system.grid_distortion(&query).await.unwrap_err();
+        assert_eq!(message(error), "the system has no surfaces");
+
+        let query = lateral_color::Query {
+            short: 0.4861327,
+            primary: 0.5875618,
+            long: 0.6562725,
+            resolution: 4,
+        };
+        let error = system.lateral_color(&query).await.unwrap_err();
+        assert_eq!(message(error), "the system has no surfaces");
+    }
+}
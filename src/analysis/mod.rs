@@ -0,0 +1,12 @@
+//! Analyses of a system, each a `Query` answered by a `System` method with a printable,
+//! plottable `Response`.
+
+pub mod chromatic_focal_shift;
+pub mod grid_distortion;
+pub mod lateral_color;
+pub mod longitudinal;
+pub mod mtf;
+pub mod ray_fan;
+pub mod seidel;
+pub mod spot_diagram;
+pub mod vignetting;
@@ -0,0 +1,211 @@
+//! Polychromatic geometric modulation transfer function.
+
+use std::{f32::consts::PI, fmt};
+
+use super::spot_diagram::pupil_grid;
+use crate::{
+    compute,
+    system::{Intersection, Ray, System},
+    utils::{Plot, Series},
+};
+
+/// Colors of the field curves, cycled through.
+const COLORS: [&str; 6] = ["white", "orange", "lime", "cyan", "violet", "yellow"];
+
+/// Fields and frequencies the MTF is computed at.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Indices of the traced fields.
+    pub fields: Vec<usize>,
+    /// Number of samples across the pupil diameter.
+    pub resolution: u32,
+    /// Highest spatial frequency, in cycles per lens unit.
+    pub max_frequency: f32,
+    /// Number of frequencies sampled from zero to the highest one.
+    pub steps: u32,
+}
+
+/// MTF of one field.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    /// Index of the field.
+    pub field: usize,
+    /// Sampled spatial frequencies, in cycles per lens unit.
+    pub frequencies: Vec<f32>,
+    /// Modulation of lines along the image x axis, varying along y.
+    pub tangential: Vec<f32>,
+    /// Modulation of lines along the image y axis, varying along x.
+    pub sagittal: Vec<f32>,
+}
+
+/// MTF of every selected field.
+#[derive(Debug)]
+pub struct Response {
+    /// Query the response answers.
+    pub query: Query,
+    /// Curves of the fields, in query order.
+    pub curves: Vec<Curve>,
+}
+
+impl Response {
+    /// Plots the curves of every field, sagittal ones dashed.
+    pub fn plot(&self) -> Plot {
+        let mut plot = Plot::new("Geometric MTF", "Frequency (cycles/mm)", "Modulation");
+
+        for (i, curve) in self.curves.iter().enumerate() {
+            let color = COLORS[i % COLORS.len()];
+            let points = |values: &[f32]| {
+                curve
+                    .frequencies
+                    .iter()
+                    .copied()
+                    .zip(values.iter().copied())
+                    .collect::<Vec<_>>()
+            };
+
+            plot.line(
+                format!("field {}", curve.field),
+                points(&curve.tangential),
+                color,
+            );
+            plot.add_series(Series {
+                label: None,
+                points: points(&curve.sagittal),
+                color,
+                dashed: true,
+            });
+        }
+
+        plot
+    }
+
+    /// One row per field and frequency.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("field,frequency,tangential,sagittal\n");
+        for curve in &self.curves {
+            for i in 0..curve.frequencies.len() {
+                csv += &format!(
+                    "{},{},{},{}\n",
+                    curve.field, curve.frequencies[i], curve.tangential[i], curve.sagittal[i]
+                );
+            }
+        }
+
+        csv
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:>12} {:>12} {:>12}",
+            "field", "frequency", "tangential", "sagittal"
+        )?;
+
+        for curve in &self.curves {
+            for i in 0..curve.frequencies.len() {
+                writeln!(
+                    f,
+                    "{:>6} {:>12.3} {:>12.6} {:>12.6}",
+                    curve.field, curve.frequencies[i], curve.tangential[i], curve.sagittal[i]
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Weighted image points of the rays that made it through, from their paths.
+pub fn image_points(paths: &[Intersection], n_surfaces: usize) -> Vec<(f32, f32, f32)> {
+    paths
+        .chunks(n_surfaces)
+        .filter_map(|path| {
+            let last = path.last()?;
+            let point = last.point();
+
+            (point.x.is_finite() && point.y.is_finite()).then_some((
+                point.x,
+                point.y,
+                last.ray.weight,
+            ))
+        })
+        .collect()
+}
+
+/// Tangential and sagittal geometric MTF of weighted image points at `frequency`, the modulus of
+/// the Fourier transform of their line spread functions.
+pub fn geometric_mtf(points: &[(f32, f32, f32)], frequency: f32) -> (f32, f32) {
+    let total = points.iter().map(|(_, _, weight)| weight).sum::<f32>();
+    let modulation = |coordinate: fn(&(f32, f32, f32)) -> f32| {
+        let (re, im) = points.iter().fold((0.0, 0.0), |(re, im), point| {
+            let phase = 2.0 * PI * frequency * coordinate(point);
+
+            (re + point.2 * phase.cos(), im + point.2 * phase.sin())
+        });
+
+        (re * re + im * im).sqrt() / total
+    };
+
+    (modulation(|point| point.1), modulation(|point| point.0))
+}
+
+impl System {
+    /// Polychromatic geometric MTF of the selected fields, from a grid of pupil rays traced at
+    /// every system wavelength in a single dispatch.
+    pub async fn mtf(&self, query: &Query) -> anyhow::Result<Response> {
+        let system = &*self.solved();
+        anyhow::ensure!(!system.surfaces.is_empty(), "the system has no surfaces");
+        if let Some(field) = query.fields.iter().find(|f| **f >= system.fields.len()) {
+            anyhow::bail!("no field {field}, the system has {}", system.fields.len());
+        }
+
+        let pupil = pupil_grid(query.resolution);
+        let rays = query
+            .fields
+            .iter()
+            .flat_map(|&field| {
+                let rays = pupil
+                    .iter()
+                    .map(|point| system.field_ray(&system.fields[field], *point))
+                    .collect::<Vec<_>>();
+
+                system.polychromatic_rays(&rays)
+            })
+            .collect::<Vec<Ray>>();
+        let n_rays = rays.len() / query.fields.len().max(1);
+
+        let n_surfaces = system.surfaces.len();
+        let response = system.trace(&compute::raytracing::Query { rays }).await?;
+        let steps = query.steps.max(2);
+        let frequencies = (0..steps)
+            .map(|i| query.max_frequency * i as f32 / (steps - 1) as f32)
+            .collect::<Vec<_>>();
+
+        let curves = query
+            .fields
+            .iter()
+            .zip(response.intersections.chunks(n_surfaces * n_rays.max(1)))
+            .map(|(&field, paths)| {
+                let points = image_points(paths, n_surfaces);
+                let (tangential, sagittal) = frequencies
+                    .iter()
+                    .map(|frequency| geometric_mtf(&points, *frequency))
+                    .unzip();
+
+                Curve {
+                    field,
+                    frequencies: frequencies.clone(),
+                    tangential,
+                    sagittal,
+                }
+            })
+            .collect();
+
+        Ok(Response {
+            query: query.clone(),
+            curves,
+        })
+    }
+}
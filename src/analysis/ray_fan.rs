@@ -0,0 +1,181 @@
+//! Transverse ray aberration fans.
+
+use std::fmt;
+
+use nalgebra::Point2;
+
+use super::spot_diagram::groups;
+use crate::{
+    compute,
+    system::{Ray, System},
+    utils::{self, Plot, Series},
+};
+
+/// Fields and wavelengths whose fans are traced.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Indices of the traced fields.
+    pub fields: Vec<usize>,
+    /// Indices of the traced system wavelengths.
+    pub wavelengths: Vec<usize>,
+    /// Number of pupil samples across each fan.
+    pub resolution: u32,
+}
+
+/// Transverse aberrations of one field and wavelength, relative to the chief ray at the primary
+/// wavelength.
+#[derive(Debug, Clone)]
+pub struct Fan {
+    /// Index of the field.
+    pub field: usize,
+    /// Wavelength, in micrometres.
+    pub wavelength: f32,
+    /// Normalized pupil coordinates of the samples.
+    pub pupil: Vec<f32>,
+    /// Image y error of the rays along the pupil y axis.
+    pub tangential: Vec<f32>,
+    /// Image x error of the rays along the pupil x axis.
+    pub sagittal: Vec<f32>,
+}
+
+/// Ray fans of every selected field and wavelength.
+#[derive(Debug)]
+pub struct Response {
+    /// Query the response answers.
+    pub query: Query,
+    /// Fans grouped by field, then by wavelength.
+    pub fans: Vec<Fan>,
+}
+
+impl Response {
+    /// Plots the fans of `field` in micrometres, sagittal ones dashed.
+    pub fn plot(&self, field: usize) -> Plot {
+        let mut plot = Plot::new(
+            format!("Ray fan, field {field}"),
+            "Pupil coordinate",
+            "Transverse aberration (µm)",
+        );
+        let curve = |pupil: &[f32], errors: &[f32]| {
+            pupil
+                .iter()
+                .zip(errors)
+                .map(|(pupil, error)| (*pupil, 1e3 * error))
+                .collect::<Vec<_>>()
+        };
+
+        for fan in self.fans.iter().filter(|fan| fan.field == field) {
+            let color = utils::wavelength_color(fan.wavelength);
+            plot.line(
+                format!("{:.4} µm", fan.wavelength),
+                curve(&fan.pupil, &fan.tangential),
+                color,
+            );
+            plot.add_series(Series {
+                label: None,
+                points: curve(&fan.pupil, &fan.sagittal),
+                color,
+                dashed: true,
+            });
+        }
+
+        plot
+    }
+
+    /// One row per pupil sample.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("field,wavelength,pupil,tangential,sagittal\n");
+        for fan in &self.fans {
+            for i in 0..fan.pupil.len() {
+                csv += &format!(
+                    "{},{},{},{},{}\n",
+                    fan.field, fan.wavelength, fan.pupil[i], fan.tangential[i], fan.sagittal[i]
+                );
+            }
+        }
+
+        csv
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:>12} {:>8} {:>12} {:>12}",
+            "field", "wavelength", "pupil", "tangential", "sagittal"
+        )?;
+
+        for fan in &self.fans {
+            for i in 0..fan.pupil.len() {
+                writeln!(
+                    f,
+                    "{:>6} {:>12.4} {:>8.3} {:>12.6} {:>12.6}",
+                    fan.field, fan.wavelength, fan.pupil[i], fan.tangential[i], fan.sagittal[i]
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl System {
+    /// Traces tangential and sagittal fans for every selected field and wavelength, followed by
+    /// the primary chief ray of every field, in a single dispatch.
+    pub async fn ray_fan(&self, query: &Query) -> anyhow::Result<Response> {
+        let system = &*self.solved();
+        anyhow::ensure!(!system.surfaces.is_empty(), "the system has no surfaces");
+        let n = query.resolution.max(2);
+        let pupil = (0..n)
+            .map(|i| 2.0 * i as f32 / (n - 1) as f32 - 1.0)
+            .collect::<Vec<_>>();
+        let groups = groups(system, &query.fields, &query.wavelengths)?;
+
+        let fan_rays = groups.iter().flat_map(|&(field, wavelength)| {
+            let field = &system.fields[field];
+            let tangential = pupil.iter().map(|p| Point2::new(0.0, *p));
+            let sagittal = pupil.iter().map(|p| Point2::new(*p, 0.0));
+
+            tangential
+                .chain(sagittal)
+                .map(move |point| system.field_ray(field, point).with_wavelength(wavelength))
+        });
+        let chief_rays = query.fields.iter().map(|&field| {
+            system
+                .field_ray(&system.fields[field], Point2::origin())
+                .with_wavelength(system.primary_wavelength())
+        });
+        let rays = fan_rays.chain(chief_rays).collect::<Vec<Ray>>();
+
+        let n_surfaces = system.surfaces.len();
+        let response = system.trace(&compute::raytracing::Query { rays }).await?;
+        let image = response
+            .intersections
+            .chunks(n_surfaces)
+            .map(|path| path[n_surfaces - 1].point().xy())
+            .collect::<Vec<_>>();
+        let (fans, chiefs) = image.split_at(groups.len() * 2 * pupil.len());
+
+        let fans = groups
+            .iter()
+            .zip(fans.chunks(2 * pupil.len()))
+            .map(|(&(field, wavelength), points)| {
+                let chief = chiefs[query.fields.iter().position(|f| *f == field).unwrap_or(0)];
+                let (tangential, sagittal) = points.split_at(pupil.len());
+
+                Fan {
+                    field,
+                    wavelength,
+                    pupil: pupil.clone(),
+                    tangential: tangential.iter().map(|p| p.y - chief.y).collect(),
+                    sagittal: sagittal.iter().map(|p| p.x - chief.x).collect(),
+                }
+            })
+            .collect();
+
+        Ok(Response {
+            query: query.clone(),
+            fans,
+        })
+    }
+}
@@ -0,0 +1,311 @@
+//! Third order Seidel aberration sums.
+
+use std::{fmt, iter::Sum, ops::Add};
+
+use crate::{
+    system::{ParaxialRay, System},
+    utils::BarChart,
+};
+
+/// Wavelengths the sums are computed at, in micrometres.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Short wavelength of the chromatic sums.
+    pub short: f32,
+    /// Wavelength of the monochromatic sums.
+    pub primary: f32,
+    /// Long wavelength of the chromatic sums.
+    pub long: f32,
+}
+
+/// Third order aberration sums, in lens units.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Coefficients {
+    /// S_I
+    pub spherical: f32,
+    /// S_II
+    pub coma: f32,
+    /// S_III
+    pub astigmatism: f32,
+    /// S_IV, the Petzval sum.
+    pub field_curvature: f32,
+    /// S_V
+    pub distortion: f32,
+    /// C_L
+    pub axial_color: f32,
+    /// C_T
+    pub lateral_color: f32,
+}
+
+impl Coefficients {
+    /// Short names of the sums, in [`Coefficients::to_array`] order.
+    pub const LABELS: [&str; 7] = ["SI", "SII", "SIII", "SIV", "SV", "CL", "CT"];
+
+    /// The sums in [`Coefficients::LABELS`] order.
+    pub fn to_array(self) -> [f32; 7] {
+        [
+            self.spherical,
+            self.coma,
+            self.astigmatism,
+            self.field_curvature,
+            self.distortion,
+            self.axial_color,
+            self.lateral_color,
+        ]
+    }
+}
+
+impl Add for Coefficients {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            spherical: self.spherical + rhs.spherical,
+            coma: self.coma + rhs.coma,
+            astigmatism: self.astigmatism + rhs.astigmatism,
+            field_curvature: self.field_curvature + rhs.field_curvature,
+            distortion: self.distortion + rhs.distortion,
+            axial_color: self.axial_color + rhs.axial_color,
+            lateral_color: self.lateral_color + rhs.lateral_color,
+        }
+    }
+}
+
+impl Sum for Coefficients {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+/// Seidel sums of every surface.
+#[derive(Debug)]
+pub struct Response {
+    /// Contribution of every surface.
+    pub surfaces: Vec<Coefficients>,
+}
+
+impl Response {
+    /// Sums of the whole system.
+    pub fn total(&self) -> Coefficients {
+        self.surfaces.iter().copied().sum()
+    }
+
+    /// Bar chart of the contributions of every surface, followed by the total.
+    pub fn plot(&self) -> BarChart {
+        const COLORS: [&str; 7] = ["red", "orange", "yellow", "lime", "cyan", "blue", "magenta"];
+
+        let rows = self
+            .surfaces
+            .iter()
+            .copied()
+            .chain([self.total()])
+            .map(Coefficients::to_array)
+            .collect::<Vec<_>>();
+
+        let categories = (1..=self.surfaces.len())
+            .map(|i| i.to_string())
+            .chain(["Total".to_string()])
+            .collect();
+
+        let mut chart = BarChart::new("Seidel diagram", "Aberration sum", categories);
+        for (i, label) in Coefficients::LABELS.into_iter().enumerate() {
+            chart.bars(label, rows.iter().map(|row| row[i]).collect(), COLORS[i]);
+        }
+
+        chart
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>6}", "surf")?;
+        for label in Coefficients::LABELS {
+            write!(f, " {label:>12}")?;
+        }
+        writeln!(f)?;
+
+        let rows = self
+            .surfaces
+            .iter()
+            .enumerate()
+            .map(|(i, coefficients)| ((i + 1).to_string(), *coefficients))
+            .chain([("TOT".to_string(), self.total())]);
+
+        for (label, coefficients) in rows {
+            write!(f, "{label:>6}")?;
+            for value in coefficients.to_array() {
+                write!(f, " {value:>12.6}")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl System {
+    /// Computes the Seidel sums of every surface from the paraxial marginal and chief rays.
+    pub fn seidel(&self, query: &Query) -> Response {
+        let system = &*self.solved();
+        let marginal = system.paraxial_marginal_ray();
+        let chief = system.paraxial_chief_ray();
+
+        // Rays reaching each surface, i.e. with the slope before refraction
+        let incoming = |ray: ParaxialRay| {
+            let traced = system.paraxial_trace(ray, query.primary);
+            let slopes = std::iter::once(ray.slope).chain(traced.iter().map(|ray| ray.slope));
+
+            traced
+                .iter()
+                .zip(slopes)
+                .map(|(traced, slope)| ParaxialRay {
+                    height: traced.height,
+                    slope,
+                })
+                .collect::<Vec<_>>()
+        };
+        let marginal = incoming(marginal);
+        let chief = incoming(chief);
+
+        let mut n = system.object.refractive_index_at(query.primary);
+        let mut dn = system.object.refractive_index_at(query.short)
+            - system.object.refractive_index_at(query.long);
+
+        let surfaces = self
+            .surfaces
+            .iter()
+            .zip(marginal.iter().zip(&chief))
+            .map(|(surface, (marginal, chief))| {
+                let n1 = surface.refractive_index_at(query.primary);
+                let dn1 = surface.refractive_index_at(query.short)
+                    - surface.refractive_index_at(query.long);
+                let c = surface.curvature;
+
+                let (h, u) = (marginal.height, marginal.slope);
+                let (hb, ub) = (chief.height, chief.slope);
+
+                let a = n * (h * c + u);
+                let ab = n * (hb * c + ub);
+                let lagrange = n * (ub * h - u * hb);
+
+                let u1 = (n * u - h * c * (n1 - n)) / n1;
+                let delta_u = u1 / n1 - u / n;
+                let delta_n = 1.0 / n1 - 1.0 / n;
+                let delta_n2 = 1.0 / (n1 * n1) - 1.0 / (n * n);
+                let delta_dispersion = dn1 / n1 - dn / n;
+
+                // Aspheric contribution of the conic, shared by the field terms in powers of hb / h
+                let aspheric = surface.conic * c * c * c * h.powi(4) * (n1 - n);
+                let ratio = if aspheric == 0.0 { 0.0 } else { hb / h };
+
+                n = n1;
+                dn = dn1;
+
+                Coefficients {
+                    spherical: -a * a * h * delta_u + aspheric,
+                    coma: -a * ab * h * delta_u + aspheric * ratio,
+                    astigmatism: -ab * ab * h * delta_u + aspheric * ratio * ratio,
+                    field_curvature: -lagrange * lagrange * c * delta_n,
+                    // Equivalent to (ab / a) (S_III + S_IV), without dividing by the incidence
+                    distortion: -ab * ab * ab * h * delta_n2
+                        + ab * c * hb * (2.0 * h * ab - hb * a) * delta_n
+                        + aspheric * ratio * ratio * ratio,
+                    axial_color: a * h * delta_dispersion,
+                    lateral_color: ab * h * delta_dispersion,
+                }
+            })
+            .collect();
+
+        Response { surfaces }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thin_singlet_closed_form() {
+        // Thin lens of index 1.5 with the stop on it, imaging an object 200 before it
+        let system = System::from_toml(
+            r#"
+            stop = 0
+
+            [object]
+            distance = 200.0
+            semi_diameter = 10.0
+            material = 1.0
+
+            [[wavelengths]]
+            value = 0.5875618
+            weight = 1.0
+
+            [[fields]]
+            x = 0.0
+            y = 0.0
+
+            [[surfaces]]
+            radius = 20.0
+            thickness = 0.0
+            semi_diameter = 5.0
+            material = 1.5
+
+            [[surfaces]]
+            radius = -50.0
+            thickness = 50.0
+            semi_diameter = 5.0
+            material = 1.0
+
+            [[surfaces]]
+            thickness = 0.0
+            semi_diameter = 10.0
+            material = 1.0
+            "#,
+            &[],
+        )
+        .unwrap();
+        let total = system
+            .seidel(&Query {
+                short: 0.4861327,
+                primary: 0.5875618,
+                long: 0.6562725,
+            })
+            .total();
+
+        // Welford, Aberrations of Optical Systems, with the shape factor B, the conjugate factor
+        // C and the Lagrange invariant H of the marginal height y and slopes u, u'
+        let (n, c1, c2) = (1.5f32, 1.0 / 20.0, -1.0 / 50.0);
+        let (y, eta, distance) = (5.0f32, 10.0, 200.0);
+        let power = (n - 1.0) * (c1 - c2);
+        let shape = (c1 + c2) / (c1 - c2);
+        let u = y / distance;
+        let u1 = u - y * power;
+        let conjugate = (u + u1) / (u - u1);
+        let lagrange = u * eta;
+
+        let spherical = y.powi(4) * power.powi(3) / 4.0
+            * ((n / (n - 1.0)).powi(2)
+                + (n + 2.0) / (n * (n - 1.0).powi(2)) * shape * shape
+                + 4.0 * (n + 1.0) / (n * (n - 1.0)) * shape * conjugate
+                + (3.0 * n + 2.0) / n * conjugate * conjugate);
+        let coma = -y * y * power * power * lagrange / 2.0
+            * ((n + 1.0) / (n * (n - 1.0)) * shape + (2.0 * n + 1.0) / n * conjugate);
+        let astigmatism = lagrange * lagrange * power;
+        let petzval = lagrange * lagrange * power / n;
+
+        for (value, expected) in [
+            (total.spherical, spherical),
+            (total.coma, coma),
+            (total.astigmatism, astigmatism),
+            (total.field_curvature, petzval),
+        ] {
+            assert!(
+                (value - expected).abs() <= 1e-4 * expected.abs(),
+                "{total:?}: {value} instead of {expected}"
+            );
+        }
+        assert!(total.distortion.abs() < 1e-7, "{total:?}");
+        assert_eq!(total.axial_color, 0.0);
+        assert_eq!(total.lateral_color, 0.0);
+    }
+}
@@ -0,0 +1,219 @@
+//! Spot diagrams on the image surface.
+
+use std::fmt;
+
+use nalgebra::Point2;
+
+use crate::{
+    compute,
+    system::{Ray, System},
+    utils::{self, Plot},
+};
+
+/// Fields and wavelengths whose spots are traced.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Indices of the traced fields.
+    pub fields: Vec<usize>,
+    /// Indices of the traced system wavelengths.
+    pub wavelengths: Vec<usize>,
+    /// Number of samples across the pupil diameter.
+    pub resolution: u32,
+}
+
+/// Spot of one field and wavelength.
+#[derive(Debug, Clone)]
+pub struct Spot {
+    /// Index of the field.
+    pub field: usize,
+    /// Wavelength, in micrometres.
+    pub wavelength: f32,
+    /// Points on the image surface of the rays that made it through.
+    pub points: Vec<Point2<f32>>,
+}
+
+impl Spot {
+    /// Mean of the points.
+    pub fn centroid(&self) -> Point2<f32> {
+        let sum = self
+            .points
+            .iter()
+            .fold(Point2::origin(), |sum, point| sum + point.coords);
+
+        sum / self.points.len().max(1) as f32
+    }
+
+    /// RMS distance of the points to their centroid.
+    pub fn rms_radius(&self) -> f32 {
+        let centroid = self.centroid();
+
+        (self
+            .points
+            .iter()
+            .map(|point| (point - centroid).norm_squared())
+            .sum::<f32>()
+            / self.points.len().max(1) as f32)
+            .sqrt()
+    }
+}
+
+/// Spots of every selected field and wavelength.
+#[derive(Debug)]
+pub struct Response {
+    /// Query the response answers.
+    pub query: Query,
+    /// Spots grouped by field, then by wavelength.
+    pub spots: Vec<Spot>,
+}
+
+impl Response {
+    /// Plots the spots of `field` relative to the centroid of their first wavelength, in
+    /// micrometres.
+    pub fn plot(&self, field: usize) -> Plot {
+        let mut plot = Plot::new(format!("Spot diagram, field {field}"), "x (µm)", "y (µm)");
+        plot.equal_aspect = true;
+
+        let spots = self.spots.iter().filter(|spot| spot.field == field);
+        let reference = spots
+            .clone()
+            .next()
+            .map_or(Point2::origin(), Spot::centroid);
+        for spot in spots {
+            let color = utils::wavelength_color(spot.wavelength);
+            for point in &spot.points {
+                let offset = 1e3 * (point - reference);
+                plot.marker(offset.x, offset.y, color);
+            }
+        }
+
+        plot
+    }
+
+    /// One row per traced point.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("field,wavelength,x,y\n");
+        for spot in &self.spots {
+            for point in &spot.points {
+                csv += &format!(
+                    "{},{},{},{}\n",
+                    spot.field, spot.wavelength, point.x, point.y
+                );
+            }
+        }
+
+        csv
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:>12} {:>8} {:>12} {:>12} {:>12}",
+            "field", "wavelength", "rays", "centroid x", "centroid y", "rms radius"
+        )?;
+
+        for spot in &self.spots {
+            let centroid = spot.centroid();
+            writeln!(
+                f,
+                "{:>6} {:>12.4} {:>8} {:>12.6} {:>12.6} {:>12.6}",
+                spot.field,
+                spot.wavelength,
+                spot.points.len(),
+                centroid.x,
+                centroid.y,
+                spot.rms_radius()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl System {
+    /// Traces a square grid of pupil points, clipped to the unit circle, for every selected field
+    /// and wavelength in a single dispatch.
+    pub async fn spot_diagram(&self, query: &Query) -> anyhow::Result<Response> {
+        let system = &*self.solved();
+        anyhow::ensure!(!system.surfaces.is_empty(), "the system has no surfaces");
+        let pupil = pupil_grid(query.resolution);
+        let groups = groups(system, &query.fields, &query.wavelengths)?;
+        let rays = groups
+            .iter()
+            .flat_map(|&(field, wavelength)| {
+                pupil.iter().map(move |point| {
+                    system
+                        .field_ray(&system.fields[field], *point)
+                        .with_wavelength(wavelength)
+                })
+            })
+            .collect::<Vec<Ray>>();
+
+        let n_surfaces = system.surfaces.len();
+        let response = system.trace(&compute::raytracing::Query { rays }).await?;
+        let spots = groups
+            .iter()
+            .zip(response.intersections.chunks(n_surfaces * pupil.len()))
+            .map(|(&(field, wavelength), paths)| Spot {
+                field,
+                wavelength,
+                points: paths
+                    .chunks(n_surfaces)
+                    .filter_map(|path| {
+                        let point = path.last()?.point();
+
+                        (point.x.is_finite() && point.y.is_finite()).then(|| point.xy())
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Response {
+            query: query.clone(),
+            spots,
+        })
+    }
+}
+
+/// Pupil points of a square grid of `resolution` samples across, within the unit circle.
+pub(super) fn pupil_grid(resolution: u32) -> Vec<Point2<f32>> {
+    let n = resolution.max(2);
+    let step = |i: u32| 2.0 * i as f32 / (n - 1) as f32 - 1.0;
+
+    (0..n)
+        .flat_map(|j| (0..n).map(move |i| Point2::new(step(i), step(j))))
+        .filter(|pupil| pupil.coords.norm_squared() <= 1.0)
+        .collect()
+}
+
+/// Field indices and wavelengths of every selected pair, grouped by field.
+pub(super) fn groups(
+    system: &System,
+    fields: &[usize],
+    wavelengths: &[usize],
+) -> anyhow::Result<Vec<(usize, f32)>> {
+    let mut groups = Vec::new();
+    for &field in fields {
+        anyhow::ensure!(
+            field < system.fields.len(),
+            "no field {field}, the system has {}",
+            system.fields.len()
+        );
+        for &wavelength in wavelengths {
+            let value = system
+                .wavelengths
+                .get(wavelength)
+                .map(|wavelength| wavelength.value);
+            let value = value.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "no wavelength {wavelength}, the system has {}",
+                    system.wavelengths.len()
+                )
+            })?;
+            groups.push((field, value));
+        }
+    }
+
+    Ok(groups)
+}
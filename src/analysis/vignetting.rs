@@ -0,0 +1,339 @@
+//! Vignetting and relative illumination over the field.
+
+use std::fmt;
+
+use nalgebra::Point2;
+
+use crate::{
+    compute,
+    system::{Intersection, System, Vignetting},
+    utils::Plot,
+};
+
+/// Extent of the sampled pupil relative to the paraxial entrance pupil, so that rays passing
+/// outside of it because of pupil aberrations are still found.
+const OVERFILL: f32 = 1.5;
+
+/// Field heights and pupil grid the vignetting is sampled with.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Wavelength traced, in micrometres.
+    pub wavelength: f32,
+    /// Number of field heights sampled between the axis and the object edge.
+    pub resolution: u32,
+    /// Number of pupil samples on each side of the square pupil grid.
+    pub pupil_resolution: u32,
+}
+
+/// Vignetting of one field height.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldPoint {
+    /// Normalized field height.
+    pub field: f32,
+    /// Fraction of the rays passing the stop that also pass every other aperture.
+    pub unvignetted: f32,
+    /// Image irradiance relative to the axis.
+    pub relative_illumination: f32,
+}
+
+/// Vignetting over the field.
+#[derive(Debug)]
+pub struct Response {
+    /// Sampled field heights, from the axis to the object edge.
+    pub fields: Vec<FieldPoint>,
+}
+
+impl Response {
+    /// Plots the relative illumination and unvignetted fraction against the field.
+    pub fn plot(&self) -> Plot {
+        let mut plot = Plot::new("Relative illumination", "Field", "Relative value");
+
+        plot.line(
+            "Illumination",
+            self.fields
+                .iter()
+                .map(|point| (point.field, point.relative_illumination))
+                .collect(),
+            "yellow",
+        );
+        plot.dashed_line(
+            "Unvignetted",
+            self.fields
+                .iter()
+                .map(|point| (point.field, point.unvignetted))
+                .collect(),
+            "cyan",
+        );
+
+        plot
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>12} {:>14}",
+            "field", "unvignetted", "illumination"
+        )?;
+        for point in &self.fields {
+            writeln!(
+                f,
+                "{:>8.4} {:>12.6} {:>14.6}",
+                point.field, point.unvignetted, point.relative_illumination
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl System {
+    /// Whether a traced ray passes the clear aperture of every surface but the image.
+    pub fn is_unvignetted(&self, intersections: &[Intersection]) -> bool {
+        let apertures = self.surfaces.split_last().map_or(&[][..], |(_, rest)| rest);
+
+        intersections
+            .iter()
+            .zip(apertures)
+            .all(|(intersection, surface)| intersection.is_within(surface))
+    }
+
+    /// Traces dense pupil bundles over the field and reports the unvignetted fraction of the
+    /// pupil and the relative illumination.
+    ///
+    /// The illumination accounts for the clear apertures, the real shape of the pupil, the cos⁴
+    /// falloff of the object space rays and the image area changes caused by distortion.
+    pub async fn vignetting(&self, query: &Query) -> anyhow::Result<Response> {
+        let system = &*self.solved();
+        let n_fields = query.resolution.max(2) as usize;
+        let n_pupil = query.pupil_resolution.max(2) as usize;
+        let n_surfaces = system.surfaces.len();
+        let stop = system.stop_index as usize;
+        anyhow::ensure!(n_surfaces > 0, "the system has no surfaces");
+        anyhow::ensure!(
+            stop < n_surfaces,
+            "no stop surface {stop}, the system has {n_surfaces}"
+        );
+
+        let heights = (0..n_fields)
+            .map(|i| i as f32 / (n_fields - 1) as f32 * system.object.semi_diameter)
+            .collect::<Vec<_>>();
+        let pupil = (0..n_pupil * n_pupil)
+            .map(|i| {
+                let step = |i: usize| OVERFILL * (2.0 * i as f32 / (n_pupil - 1) as f32 - 1.0);
+                Point2::new(step(i % n_pupil), step(i / n_pupil))
+            })
+            .collect::<Vec<_>>();
+
+        // Pupil bundles of every field, followed by their chief rays
+        let rays = heights
+            .iter()
+            .flat_map(|height| {
+                let object = Point2::new(0.0, *height);
+                pupil.iter().map(move |point| (object, *point))
+            })
+            .chain(
+                heights
+                    .iter()
+                    .map(|height| (Point2::new(0.0, *height), Point2::origin())),
+            )
+            .map(|(object, point)| {
+                system
+                    .pupil_ray(object, point)
+                    .with_wavelength(query.wavelength)
+            })
+            .collect();
+
+        let response = system.trace(&compute::raytracing::Query { rays }).await?;
+
+        let rays = response
+            .intersections
+            .chunks(n_surfaces)
+            .collect::<Vec<_>>();
+        let (bundles, chiefs) = rays.split_at(n_fields * n_pupil * n_pupil);
+
+        let image_heights = chiefs
+            .iter()
+            .map(|intersections| intersections[n_surfaces - 1].point().y)
+            .collect::<Vec<_>>();
+        let magnification = system.paraxial_magnification();
+
+        let mut illumination = Vec::with_capacity(n_fields);
+        let mut fields = Vec::with_capacity(n_fields);
+        for (i, bundle) in bundles.chunks(n_pupil * n_pupil).enumerate() {
+            let mut passing_stop = 0;
+            let mut passing = 0;
+            let mut irradiance = 0.0;
+
+            for intersections in bundle {
+                if !intersections[stop].is_within(&system.surfaces[stop]) {
+                    continue;
+                }
+                passing_stop += 1;
+
+                if system.is_unvignetted(intersections) {
+                    passing += 1;
+                    irradiance += intersections[0].ray.direction.z.powi(4);
+                }
+            }
+
+            // Local image area over the paraxial one, from the radial and tangential magnifications
+            let area = if i == 0 {
+                1.0
+            } else {
+                let (a, b) = (i - 1, (i + 1).min(n_fields - 1));
+                let radial = (image_heights[b] - image_heights[a]) / (heights[b] - heights[a]);
+                let tangential = image_heights[i] / heights[i];
+
+                radial * tangential / (magnification * magnification)
+            };
+
+            illumination.push(irradiance / area);
+            fields.push(FieldPoint {
+                field: i as f32 / (n_fields - 1) as f32,
+                unvignetted: if passing_stop > 0 {
+                    passing as f32 / passing_stop as f32
+                } else {
+                    0.0
+                },
+                relative_illumination: 0.0,
+            });
+        }
+
+        let axis = illumination[0];
+        anyhow::ensure!(
+            axis > 0.0,
+            "no ray of the axial field passes, the illumination can't be made relative to it"
+        );
+        for (point, illumination) in fields.iter_mut().zip(illumination) {
+            point.relative_illumination = illumination / axis;
+        }
+
+        Ok(Response { fields })
+    }
+
+    /// Sets the vignetting factors of every field so that the remapped unit pupil spans the
+    /// rays passing all the clear apertures, failing on a field no ray passes along either pupil
+    /// axis.
+    pub async fn solve_vignetting(
+        &mut self,
+        wavelength: f32,
+        resolution: u32,
+    ) -> anyhow::Result<()> {
+        let system = self.solved().into_owned();
+        let n = resolution.max(2) as usize;
+        let n_surfaces = system.surfaces.len();
+        anyhow::ensure!(n_surfaces > 0, "the system has no surfaces");
+        let samples = (0..n)
+            .map(|i| OVERFILL * (2.0 * i as f32 / (n - 1) as f32 - 1.0))
+            .collect::<Vec<_>>();
+
+        // Extent of the unvignetted samples along a line of the pupil
+        let extents = |response: &compute::raytracing::Response| {
+            response
+                .intersections
+                .chunks(n * n_surfaces)
+                .map(|line| {
+                    line.chunks(n_surfaces)
+                        .zip(&samples)
+                        .filter(|(intersections, _)| system.is_unvignetted(intersections))
+                        .fold(None, |extent: Option<(f32, f32)>, (_, sample)| {
+                            Some(extent.map_or((*sample, *sample), |(min, max)| {
+                                (min.min(*sample), max.max(*sample))
+                            }))
+                        })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let rays = self
+            .fields
+            .iter()
+            .flat_map(|field| {
+                samples
+                    .iter()
+                    .map(|y| system.pupil_ray(field.point, Point2::new(0.0, *y)))
+                    .map(|ray| ray.with_wavelength(wavelength))
+            })
+            .collect();
+        let vertical = extents(&system.trace(&compute::raytracing::Query { rays }).await?);
+
+        let mut factors = Vec::with_capacity(system.fields.len());
+        for (i, extent) in vertical.into_iter().enumerate() {
+            let (min, max) =
+                extent.ok_or_else(|| anyhow::anyhow!("field {i} is fully vignetted"))?;
+
+            factors.push(Vignetting {
+                decenter_y: 0.5 * (min + max),
+                compression_y: 1.0 - 0.5 * (max - min),
+                ..Default::default()
+            });
+        }
+
+        let rays = self
+            .fields
+            .iter()
+            .zip(&factors)
+            .flat_map(|(field, factors)| {
+                samples
+                    .iter()
+                    .map(|x| system.pupil_ray(field.point, Point2::new(*x, factors.decenter_y)))
+                    .map(|ray| ray.with_wavelength(wavelength))
+            })
+            .collect();
+        let horizontal = extents(&system.trace(&compute::raytracing::Query { rays }).await?);
+
+        for (i, ((field, mut factors), extent)) in self
+            .fields
+            .iter_mut()
+            .zip(factors)
+            .zip(horizontal)
+            .enumerate()
+        {
+            let (min, max) = extent.ok_or_else(|| {
+                anyhow::anyhow!("no ray of field {i} passes along the x axis of the pupil")
+            })?;
+
+            factors.decenter_x = 0.5 * (min + max);
+            factors.compression_x = 1.0 - 0.5 * (max - min);
+            field.vignetting = factors;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_empty_systems_and_vignetted_axis() {
+        let system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        let query = Query {
+            wavelength: 0.5875618,
+            resolution: 3,
+            pupil_resolution: 4,
+        };
+        let error = |system: System| {
+            let query = query.clone();
+
+            async move { format!("{:#}", system.vignetting(&query).await.unwrap_err()) }
+        };
+
+        let response = system.vignetting(&query).await.unwrap();
+        assert_eq!(response.fields[0].relative_illumination, 1.0);
+
+        let mut empty = system.clone();
+        empty.surfaces.clear();
+        assert_eq!(error(empty).await, "the system has no surfaces");
+
+        let mut blocked = system.clone();
+        blocked.surfaces[1].semi_diameter = 1e-4;
+        assert_eq!(
+            error(blocked).await,
+            "no ray of the axial field passes, the illumination can't be made relative to it"
+        );
+    }
+}
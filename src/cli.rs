@@ -0,0 +1,764 @@
+//! Command-line interface over the analyses, optimizer and tolerancing.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use light::{
+    analysis, compute,
+    glass::Catalog,
+    optimization,
+    query::{
+        self,
+        merit::{MeritFunction, Operand, Target},
+    },
+    system::{Material, Override, System},
+    tolerance::{self, Perturbation, Tolerance},
+    utils::{self, Plot},
+};
+use nalgebra::Point2;
+use serde_json::{Value, json};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Sequential lens design and analysis")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Args)]
+struct Input {
+    /// Prescription file, TOML or Zemax ZMX.
+    prescription: PathBuf,
+    /// Zemax AGF glass catalog the prescription refers to, repeatable.
+    #[arg(long = "catalog", value_name = "AGF")]
+    catalogs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct Selection {
+    /// Comma-separated field indices, all fields by default.
+    #[arg(long, value_delimiter = ',')]
+    fields: Vec<usize>,
+    /// Comma-separated wavelength indices, all wavelengths by default.
+    #[arg(long, value_delimiter = ',')]
+    wavelengths: Vec<usize>,
+    /// Configuration analysed, or the only one optimized, the base system by default.
+    #[arg(long)]
+    configuration: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+struct Outputs {
+    /// Output file, its format picked by the extension: json, csv or svg. Repeatable.
+    ///
+    /// Commands plotting every field separately replace `{field}` in SVG paths by the field
+    /// index.
+    #[arg(short, long = "output", value_name = "PATH")]
+    outputs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CriterionArg {
+    Spot,
+    Wavefront,
+    Mtf,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Traces one ray per field and wavelength, printing its intersections with every surface.
+    Trace {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        selection: Selection,
+        /// Normalized pupil coordinates of the rays.
+        #[arg(long, value_name = "X,Y", default_value = "0,0", value_parser = pupil)]
+        pupil: Point2<f32>,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+    /// Spot diagrams on the image surface.
+    Spot {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        selection: Selection,
+        /// Number of rays across the pupil diameter.
+        #[arg(long, default_value_t = 16)]
+        resolution: u32,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+    /// Tangential and sagittal ray fans.
+    Fan {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        selection: Selection,
+        /// Number of rays across each fan.
+        #[arg(long, default_value_t = 21)]
+        resolution: u32,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+    /// Polychromatic geometric MTF.
+    Mtf {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        selection: Selection,
+        /// Number of rays across the pupil diameter.
+        #[arg(long, default_value_t = 32)]
+        resolution: u32,
+        /// Highest spatial frequency, in cycles per millimetre.
+        #[arg(long, default_value_t = 100.0)]
+        max_frequency: f32,
+        /// Number of frequencies sampled.
+        #[arg(long, default_value_t = 21)]
+        steps: u32,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+    /// Damped least-squares optimization of the prescription variables, minimizing the RMS spots
+    /// of the selected fields.
+    ///
+    /// TOML, ZMX and SEQ outputs save the optimized system, JSON, CSV and SVG ones the merit
+    /// history.
+    Optimize {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        selection: Selection,
+        /// Target effective focal length, kept free when omitted.
+        #[arg(long)]
+        efl: Option<f32>,
+        #[arg(long, default_value_t = 50)]
+        iterations: u32,
+        /// Number of rays across the pupil diameter.
+        #[arg(long, default_value_t = 16)]
+        resolution: u32,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+    /// Sensitivity of the image quality over the selected fields and wavelengths to
+    /// manufacturing errors of every lens surface.
+    Tolerance {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        selection: Selection,
+        #[arg(long, value_enum, default_value_t = CriterionArg::Spot)]
+        criterion: CriterionArg,
+        /// Spatial frequency of the MTF criterion, in cycles per millimetre.
+        #[arg(long, default_value_t = 50.0)]
+        frequency: f32,
+        /// Radius tolerance, in millimetres.
+        #[arg(long, default_value_t = 0.05)]
+        radius: f32,
+        /// Center thickness tolerance of the lenses, in millimetres.
+        #[arg(long, default_value_t = 0.02)]
+        thickness: f32,
+        /// Decenter tolerance, in millimetres.
+        #[arg(long, default_value_t = 0.01)]
+        decenter: f32,
+        /// Tilt tolerance, in radians.
+        #[arg(long, default_value_t = 1e-3)]
+        tilt: f32,
+        /// Index tolerance of the model glasses.
+        #[arg(long, default_value_t = 1e-3)]
+        index: f32,
+        /// Abbe number tolerance of the model glasses.
+        #[arg(long, default_value_t = 0.5)]
+        abbe: f32,
+        /// Number of rays across the pupil diameter.
+        #[arg(long, default_value_t = 16)]
+        resolution: u32,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+    /// Layout of the system with rays of the selected fields and wavelengths, as SVG.
+    Render {
+        #[command(flatten)]
+        input: Input,
+        #[command(flatten)]
+        selection: Selection,
+        /// Number of rays across the pupil of each field.
+        #[arg(long, default_value_t = 5)]
+        rays: u32,
+        #[command(flatten)]
+        outputs: Outputs,
+    },
+}
+
+/// Parses normalized pupil coordinates written as `x,y`.
+fn pupil(value: &str) -> Result<Point2<f32>, String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected x,y, got {value}"))?;
+    let parse = |value: &str| value.trim().parse::<f32>().map_err(|e| e.to_string());
+
+    Ok(Point2::new(parse(x)?, parse(y)?))
+}
+
+impl Input {
+    fn load(&self) -> anyhow::Result<System> {
+        let catalogs = self
+            .catalogs
+            .iter()
+            .map(Catalog::load)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let path = &self.prescription;
+
+        let system = if extension(path) == "zmx" {
+            System::load_zmx(path, &catalogs)
+        } else {
+            System::load(path, &catalogs)
+        };
+
+        system.with_context(|| format!("loading {}", path.display()))
+    }
+}
+
+impl Selection {
+    fn fields(&self, system: &System) -> Vec<usize> {
+        if self.fields.is_empty() {
+            (0..system.fields.len()).collect()
+        } else {
+            self.fields.clone()
+        }
+    }
+
+    fn wavelengths(&self, system: &System) -> Vec<usize> {
+        if self.wavelengths.is_empty() {
+            (0..system.wavelengths.len()).collect()
+        } else {
+            self.wavelengths.clone()
+        }
+    }
+
+    /// The selected configuration of `system`, the system itself when none is.
+    fn system(&self, system: System) -> anyhow::Result<System> {
+        match self.configuration {
+            Some(configuration) => system.configuration(configuration),
+            None => Ok(system),
+        }
+    }
+
+    /// The selected configuration of `system`, keeping only the selected fields and wavelengths.
+    ///
+    /// The primary wavelength stays the same if selected, the first selected one otherwise.
+    fn restricted(&self, system: System) -> anyhow::Result<System> {
+        let mut system = self.system(system)?;
+        anyhow::ensure!(
+            self.fields.is_empty()
+                || !system.configurations.iter().any(|configuration| {
+                    configuration
+                        .overrides
+                        .iter()
+                        .any(|value| matches!(value, Override::Fields(_)))
+                }),
+            "the configurations replace the fields, select one with --configuration"
+        );
+
+        system.fields = self
+            .fields(&system)
+            .into_iter()
+            .map(|field| {
+                system.fields.get(field).cloned().with_context(|| {
+                    format!("no field {field}, the system has {}", system.fields.len())
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let wavelengths = self.wavelengths(&system);
+        let primary = wavelengths
+            .iter()
+            .position(|index| *index == system.primary as usize)
+            .unwrap_or(0);
+        system.wavelengths = wavelengths
+            .into_iter()
+            .map(|index| {
+                system.wavelengths.get(index).cloned().with_context(|| {
+                    format!(
+                        "no wavelength {index}, the system has {}",
+                        system.wavelengths.len()
+                    )
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        system.primary = primary as u32;
+
+        Ok(system)
+    }
+}
+
+/// Results of a command in the output formats it supports.
+struct Report {
+    json: Option<Value>,
+    csv: Option<String>,
+    /// SVG documents, along with the field they show if any.
+    svg: Vec<(Option<usize>, svg::Document)>,
+}
+
+impl Report {
+    /// Plots of every field of `fields`.
+    fn plots(
+        fields: &[usize],
+        plot: impl Fn(usize) -> Plot,
+    ) -> Vec<(Option<usize>, svg::Document)> {
+        fields
+            .iter()
+            .map(|field| (Some(*field), plot(*field).finish()))
+            .collect()
+    }
+}
+
+impl Outputs {
+    fn write(&self, report: &Report) -> anyhow::Result<()> {
+        for path in &self.outputs {
+            match (extension(path).as_str(), &report.json, &report.csv) {
+                ("json", Some(json), _) => {
+                    std::fs::write(path, serde_json::to_string_pretty(json)?)?
+                }
+                ("csv", _, Some(csv)) => std::fs::write(path, csv)?,
+                ("svg", _, _) => self.write_svg(path, &report.svg)?,
+                _ => anyhow::bail!("{}: unsupported output format", path.display()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_svg(
+        &self,
+        path: &Path,
+        documents: &[(Option<usize>, svg::Document)],
+    ) -> anyhow::Result<()> {
+        let template = path.to_string_lossy();
+        if template.contains("{field}") {
+            for (field, document) in documents {
+                let field = field.map_or_else(String::new, |field| field.to_string());
+                svg::save(template.replace("{field}", &field), document)?;
+            }
+        } else {
+            match documents {
+                [(_, document)] => svg::save(path, document)?,
+                [] => anyhow::bail!("{}: unsupported output format", path.display()),
+                _ => anyhow::bail!(
+                    "{}: one plot per field, put {{field}} in the path",
+                    path.display()
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().map_or_else(String::new, |extension| {
+        extension.to_string_lossy().to_lowercase()
+    })
+}
+
+impl Cli {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self.command {
+            Command::Trace {
+                input,
+                selection,
+                pupil,
+                outputs,
+            } => {
+                let system = selection.system(input.load()?)?;
+                let report = trace(&system, &selection, pupil).await?;
+                outputs.write(&report)
+            }
+            Command::Spot {
+                input,
+                selection,
+                resolution,
+                outputs,
+            } => {
+                let system = selection.system(input.load()?)?;
+                let query = analysis::spot_diagram::Query {
+                    fields: selection.fields(&system),
+                    wavelengths: selection.wavelengths(&system),
+                    resolution,
+                };
+                let response = system.spot_diagram(&query).await?;
+                println!("{response}");
+
+                let spots = response
+                    .spots
+                    .iter()
+                    .map(|spot| {
+                        let centroid = spot.centroid();
+
+                        json!({
+                            "field": spot.field,
+                            "wavelength": spot.wavelength,
+                            "centroid": [centroid.x, centroid.y],
+                            "rms_radius": spot.rms_radius(),
+                            "points": spot.points.iter().map(|p| [p.x, p.y]).collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                outputs.write(&Report {
+                    json: Some(json!({ "spots": spots })),
+                    csv: Some(response.to_csv()),
+                    svg: Report::plots(&query.fields, |field| response.plot(field)),
+                })
+            }
+            Command::Fan {
+                input,
+                selection,
+                resolution,
+                outputs,
+            } => {
+                let system = selection.system(input.load()?)?;
+                let query = analysis::ray_fan::Query {
+                    fields: selection.fields(&system),
+                    wavelengths: selection.wavelengths(&system),
+                    resolution,
+                };
+                let response = system.ray_fan(&query).await?;
+                println!("{response}");
+
+                let fans = response
+                    .fans
+                    .iter()
+                    .map(|fan| {
+                        json!({
+                            "field": fan.field,
+                            "wavelength": fan.wavelength,
+                            "pupil": fan.pupil,
+                            "tangential": fan.tangential,
+                            "sagittal": fan.sagittal,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                outputs.write(&Report {
+                    json: Some(json!({ "fans": fans })),
+                    csv: Some(response.to_csv()),
+                    svg: Report::plots(&query.fields, |field| response.plot(field)),
+                })
+            }
+            Command::Mtf {
+                input,
+                selection,
+                resolution,
+                max_frequency,
+                steps,
+                outputs,
+            } => {
+                let system = selection.system(input.load()?)?;
+                let query = analysis::mtf::Query {
+                    fields: selection.fields(&system),
+                    resolution,
+                    max_frequency,
+                    steps,
+                };
+                let response = system.mtf(&query).await?;
+                println!("{response}");
+
+                let curves = response
+                    .curves
+                    .iter()
+                    .map(|curve| {
+                        json!({
+                            "field": curve.field,
+                            "frequencies": curve.frequencies,
+                            "tangential": curve.tangential,
+                            "sagittal": curve.sagittal,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                outputs.write(&Report {
+                    json: Some(json!({ "curves": curves })),
+                    csv: Some(response.to_csv()),
+                    svg: vec![(None, response.plot().finish())],
+                })
+            }
+            Command::Optimize {
+                input,
+                selection,
+                efl,
+                iterations,
+                resolution,
+                outputs,
+            } => {
+                anyhow::ensure!(
+                    selection.wavelengths.is_empty(),
+                    "the spot operands weigh every wavelength, --wavelengths can't be optimized for"
+                );
+                let system = input.load()?;
+                anyhow::ensure!(
+                    !system.variables.is_empty(),
+                    "{} has no variables",
+                    input.prescription.display()
+                );
+
+                if let Some(configuration) = selection.configuration {
+                    system.configuration(configuration)?;
+                }
+
+                let mut operands = efl
+                    .map(|efl| {
+                        Operand::new(query::efl::EffectiveFocalLength, Target::Equal(efl), 1.0)
+                    })
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                operands.extend(selection.fields(&system).into_iter().map(|field| {
+                    Operand::new(
+                        query::spot::SpotRms { field, resolution },
+                        Target::Equal(0.0),
+                        1.0,
+                    )
+                }));
+                if let Some(configuration) = selection.configuration {
+                    operands = operands
+                        .into_iter()
+                        .map(|operand| operand.in_configuration(configuration))
+                        .collect();
+                }
+                let query = optimization::Query {
+                    iterations,
+                    ..optimization::Query::new(MeritFunction::new(operands))
+                };
+                let response = system.optimize(&query).await?;
+                println!("{response}");
+                println!("{}", query.merit_function.report(&response.merit));
+
+                let (systems, reports): (Vec<PathBuf>, Vec<PathBuf>) = outputs
+                    .outputs
+                    .into_iter()
+                    .partition(|path| matches!(extension(path).as_str(), "toml" | "zmx" | "seq"));
+                for path in &systems {
+                    match extension(path).as_str() {
+                        "toml" => response.system.save(path)?,
+                        "zmx" => response.system.save_zmx(path)?,
+                        _ => response.system.save_seq(path)?,
+                    }
+                }
+
+                let history = response
+                    .history
+                    .iter()
+                    .map(|iteration| {
+                        json!({ "merit": iteration.merit, "damping": iteration.damping })
+                    })
+                    .collect::<Vec<_>>();
+                let mut csv = String::from("iteration,merit,damping\n");
+                for (i, iteration) in response.history.iter().enumerate() {
+                    csv += &format!("{i},{},{}\n", iteration.merit, iteration.damping);
+                }
+                Outputs { outputs: reports }.write(&Report {
+                    json: Some(json!({
+                        "merit": response.merit.value,
+                        "residuals": response.merit.residuals,
+                        "history": history,
+                    })),
+                    csv: Some(csv),
+                    svg: vec![(None, response.plot().finish())],
+                })
+            }
+            Command::Tolerance {
+                input,
+                selection,
+                criterion,
+                frequency,
+                radius,
+                thickness,
+                decenter,
+                tilt,
+                index,
+                abbe,
+                resolution,
+                outputs,
+            } => {
+                let system = selection.restricted(input.load()?)?;
+                let criterion = match criterion {
+                    CriterionArg::Spot => tolerance::Criterion::RmsSpot,
+                    CriterionArg::Wavefront => tolerance::Criterion::RmsWavefront,
+                    CriterionArg::Mtf => tolerance::Criterion::Mtf { frequency },
+                };
+
+                // Every surface but the image, thickness and glass errors for the lenses only
+                let mut tolerances = Vec::new();
+                for (i, surface) in system.surfaces.iter().enumerate() {
+                    if i + 1 == system.surfaces.len() {
+                        break;
+                    }
+                    if surface.curvature != 0.0 {
+                        tolerances.push(Tolerance::new(i, Perturbation::Radius, radius));
+                    }
+                    tolerances.push(Tolerance::new(i, Perturbation::DecenterY, decenter));
+                    tolerances.push(Tolerance::new(i, Perturbation::TiltX, tilt));
+                    if surface.material != Material::Constant(1.0) {
+                        tolerances.push(Tolerance::new(i, Perturbation::Thickness, thickness));
+                    }
+                    if let Material::Model { .. } = surface.material {
+                        tolerances.push(Tolerance::new(i, Perturbation::Index, index));
+                        tolerances.push(Tolerance::new(i, Perturbation::Abbe, abbe));
+                    }
+                }
+
+                let response = system.sensitivity(&tolerance::Query {
+                    criterion,
+                    tolerances,
+                    resolution,
+                })?;
+                println!("{response}");
+
+                let sensitivities = response
+                    .sensitivities
+                    .iter()
+                    .map(|sensitivity| {
+                        let tolerance = &sensitivity.tolerance;
+
+                        json!({
+                            "surface": tolerance.surface,
+                            "parameter": tolerance.perturbation.label(),
+                            "tolerance": tolerance.magnitude,
+                            "minus": sensitivity.minus,
+                            "plus": sensitivity.plus,
+                            "worst": sensitivity.worst,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                outputs.write(&Report {
+                    json: Some(json!({
+                        "criterion": format!("{criterion:?}"),
+                        "nominal": response.nominal,
+                        "estimated_change": response.estimated_change(),
+                        "sensitivities": sensitivities,
+                    })),
+                    csv: Some(response.to_csv()),
+                    svg: Vec::new(),
+                })
+            }
+            Command::Render {
+                input,
+                selection,
+                rays,
+                outputs,
+            } => {
+                let system = selection.system(input.load()?)?;
+                let report = render(&system, &selection, rays).await?;
+                outputs.write(&report)
+            }
+        }
+    }
+}
+
+/// Rays of every selected field and wavelength through `pupil` points.
+fn field_rays(
+    system: &System,
+    selection: &Selection,
+    pupil: &[Point2<f32>],
+) -> anyhow::Result<Vec<(usize, f32, compute::raytracing::Query)>> {
+    let mut groups = Vec::new();
+    for field in selection.fields(system) {
+        let object = system
+            .fields
+            .get(field)
+            .with_context(|| format!("no field {field}, the system has {}", system.fields.len()))?;
+        for index in selection.wavelengths(system) {
+            let wavelength = system.wavelengths.get(index).with_context(|| {
+                format!(
+                    "no wavelength {index}, the system has {}",
+                    system.wavelengths.len()
+                )
+            })?;
+            let rays = pupil
+                .iter()
+                .map(|point| {
+                    system
+                        .field_ray(object, *point)
+                        .with_wavelength(wavelength.value)
+                })
+                .collect();
+
+            groups.push((field, wavelength.value, compute::raytracing::Query { rays }));
+        }
+    }
+
+    Ok(groups)
+}
+
+async fn trace(
+    system: &System,
+    selection: &Selection,
+    pupil: Point2<f32>,
+) -> anyhow::Result<Report> {
+    let groups = field_rays(system, selection, &[pupil])?;
+    let rays = groups
+        .iter()
+        .flat_map(|(_, _, query)| query.rays.iter().copied())
+        .collect();
+    let response = system.trace(&compute::raytracing::Query { rays }).await?;
+    let n_surfaces = system.surfaces.len();
+
+    println!(
+        "{:>6} {:>12} {:>8} {:>12} {:>12} {:>12}",
+        "field", "wavelength", "surface", "x", "y", "z"
+    );
+    let mut csv = String::from("field,wavelength,surface,x,y,z\n");
+    let mut json = Vec::new();
+    for ((field, wavelength, _), path) in
+        groups.iter().zip(response.intersections.chunks(n_surfaces))
+    {
+        let points = path
+            .iter()
+            .map(|intersection| intersection.point())
+            .collect::<Vec<_>>();
+        for (surface, point) in points.iter().enumerate() {
+            println!(
+                "{field:>6} {wavelength:>12.4} {surface:>8} {:>12.6} {:>12.6} {:>12.6}",
+                point.x, point.y, point.z
+            );
+            csv += &format!(
+                "{field},{wavelength},{surface},{},{},{}\n",
+                point.x, point.y, point.z
+            );
+        }
+
+        json.push(json!({
+            "field": field,
+            "wavelength": wavelength,
+            "pupil": [pupil.x, pupil.y],
+            "points": points.iter().map(|p| [p.x, p.y, p.z]).collect::<Vec<_>>(),
+        }));
+    }
+
+    Ok(Report {
+        json: Some(json!({ "rays": json })),
+        csv: Some(csv),
+        svg: Vec::new(),
+    })
+}
+
+async fn render(system: &System, selection: &Selection, rays: u32) -> anyhow::Result<Report> {
+    let system = &*system.solved();
+    let n = rays.max(2);
+    let pupil = (0..n)
+        .map(|i| Point2::new(0.0, 2.0 * i as f32 / (n - 1) as f32 - 1.0))
+        .collect::<Vec<_>>();
+
+    let mut view = utils::View::new();
+    for (_, _, query) in field_rays(system, selection, &pupil)? {
+        let response = system.trace(&query).await?;
+        view.draw_intersections(&response.intersections);
+    }
+    view.draw_system(system);
+    view.finish();
+
+    Ok(Report {
+        json: None,
+        csv: None,
+        svg: vec![(None, view.document)],
+    })
+}
@@ -0,0 +1,241 @@
+//! Sequential CPU counterpart of `raytracing.wgsl`, for small queries and hosts without a GPU.
+//!
+//! The tracer is generic over the [`Scalar`] type so that tracing with [`Dual`] numbers yields
+//! exact derivatives with respect to the surface parameters.
+//!
+//! [`Dual`]: super::dual::Dual
+
+use super::{
+    dual::{Scalar, Vector},
+    raytracing::{Query, Response},
+};
+use crate::system::{Intersection, Ray, Surface, System};
+
+/// Surface parameters the generic tracer differentiates.
+#[derive(Debug, Clone, Copy)]
+pub struct Parameters<T> {
+    /// Inverse of the radius of curvature.
+    pub curvature: T,
+    /// Distance to the next surface.
+    pub thickness: T,
+    /// Conic constant.
+    pub conic: T,
+}
+
+impl<T: Scalar> Parameters<T> {
+    /// Parameters of `surface`, with no derivatives.
+    pub fn constant(surface: &Surface) -> Self {
+        Self {
+            curvature: T::constant(surface.curvature),
+            thickness: T::constant(surface.thickness),
+            conic: T::constant(surface.conic),
+        }
+    }
+
+    /// Sag at the radial distance whose square is `r2`.
+    pub fn sag(&self, r2: f32) -> T {
+        let one = T::constant(1.0);
+        let r2 = T::constant(r2);
+        let c = self.curvature;
+
+        c * r2 / (one + (one - (one + self.conic) * c * c * r2).sqrt())
+    }
+}
+
+/// Ray reaching a surface.
+#[derive(Debug, Clone, Copy)]
+pub struct Hit<T> {
+    /// Point on the surface.
+    pub point: Vector<T>,
+    /// Direction before refraction.
+    pub incident: Vector<T>,
+    /// Surface normal at the point.
+    pub normal: Vector<T>,
+    /// Distance from the previous point.
+    pub t: T,
+    /// Optical path length from the ray origin.
+    pub optical_path: T,
+}
+
+fn refract<T: Scalar>(direction: Vector<T>, normal: Vector<T>, mu: T) -> Vector<T> {
+    let one = T::constant(1.0);
+    let a = normal.dot(&direction);
+
+    (normal * (one - mu * mu * (one - a * a)).sqrt() + (direction - normal * a) * mu).normalize()
+}
+
+/// Intersection with the conic `c (x² + y²) + c (1 + k) z² - 2 z = 0`, spheres and planes
+/// included, in the local coordinates of the surface.
+fn intersect<T: Scalar>(
+    parameters: &Parameters<T>,
+    origin: Vector<T>,
+    direction: Vector<T>,
+) -> (Vector<T>, T) {
+    let two = T::constant(2.0);
+    let c = parameters.curvature;
+    let ck = c * (T::constant(1.0) + parameters.conic);
+    let d = direction;
+
+    // Start from the vertex plane, so that distant origins don't lose precision
+    let t0 = -origin.z / d.z;
+    let o = origin + d * t0;
+
+    let a = c * (d.x * d.x + d.y * d.y) + ck * d.z * d.z;
+    let b = two * (c * (o.x * d.x + o.y * d.y) + ck * o.z * d.z - d.z);
+    let k = c * (o.x * o.x + o.y * o.y) + ck * o.z * o.z - two * o.z;
+
+    // Root on the sheet through the vertex, stable when a vanishes
+    let delta = (b * b - T::constant(4.0) * a * k).sqrt();
+    let t = if b.value() < 0.0 {
+        two * k / (delta - b)
+    } else {
+        two * k / (-b - delta)
+    };
+    let (p, t) = (o + d * t, t0 + t);
+
+    let normal = Vector::new(c * p.x, c * p.y, ck * p.z - T::constant(1.0)).normalize();
+
+    (normal, t)
+}
+
+impl System {
+    /// Traces `ray` through the surfaces described by `parameters`, the media being evaluated at
+    /// the ray wavelength.
+    pub fn trace_ray<T: Scalar>(&self, parameters: &[Parameters<T>], ray: &Ray) -> Vec<Hit<T>> {
+        let mut origin = Vector::constant(ray.origin);
+        let mut direction = Vector::<T>::constant(ray.direction);
+        let mut optical_path = T::constant(0.0);
+        let mut n0 = self.object.refractive_index_at(ray.wavelength);
+        let mut z = T::constant(0.0);
+
+        self.surfaces
+            .iter()
+            .zip(parameters)
+            .map(|(surface, parameters)| {
+                let n1 = surface.refractive_index_at(ray.wavelength);
+
+                // Local coordinates of the decentered and tilted surface
+                let rotation = surface.rotation();
+                let inverse = rotation.transpose();
+                let vertex = Vector::new(
+                    T::constant(surface.decenter.x),
+                    T::constant(surface.decenter.y),
+                    z,
+                );
+                let (normal, t) = intersect(
+                    parameters,
+                    (origin - vertex).transform(&inverse),
+                    direction.transform(&inverse),
+                );
+                let normal = normal.transform(&rotation);
+
+                let point = origin + direction * t;
+                optical_path = optical_path + T::constant(n0) * t;
+
+                let hit = Hit {
+                    point,
+                    incident: direction,
+                    normal,
+                    t,
+                    optical_path,
+                };
+
+                direction = refract(direction, -normal, T::constant(n0 / n1));
+                origin = point;
+                z = z + parameters.thickness;
+                n0 = n1;
+
+                hit
+            })
+            .collect()
+    }
+
+    /// Surface parameters without derivatives.
+    pub fn parameters<T: Scalar>(&self) -> Vec<Parameters<T>> {
+        self.surfaces.iter().map(Parameters::constant).collect()
+    }
+
+    /// Optical path difference of `rays` with respect to `chief`, in lens units.
+    ///
+    /// The reference sphere is centered on the chief ray image point and passes through the
+    /// center of the paraxial exit pupil, whose position isn't differentiated.
+    pub fn opd<T: Scalar>(
+        &self,
+        parameters: &[Parameters<T>],
+        rays: &[Ray],
+        chief: &Ray,
+    ) -> Vec<T> {
+        let exit_pupil = self.exit_pupil();
+
+        let reference = |ray: &Ray, center: Option<(Vector<T>, T)>| {
+            let hits = self.trace_ray(parameters, ray);
+            let last = hits.last()?;
+            let (center, radius) = center.unwrap_or_else(|| {
+                let pupil = Vector::constant(nalgebra::Vector3::new(0.0, 0.0, exit_pupil.z));
+
+                (last.point, (last.point - pupil).norm())
+            });
+
+            // Image space index, the one before the image surface
+            let index = self
+                .surfaces
+                .len()
+                .checked_sub(2)
+                .map_or(self.object.refractive_index_at(ray.wavelength), |i| {
+                    self.surfaces[i].refractive_index_at(ray.wavelength)
+                });
+
+            // Step back along the ray to the reference sphere
+            let w = last.point - center;
+            let wd = w.dot(&last.incident);
+            let s = -wd - (wd * wd - w.dot(&w) + radius * radius).sqrt();
+
+            Some((last.optical_path + T::constant(index) * s, (center, radius)))
+        };
+
+        let Some((chief_path, sphere)) = reference(chief, None) else {
+            return Vec::new();
+        };
+
+        rays.iter()
+            .map(|ray| {
+                reference(ray, Some(sphere))
+                    .map_or(T::constant(f32::NAN), |(path, _)| path - chief_path)
+            })
+            .collect()
+    }
+
+    /// Same as [`System::trace`], evaluated on the CPU.
+    pub fn trace_cpu(&self, query: &Query) -> Response {
+        let system = self.solved();
+        let parameters = system.parameters::<f32>();
+
+        let intersections = query
+            .rays
+            .iter()
+            .flat_map(|ray| {
+                let mut origin = ray.origin;
+
+                system
+                    .trace_ray(&parameters, ray)
+                    .into_iter()
+                    .map(move |hit| {
+                        let intersection = Intersection {
+                            ray: Ray {
+                                origin,
+                                direction: hit.incident.value(),
+                                ..*ray
+                            },
+                            normal: hit.normal.value(),
+                            t: hit.t,
+                        };
+                        origin = hit.point.value();
+
+                        intersection
+                    })
+            })
+            .collect();
+
+        Response { intersections }
+    }
+}
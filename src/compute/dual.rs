@@ -0,0 +1,235 @@
+//! Forward-mode automatic differentiation, for the generic CPU tracer.
+
+use std::{
+    fmt,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+/// Number type the generic tracer computes with.
+pub trait Scalar:
+    Copy
+    + fmt::Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// Number with no derivatives.
+    fn constant(value: f32) -> Self;
+    /// Value without derivatives.
+    fn value(self) -> f32;
+    /// Square root.
+    fn sqrt(self) -> Self;
+}
+
+impl Scalar for f32 {
+    fn constant(value: f32) -> Self {
+        value
+    }
+
+    fn value(self) -> f32 {
+        self
+    }
+
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+}
+
+/// Value along with its derivatives with respect to `N` seeded inputs, in double precision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual<const N: usize> {
+    /// Value of the number.
+    pub value: f64,
+    /// Derivatives with respect to each seeded input.
+    pub derivatives: [f64; N],
+}
+
+impl<const N: usize> Dual<N> {
+    /// Input whose derivative is the `index`-th one.
+    pub fn variable(value: f32, index: usize) -> Self {
+        let mut derivatives = [0.0; N];
+        derivatives[index] = 1.0;
+
+        Self {
+            value: value as f64,
+            derivatives,
+        }
+    }
+
+    fn map(self, value: f64, scale: f64) -> Self {
+        Self {
+            value,
+            derivatives: self.derivatives.map(|d| d * scale),
+        }
+    }
+}
+
+impl<const N: usize> Scalar for Dual<N> {
+    fn constant(value: f32) -> Self {
+        Self {
+            value: value as f64,
+            derivatives: [0.0; N],
+        }
+    }
+
+    fn value(self) -> f32 {
+        self.value as f32
+    }
+
+    fn sqrt(self) -> Self {
+        let value = self.value.sqrt();
+
+        self.map(value, 0.5 / value)
+    }
+}
+
+impl<const N: usize> Add for Dual<N> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            value: self.value + rhs.value,
+            derivatives: std::array::from_fn(|i| self.derivatives[i] + rhs.derivatives[i]),
+        }
+    }
+}
+
+impl<const N: usize> Sub for Dual<N> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl<const N: usize> Mul for Dual<N> {
+    type Output = Self;
+
+    // Product rule
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            value: self.value * rhs.value,
+            derivatives: std::array::from_fn(|i| {
+                self.derivatives[i] * rhs.value + self.value * rhs.derivatives[i]
+            }),
+        }
+    }
+}
+
+impl<const N: usize> Div for Dual<N> {
+    type Output = Self;
+
+    // Quotient rule
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: Self) -> Self {
+        let value = self.value / rhs.value;
+
+        Self {
+            value,
+            derivatives: std::array::from_fn(|i| {
+                (self.derivatives[i] - value * rhs.derivatives[i]) / rhs.value
+            }),
+        }
+    }
+}
+
+impl<const N: usize> Neg for Dual<N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.map(-self.value, -1.0)
+    }
+}
+
+/// Three-dimensional vector over any [`Scalar`].
+#[derive(Debug, Clone, Copy)]
+pub struct Vector<T> {
+    /// x component.
+    pub x: T,
+    /// y component.
+    pub y: T,
+    /// z component.
+    pub z: T,
+}
+
+impl<T: Scalar> Vector<T> {
+    /// Vector of the given components.
+    pub fn new(x: T, y: T, z: T) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Vector of constant components.
+    pub fn constant(vector: nalgebra::Vector3<f32>) -> Self {
+        Self::new(
+            T::constant(vector.x),
+            T::constant(vector.y),
+            T::constant(vector.z),
+        )
+    }
+
+    /// Value of the vector without derivatives.
+    pub fn value(&self) -> nalgebra::Vector3<f32> {
+        nalgebra::Vector3::new(self.x.value(), self.y.value(), self.z.value())
+    }
+
+    /// Dot product.
+    pub fn dot(&self, rhs: &Self) -> T {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    /// Euclidean norm.
+    pub fn norm(&self) -> T {
+        self.dot(self).sqrt()
+    }
+
+    /// Vector of the same direction and unit norm.
+    pub fn normalize(&self) -> Self {
+        *self * (T::constant(1.0) / self.norm())
+    }
+
+    /// Product with a constant matrix.
+    pub fn transform(&self, matrix: &nalgebra::Matrix3<f32>) -> Self {
+        let row = |i: usize| {
+            T::constant(matrix[(i, 0)]) * self.x
+                + T::constant(matrix[(i, 1)]) * self.y
+                + T::constant(matrix[(i, 2)]) * self.z
+        };
+
+        Self::new(row(0), row(1), row(2))
+    }
+}
+
+impl<T: Scalar> Add for Vector<T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl<T: Scalar> Sub for Vector<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl<T: Scalar> Mul<T> for Vector<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self {
+        Self::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl<T: Scalar> Neg for Vector<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
@@ -1,15 +1,26 @@
+//! Fans of rays from a single point, used to aim the chief ray through the stop.
+
 use nalgebra::Vector3;
 
+/// Fan of rays from a point, their directions spread evenly between two bounds.
 #[derive(Debug, encase::ShaderType)]
 pub struct Query {
+    /// Point the rays start from.
     pub origin: Vector3<f32>,
+    /// Direction of the first ray.
     pub dir_a: Vector3<f32>,
+    /// Direction of the last ray.
     pub dir_b: Vector3<f32>,
+    /// Number of rays in the fan.
     pub resolution: u32,
+    /// Wavelength of the rays, in micrometres.
+    pub wavelength: f32,
 }
 
+/// Result of a fan trace.
 #[derive(Debug, encase::ShaderType)]
 pub struct Response {
+    /// Height of every ray at the aperture stop.
     #[size(runtime)]
     pub heights: Vec<f32>,
 }
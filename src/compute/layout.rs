@@ -0,0 +1,373 @@
+//! Buffer layouts of the prescription as seen by the shaders.
+
+use crate::{glass, system};
+
+/// Dispersion formula and its coefficients, evaluated by `refractive_index` in the shaders.
+#[derive(Debug, encase::ShaderType)]
+pub struct Material {
+    /// One of the formula constants below.
+    pub formula: u32,
+    /// Coefficients of the formula, unused ones zero.
+    pub coefficients: [f32; 10],
+    /// Schott `D₀, D₁, D₂, E₀, E₁, λtk` coefficients.
+    pub thermal: [f32; 6],
+    /// Reference temperature and pressure followed by the ones of the environment, all zero
+    /// leaving the index untouched.
+    pub environment: [f32; 4],
+}
+
+/// Object space of the system.
+#[derive(Debug, encase::ShaderType)]
+pub struct Object {
+    /// Distance from the object to the first surface.
+    pub distance: f32,
+    /// Half the height of the object.
+    pub semi_diameter: f32,
+    /// Medium the object is in.
+    pub material: Material,
+}
+
+/// Surface along with the medium following it.
+#[derive(Debug, encase::ShaderType)]
+pub struct Surface {
+    /// Distance to the next surface.
+    pub thickness: f32,
+    /// Inverse of the radius of curvature.
+    pub curvature: f32,
+    /// Conic constant.
+    pub conic: f32,
+    /// Clear semi-diameter, rays beyond it being vignetted.
+    pub semi_diameter: f32,
+    /// Medium following the surface.
+    pub material: Material,
+    /// Lateral offset of the surface vertex.
+    pub decenter: nalgebra::Vector2<f32>,
+    /// Rotation of the surface about its vertex.
+    pub rotation: nalgebra::Matrix3<f32>,
+}
+
+/// Bound as group 0 by every shader.
+///
+/// A batch of systems sharing the object and the number of surfaces is laid out with their
+/// surfaces one after the other, each system being traced by `rays_per_system` consecutive rays.
+#[derive(Debug, encase::ShaderType)]
+pub struct System {
+    /// Object space shared by the systems.
+    pub object: Object,
+    /// Index of the aperture stop in the surfaces of each system.
+    pub stop_index: u32,
+    /// Number of surfaces of each system, the image included.
+    pub surface_count: u32,
+    /// Number of consecutive rays traced through each system.
+    pub rays_per_system: u32,
+    /// Surfaces of every system, one system after the other.
+    #[size(runtime)]
+    pub surfaces: Vec<Surface>,
+}
+
+impl Material {
+    /// Index independent of the wavelength.
+    pub const CONSTANT: u32 = 0;
+    /// Up to five interleaved `(K, L)` terms.
+    pub const SELLMEIER: u32 = 1;
+    /// Schott power series.
+    pub const SCHOTT: u32 = 2;
+    /// Conrady formula.
+    pub const CONRADY: u32 = 3;
+    /// Cauchy formula.
+    pub const CAUCHY: u32 = 4;
+    /// Herzberger formula.
+    pub const HERZBERGER: u32 = 5;
+    /// Sellmeier formula with a constant term and two resonances.
+    pub const SELLMEIER_2: u32 = 6;
+    /// Handbook of Optics formula 1.
+    pub const HANDBOOK_1: u32 = 7;
+    /// Handbook of Optics formula 2.
+    pub const HANDBOOK_2: u32 = 8;
+    /// Sellmeier formula with a constant term and two terms.
+    pub const SELLMEIER_4: u32 = 9;
+    /// Extended Schott formula.
+    pub const EXTENDED: u32 = 10;
+    /// Extended Schott formula with higher powers.
+    pub const EXTENDED_2: u32 = 11;
+    /// Extended Schott formula with nine coefficients.
+    pub const EXTENDED_3: u32 = 12;
+
+    fn new(formula: u32, values: &[f32]) -> Self {
+        let mut coefficients = [0.0; 10];
+        coefficients[..values.len()].copy_from_slice(values);
+
+        Self {
+            formula,
+            coefficients,
+            thermal: [0.0; 6],
+            environment: [0.0; 4],
+        }
+    }
+}
+
+impl From<&glass::Glass> for Material {
+    fn from(glass: &glass::Glass) -> Self {
+        let formula = match glass.formula {
+            glass::Formula::Schott => Self::SCHOTT,
+            glass::Formula::Sellmeier1
+            | glass::Formula::Sellmeier3
+            | glass::Formula::Sellmeier5 => Self::SELLMEIER,
+            glass::Formula::Herzberger => Self::HERZBERGER,
+            glass::Formula::Sellmeier2 => Self::SELLMEIER_2,
+            glass::Formula::Conrady => Self::CONRADY,
+            glass::Formula::Handbook1 => Self::HANDBOOK_1,
+            glass::Formula::Handbook2 => Self::HANDBOOK_2,
+            glass::Formula::Sellmeier4 => Self::SELLMEIER_4,
+            glass::Formula::Extended => Self::EXTENDED,
+            glass::Formula::Extended2 => Self::EXTENDED_2,
+            glass::Formula::Extended3 => Self::EXTENDED_3,
+        };
+
+        Self {
+            formula,
+            coefficients: glass.coefficients,
+            thermal: [0.0; 6],
+            environment: [0.0; 4],
+        }
+    }
+}
+
+impl From<&system::Material> for Material {
+    fn from(material: &system::Material) -> Self {
+        match material.to_cauchy() {
+            system::Material::Constant(n) => Self::new(Self::CONSTANT, &[n]),
+            system::Material::Sellmeier { b, c } => {
+                Self::new(Self::SELLMEIER, &[b[0], c[0], b[1], c[1], b[2], c[2]])
+            }
+            system::Material::Schott(a) => Self::new(Self::SCHOTT, &a),
+            system::Material::Conrady { n0, a, b } => Self::new(Self::CONRADY, &[n0, a, b]),
+            system::Material::Cauchy { a, b, c } => Self::new(Self::CAUCHY, &[a, b, c]),
+            system::Material::Herzberger(a) => Self::new(Self::HERZBERGER, &a),
+            system::Material::Glass(glass) => glass.as_ref().into(),
+            system::Material::Perturbed {
+                material,
+                environment,
+            } => {
+                let thermal = material.thermal();
+
+                Self {
+                    thermal: [
+                        thermal.d0,
+                        thermal.d1,
+                        thermal.d2,
+                        thermal.e0,
+                        thermal.e1,
+                        thermal.lambda_tk,
+                    ],
+                    environment: [
+                        thermal.reference_temperature,
+                        1.0,
+                        environment.temperature,
+                        environment.pressure,
+                    ],
+                    ..material.as_ref().into()
+                }
+            }
+            system::Material::Model { .. } => unreachable!("model glasses are converted above"),
+        }
+    }
+}
+
+impl From<&system::Object> for Object {
+    fn from(object: &system::Object) -> Self {
+        Self {
+            distance: object.distance,
+            semi_diameter: object.semi_diameter,
+            material: (&object.material).into(),
+        }
+    }
+}
+
+impl From<&system::Surface> for Surface {
+    fn from(surface: &system::Surface) -> Self {
+        Self {
+            thickness: surface.thickness,
+            curvature: surface.curvature,
+            conic: surface.conic,
+            semi_diameter: surface.semi_diameter,
+            material: (&surface.material).into(),
+            decenter: surface.decenter,
+            rotation: surface.rotation(),
+        }
+    }
+}
+
+impl From<&system::System> for System {
+    fn from(system: &system::System) -> Self {
+        Self {
+            object: (&system.object).into(),
+            stop_index: system.stop_index,
+            surface_count: system.surfaces.len() as u32,
+            rays_per_system: u32::MAX,
+            surfaces: system.surfaces.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl System {
+    /// Lays out `systems` as a batch, the object and stop of the first one being shared.
+    pub fn batch(systems: &[system::System], rays_per_system: u32) -> Self {
+        let mut batch = Self::from(&systems[0]);
+        batch.rays_per_system = rays_per_system;
+        batch.surfaces = systems
+            .iter()
+            .flat_map(|system| system.surfaces.iter().map(Into::into))
+            .collect();
+
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Point3, Vector3};
+
+    use crate::{
+        compute::{fan, raytracing},
+        glass::{Formula, Glass, Thermal},
+        system::{Material, Ray, System},
+    };
+
+    /// Coefficients of every formula giving indices around 1.5 in the visible.
+    const FORMULAS: [(Formula, [f32; 10]); 13] = [
+        (
+            Formula::Schott,
+            [
+                2.27, -0.0101, 0.0106, 2.08e-4, -7.6e-6, 4.9e-7, 0.0, 0.0, 0.0, 0.0,
+            ],
+        ),
+        (
+            Formula::Sellmeier1,
+            [1.04, 0.006, 0.232, 0.02, 1.01, 103.56, 0.0, 0.0, 0.0, 0.0],
+        ),
+        (
+            Formula::Herzberger,
+            [1.5, 0.004, 1e-4, -0.001, 1e-4, -1e-5, 0.0, 0.0, 0.0, 0.0],
+        ),
+        (
+            Formula::Sellmeier2,
+            [0.2, 1.0, 0.1, 0.01, 0.2, 0.0, 0.0, 0.0, 0.0, 0.0],
+        ),
+        (
+            Formula::Conrady,
+            [1.49, 0.005, 5e-4, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        ),
+        (
+            Formula::Sellmeier3,
+            [1.04, 0.006, 0.232, 0.02, 1.01, 103.56, 0.01, 0.05, 0.0, 0.0],
+        ),
+        (
+            Formula::Handbook1,
+            [2.25, 0.01, 0.02, 0.01, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        ),
+        (
+            Formula::Handbook2,
+            [1.2, 1.0, 0.01, 0.01, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        ),
+        (
+            Formula::Sellmeier4,
+            [1.2, 1.0, 0.01, 0.1, 100.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        ),
+        (
+            Formula::Extended,
+            [
+                2.27, -0.0101, 0.0106, 2.08e-4, -7.6e-6, 4.9e-7, 1e-8, 1e-9, 0.0, 0.0,
+            ],
+        ),
+        (
+            Formula::Sellmeier5,
+            [
+                1.04, 0.006, 0.232, 0.02, 1.01, 103.56, 0.01, 0.05, 0.01, 0.07,
+            ],
+        ),
+        (
+            Formula::Extended2,
+            [
+                2.27, -0.0101, 0.0106, 2.08e-4, -7.6e-6, 4.9e-7, 1e-4, -1e-5, 0.0, 0.0,
+            ],
+        ),
+        (
+            Formula::Extended3,
+            [
+                2.27, -0.0101, 1e-4, 0.0106, 2.08e-4, -7.6e-6, 4.9e-7, 1e-8, 1e-9, 0.0,
+            ],
+        ),
+    ];
+
+    #[tokio::test]
+    async fn shaders_match_cpu_dispersion() {
+        let origin = Point3::new(0.0, 0.0, -20.0);
+        let (dir_a, dir_b) = (Vector3::new(0.0, -0.05, 1.0), Vector3::new(0.0, 0.05, 1.0));
+        let resolution = 5;
+
+        for (formula, coefficients) in FORMULAS {
+            let glass = Glass {
+                name: format!("{formula:?}"),
+                catalog: "test".to_string(),
+                formula,
+                coefficients,
+                nd: 1.5,
+                vd: 60.0,
+                comment: String::new(),
+                expansion: 0.0,
+                density: 0.0,
+                thermal: Thermal::default(),
+                wavelength_range: (0.0, f32::INFINITY),
+                transmission: Vec::new(),
+            };
+            // Slab of the glass, the image being the stop so that the fans cross it
+            let mut system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+            system.surfaces[0].material = Material::Glass(Arc::new(glass));
+            system.surfaces[1].material = Material::Constant(1.0);
+            system.stop_index = 3;
+
+            for wavelength in [0.45, 0.55, 0.7] {
+                let index = system.surfaces[0].refractive_index_at(wavelength);
+                assert!((1.4..1.7).contains(&index), "{formula:?}: index {index}");
+
+                let rays = (0..resolution)
+                    .map(|i| {
+                        let t = i as f32 / (resolution - 1) as f32;
+                        let direction = (dir_a + t * (dir_b - dir_a)).normalize();
+
+                        Ray::new(origin, direction).with_wavelength(wavelength)
+                    })
+                    .collect::<Vec<_>>();
+                let query = raytracing::Query { rays };
+                let cpu = system.trace_cpu(&query);
+                let gpu = system.trace(&query).await.unwrap();
+                for (cpu, gpu) in cpu.intersections.iter().zip(&gpu.intersections) {
+                    let error = (cpu.ray.direction - gpu.ray.direction).norm();
+                    assert!(error < 1e-5, "{formula:?} at {wavelength} µm: {error}");
+                }
+
+                let fan = system
+                    .fan(&fan::Query {
+                        origin: origin.coords,
+                        dir_a,
+                        dir_b,
+                        resolution,
+                        wavelength,
+                    })
+                    .await
+                    .unwrap();
+                let n_surfaces = system.surfaces.len();
+                for (height, path) in fan.heights.iter().zip(cpu.intersections.chunks(n_surfaces)) {
+                    let expected = path[n_surfaces - 1].point().y;
+                    assert!(
+                        (height - expected).abs() < 1e-4,
+                        "{formula:?} at {wavelength} µm: fan height {height} instead of {expected}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,13 +1,23 @@
+//! GPU ray tracing, its buffer layouts and the CPU tracer used for derivatives.
+
+pub mod cpu;
+pub mod dual;
 pub mod fan;
+pub mod layout;
 pub mod raytracing;
 
+/// GPU device and the bind group layouts shared by the compute shaders.
 pub struct Gpu {
+    /// Device the shaders run on.
     pub device: wgpu::Device,
+    /// Queue the dispatches are submitted to.
     pub queue: wgpu::Queue,
+    /// Layouts of the system, query and response bind groups.
     pub bind_group_layouts: Vec<wgpu::BindGroupLayout>,
 }
 
 impl Gpu {
+    /// Requests the default adapter and a device able to run the shaders.
     pub async fn new() -> anyhow::Result<Self> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
         let adapter = instance.request_adapter(&Default::default()).await.unwrap();
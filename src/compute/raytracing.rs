@@ -1,13 +1,37 @@
-use crate::system::{Intersection, Ray};
+//! Buffers of the ray tracing shader.
 
+use crate::system::{Intersection, Ray, System};
+
+/// Rays traced through the system.
 #[derive(Debug, encase::ShaderType)]
 pub struct Query {
+    /// Rays to trace, each one in its own thread.
     #[size(runtime)]
     pub rays: Vec<Ray>,
 }
 
+/// Intersections of the traced rays.
 #[derive(Debug, Default, encase::ShaderType)]
 pub struct Response {
+    /// Intersections of every ray with each surface, ray by ray.
     #[size(runtime)]
     pub intersections: Vec<Intersection>,
 }
+
+/// Part of a traced batch, along with the system it was traced through.
+#[derive(Debug, Clone, Copy)]
+pub struct RayTracingResult<'a> {
+    /// System the rays were traced through.
+    pub system: &'a System,
+    /// Traced rays.
+    pub rays: &'a [Ray],
+    /// Intersections of `rays`, surface by surface.
+    pub intersections: &'a [Intersection],
+}
+
+impl<'a> RayTracingResult<'a> {
+    /// Intersections of every ray with all the surfaces.
+    pub fn paths(&self) -> impl Iterator<Item = &'a [Intersection]> {
+        self.intersections.chunks(self.system.surfaces.len().max(1))
+    }
+}
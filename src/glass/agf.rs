@@ -0,0 +1,190 @@
+//! Zemax AGF glass catalog format.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use super::{Formula, Glass, Thermal, Transmission};
+
+/// Decodes a Zemax text file, AGF and ZMX files being often saved as UTF-16.
+pub fn decode(bytes: &[u8]) -> anyhow::Result<String> {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|pair| from([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+
+        String::from_utf16(&units).context("invalid UTF-16 file")
+    };
+
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => Ok(String::from_utf8_lossy(rest).into_owned()),
+        _ => Ok(String::from_utf8_lossy(bytes).into_owned()),
+    }
+}
+
+fn numbers(fields: &[&str]) -> anyhow::Result<Vec<f32>> {
+    fields
+        .iter()
+        .map(|field| {
+            field
+                .parse()
+                .with_context(|| format!("{field:?} isn't a number"))
+        })
+        .collect()
+}
+
+fn field(values: &[f32], index: usize) -> f32 {
+    values.get(index).copied().unwrap_or(0.0)
+}
+
+/// Parses the glasses of an AGF catalog named `catalog`.
+pub fn parse(catalog: &str, source: &str) -> anyhow::Result<Vec<Arc<Glass>>> {
+    let mut glasses = Vec::new();
+    let mut current: Option<Glass> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        let Some(record) = fields.next() else {
+            continue;
+        };
+        let fields = fields.collect::<Vec<_>>();
+        let context = || format!("{catalog}, line {}: invalid {record} record", number + 1);
+
+        if record == "NM" {
+            glasses.extend(current.take().map(Arc::new));
+
+            let name = fields.first().with_context(context)?;
+            let code = fields
+                .get(1)
+                .and_then(|code| code.parse::<f32>().ok())
+                .with_context(context)?;
+            let formula = Formula::from_code(code as u32).with_context(|| {
+                format!(
+                    "{catalog}, line {}: glass {name} uses unsupported formula {code}",
+                    number + 1
+                )
+            })?;
+            let values = numbers(&fields[2.min(fields.len())..]).with_context(context)?;
+
+            current = Some(Glass {
+                name: name.to_string(),
+                catalog: catalog.to_string(),
+                formula,
+                coefficients: [0.0; 10],
+                nd: field(&values, 1),
+                vd: field(&values, 2),
+                comment: String::new(),
+                expansion: 0.0,
+                density: 0.0,
+                thermal: Thermal::default(),
+                wavelength_range: (0.0, f32::INFINITY),
+                transmission: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(glass) = current.as_mut() else {
+            continue;
+        };
+        let values = || numbers(&fields).with_context(context);
+
+        match record {
+            "GC" => glass.comment = fields.join(" "),
+            "ED" => {
+                let values = values()?;
+                glass.expansion = 1e-6 * field(&values, 0);
+                glass.density = field(&values, 2);
+            }
+            "CD" => {
+                let values = values()?;
+                for (coefficient, value) in glass.coefficients.iter_mut().zip(&values) {
+                    *coefficient = *value;
+                }
+            }
+            "TD" => {
+                let values = values()?;
+                glass.thermal = Thermal {
+                    d0: field(&values, 0),
+                    d1: field(&values, 1),
+                    d2: field(&values, 2),
+                    e0: field(&values, 3),
+                    e1: field(&values, 4),
+                    lambda_tk: field(&values, 5),
+                    reference_temperature: values.get(6).copied().unwrap_or(20.0),
+                }
+            }
+            "LD" => {
+                let values = values()?;
+                glass.wavelength_range = (field(&values, 0), field(&values, 1));
+            }
+            "IT" => {
+                let values = values()?;
+                anyhow::ensure!(values.len() >= 3, context());
+
+                glass.transmission.push(Transmission {
+                    wavelength: values[0],
+                    transmittance: values[1],
+                    thickness: values[2],
+                });
+            }
+            _ => {}
+        }
+    }
+
+    glasses.extend(current.map(Arc::new));
+
+    Ok(glasses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = "\
+CC Test catalog
+NM N-BK7 2 517642.251 1.5168 64.17 0 1 0
+GC Sellmeier glass
+ED 7.1 8.3 2.51 0 0
+CD 1.03961212 0.00600069867 0.231792344 0.0200179144 1.01046945 103.560653 0 0 0 0
+LD 0.3 2.5
+IT 0.35 0.99 10
+NM BK7 1 517642.000 1.5168 64.17 0 0 0
+CD 2.2718929 -0.010108077 0.010592509 2.0816965E-4 -7.6472538E-6 4.9240991E-7 0 0 0 0
+LD 0.365 1.014
+";
+
+    #[test]
+    fn matches_catalog_nd() {
+        let glasses = parse("test", CATALOG).unwrap();
+        assert_eq!(glasses.len(), 2);
+        assert_eq!(glasses[0].formula, Formula::Sellmeier1);
+        assert_eq!(glasses[1].formula, Formula::Schott);
+
+        for glass in &glasses {
+            let nd = glass.refractive_index(0.5875618);
+            assert!(
+                (nd - glass.nd).abs() < 1e-5,
+                "{}: {nd} instead of {}",
+                glass.name,
+                glass.nd
+            );
+        }
+        assert_eq!(glasses[0].comment, "Sellmeier glass");
+        assert_eq!(glasses[0].wavelength_range, (0.3, 2.5));
+        assert_eq!(glasses[0].transmission.len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        let source = CATALOG.replace("LD 0.3 2.5", "LD 0.3 2,5");
+        let error = parse("test", &source).unwrap_err();
+
+        assert_eq!(
+            format!("{error:#}"),
+            "test, line 6: invalid LD record: \"2,5\" isn't a number: invalid float literal"
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Glass catalogs and their dispersion formulas.
+
+pub mod agf;
+
+use std::{path::Path, sync::Arc};
+
+use crate::system::Material;
+
+/// Dispersion formulas used by the glass catalogs, numbered as in the AGF format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Formula {
+    /// Index squared as a power series of the wavelength.
+    Schott = 1,
+    /// Sellmeier formula with three terms.
+    Sellmeier1 = 2,
+    /// Herzberger formula.
+    Herzberger = 3,
+    /// Sellmeier formula with a constant term and two resonances.
+    Sellmeier2 = 4,
+    /// Conrady formula.
+    Conrady = 5,
+    /// Sellmeier formula with four terms.
+    Sellmeier3 = 6,
+    /// Handbook of Optics formula 1.
+    Handbook1 = 7,
+    /// Handbook of Optics formula 2.
+    Handbook2 = 8,
+    /// Sellmeier formula with a constant term and two terms.
+    Sellmeier4 = 9,
+    /// Schott formula extended to eight coefficients.
+    Extended = 10,
+    /// Sellmeier formula with five terms.
+    Sellmeier5 = 11,
+    /// Schott formula extended to eight coefficients with higher powers.
+    Extended2 = 12,
+    /// Schott formula extended to nine coefficients.
+    Extended3 = 13,
+}
+
+impl Formula {
+    /// Formula of AGF code `code`.
+    pub fn from_code(code: u32) -> Option<Self> {
+        Some(match code {
+            1 => Self::Schott,
+            2 => Self::Sellmeier1,
+            3 => Self::Herzberger,
+            4 => Self::Sellmeier2,
+            5 => Self::Conrady,
+            6 => Self::Sellmeier3,
+            7 => Self::Handbook1,
+            8 => Self::Handbook2,
+            9 => Self::Sellmeier4,
+            10 => Self::Extended,
+            11 => Self::Sellmeier5,
+            12 => Self::Extended2,
+            13 => Self::Extended3,
+            _ => return None,
+        })
+    }
+
+    /// Evaluates the formula at `wavelength`, in micrometres.
+    pub fn refractive_index(&self, c: &[f32; 10], wavelength: f32) -> f32 {
+        let l2 = wavelength * wavelength;
+        let inv = |power: i32| l2.powi(-power);
+        let sellmeier = |terms: usize| {
+            (0..terms)
+                .map(|i| c[2 * i] * l2 / (l2 - c[2 * i + 1]))
+                .sum::<f32>()
+        };
+
+        match self {
+            Self::Schott => {
+                (c[0] + c[1] * l2 + c[2] * inv(1) + c[3] * inv(2) + c[4] * inv(3) + c[5] * inv(4))
+                    .sqrt()
+            }
+            Self::Sellmeier1 => (1.0 + sellmeier(3)).sqrt(),
+            Self::Sellmeier3 => (1.0 + sellmeier(4)).sqrt(),
+            Self::Sellmeier5 => (1.0 + sellmeier(5)).sqrt(),
+            Self::Herzberger => {
+                let l = 1.0 / (l2 - 0.028);
+                c[0] + c[1] * l + c[2] * l * l + c[3] * l2 + c[4] * l2 * l2 + c[5] * l2 * l2 * l2
+            }
+            Self::Sellmeier2 => {
+                (1.0 + c[0] + c[1] * l2 / (l2 - c[2] * c[2]) + c[3] / (l2 - c[4] * c[4])).sqrt()
+            }
+            Self::Conrady => c[0] + c[1] / wavelength + c[2] / wavelength.powf(3.5),
+            Self::Handbook1 => (c[0] + c[1] / (l2 - c[2]) - c[3] * l2).sqrt(),
+            Self::Handbook2 => (c[0] + c[1] * l2 / (l2 - c[2]) - c[3] * l2).sqrt(),
+            Self::Sellmeier4 => (c[0] + c[1] * l2 / (l2 - c[2]) + c[3] * l2 / (l2 - c[4])).sqrt(),
+            Self::Extended => {
+                (c[0] + c[1] * l2 + (2..8).map(|i| c[i] * inv(i as i32 - 1)).sum::<f32>()).sqrt()
+            }
+            Self::Extended2 => (c[0]
+                + c[1] * l2
+                + (2..6).map(|i| c[i] * inv(i as i32 - 1)).sum::<f32>()
+                + c[6] * l2 * l2
+                + c[7] * l2 * l2 * l2)
+                .sqrt(),
+            Self::Extended3 => (c[0]
+                + c[1] * l2
+                + c[2] * l2 * l2
+                + (3..9).map(|i| c[i] * inv(i as i32 - 2)).sum::<f32>())
+            .sqrt(),
+        }
+    }
+}
+
+/// Schott model of the temperature dependence of the absolute index (TD record).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thermal {
+    /// Linear coefficient of the index change.
+    pub d0: f32,
+    /// Quadratic coefficient of the index change.
+    pub d1: f32,
+    /// Cubic coefficient of the index change.
+    pub d2: f32,
+    /// Linear coefficient of the dispersion change.
+    pub e0: f32,
+    /// Quadratic coefficient of the dispersion change.
+    pub e1: f32,
+    /// Characteristic wavelength, in micrometres.
+    pub lambda_tk: f32,
+    /// Temperature at which the dispersion coefficients are given, in °C.
+    pub reference_temperature: f32,
+}
+
+impl Default for Thermal {
+    /// Temperature independent glass, referenced at 20 °C.
+    fn default() -> Self {
+        Self {
+            d0: 0.0,
+            d1: 0.0,
+            d2: 0.0,
+            e0: 0.0,
+            e1: 0.0,
+            lambda_tk: 0.0,
+            reference_temperature: 20.0,
+        }
+    }
+}
+
+impl Thermal {
+    /// Change of the absolute `index` at `wavelength` for a temperature change of `delta` K.
+    pub fn index_change(&self, index: f32, wavelength: f32, delta: f32) -> f32 {
+        let dispersion = (self.e0 * delta + self.e1 * delta * delta)
+            / (wavelength * wavelength - self.lambda_tk * self.lambda_tk);
+
+        (index * index - 1.0) / (2.0 * index)
+            * (self.d0 * delta
+                + self.d1 * delta * delta
+                + self.d2 * delta * delta * delta
+                + dispersion)
+    }
+}
+
+/// Internal transmittance sample (IT record).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transmission {
+    /// Wavelength of the sample, in micrometres.
+    pub wavelength: f32,
+    /// Internal transmittance through the sample.
+    pub transmittance: f32,
+    /// Sample thickness, in millimetres.
+    pub thickness: f32,
+}
+
+/// Catalog glass, as read from an AGF file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glass {
+    /// Name of the glass in its catalog.
+    pub name: String,
+    /// Name of the catalog the glass was read from.
+    pub catalog: String,
+    /// Dispersion formula of the glass.
+    pub formula: Formula,
+    /// Coefficients of the dispersion formula, unused ones zero.
+    pub coefficients: [f32; 10],
+    /// Index at the d line.
+    pub nd: f32,
+    /// Abbe number at the d line.
+    pub vd: f32,
+    /// Free text comment of the catalog.
+    pub comment: String,
+    /// Thermal expansion coefficient from -30 °C to 70 °C, in 1/K.
+    pub expansion: f32,
+    /// Density, in g/cm³.
+    pub density: f32,
+    /// Temperature dependence of the index.
+    pub thermal: Thermal,
+    /// Range in which the dispersion formula is valid, in micrometres.
+    pub wavelength_range: (f32, f32),
+    /// Internal transmittance samples, by increasing wavelength.
+    pub transmission: Vec<Transmission>,
+}
+
+impl Glass {
+    /// Index relative to air at the catalog reference temperature.
+    pub fn refractive_index(&self, wavelength: f32) -> f32 {
+        self.formula
+            .refractive_index(&self.coefficients, wavelength)
+    }
+
+    /// Internal transmittance through `thickness` millimetres, interpolated between the catalog
+    /// samples and scaled from their thickness with the Beer-Lambert law.
+    pub fn transmittance(&self, wavelength: f32, thickness: f32) -> Option<f32> {
+        let scaled =
+            |sample: &Transmission| sample.transmittance.powf(thickness / sample.thickness);
+
+        let upper = self
+            .transmission
+            .iter()
+            .position(|sample| sample.wavelength >= wavelength)?;
+        let b = &self.transmission[upper];
+
+        if upper == 0 || b.wavelength == wavelength {
+            return (b.wavelength == wavelength).then(|| scaled(b));
+        }
+
+        let a = &self.transmission[upper - 1];
+        let t = (wavelength - a.wavelength) / (b.wavelength - a.wavelength);
+
+        Some(scaled(a) + t * (scaled(b) - scaled(a)))
+    }
+}
+
+/// Named collection of glasses.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    /// Name the glasses refer to the catalog by, usually the file name.
+    pub name: String,
+    /// Glasses of the catalog, in file order.
+    pub glasses: Vec<Arc<Glass>>,
+}
+
+impl Catalog {
+    /// Reads a Zemax AGF catalog, in UTF-8 or UTF-16 with byte order mark.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().to_uppercase());
+
+        Self::parse(name, &agf::decode(&bytes)?)
+    }
+
+    /// Parses the AGF `source` of the catalog `name`.
+    pub fn parse(name: impl Into<String>, source: &str) -> anyhow::Result<Self> {
+        let name = name.into();
+        let glasses = agf::parse(&name, source)?;
+
+        Ok(Self { name, glasses })
+    }
+
+    /// Looks a glass up by name, ignoring case.
+    pub fn glass(&self, name: &str) -> Option<&Arc<Glass>> {
+        self.glasses
+            .iter()
+            .find(|glass| glass.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Material of the glass `name`, failing when the catalog doesn't have it.
+    pub fn material(&self, name: &str) -> anyhow::Result<Material> {
+        self.glass(name)
+            .map(|glass| Material::Glass(glass.clone()))
+            .ok_or_else(|| anyhow::anyhow!("glass {name} not found in catalog {}", self.name))
+    }
+}
+
+/// Looks a glass up in several catalogs, the first match winning.
+pub fn find<'a>(catalogs: &'a [Catalog], name: &str) -> Option<&'a Arc<Glass>> {
+    catalogs.iter().find_map(|catalog| catalog.glass(name))
+}
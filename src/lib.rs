@@ -0,0 +1,18 @@
+//! Sequential optical system design: ray tracing on the GPU, analyses, optimization and
+//! tolerancing.
+//!
+//! A [`system::System`] is built in code or loaded from a prescription file, then traced with
+//! [`system::System::trace`] or analysed through the `Query` and `Response` types of
+//! [`analysis`]. Lengths are in millimetres and wavelengths in micrometres.
+
+#![warn(missing_docs)]
+
+pub mod analysis;
+pub mod compute;
+pub mod glass;
+pub mod optimization;
+pub mod query;
+pub mod stock;
+pub mod system;
+pub mod tolerance;
+pub mod utils;
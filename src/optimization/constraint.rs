@@ -0,0 +1,163 @@
+use nalgebra::Point2;
+
+use crate::system::System;
+
+/// Residual scale of a constraint violation of one lens unit.
+pub const PENALTY: f32 = 100.0;
+
+/// Closed interval, unbounded by default.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    /// Lower end, negative infinity when unbounded.
+    pub min: f32,
+    /// Upper end, infinity when unbounded.
+    pub max: f32,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self {
+            min: f32::NEG_INFINITY,
+            max: f32::INFINITY,
+        }
+    }
+}
+
+impl Bounds {
+    /// Interval from `min` to `max`.
+    pub fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    /// Interval bounded from below.
+    pub fn at_least(min: f32) -> Self {
+        Self {
+            min,
+            ..Default::default()
+        }
+    }
+
+    /// Interval bounded from above.
+    pub fn at_most(max: f32) -> Self {
+        Self {
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Closest value of the interval to `value`.
+    pub fn clamp(&self, value: f32) -> f32 {
+        value.max(self.min).min(self.max)
+    }
+
+    /// Signed distance from `value` to the interval, zero inside.
+    pub fn violation(&self, value: f32) -> f32 {
+        value - self.clamp(value)
+    }
+}
+
+/// Hard constraint on the geometry, turned into penalties by the merit function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constraint {
+    /// Axial thickness following a surface.
+    CenterThickness {
+        /// Surface the thickness follows.
+        surface: usize,
+        /// Allowed thicknesses.
+        bounds: Bounds,
+    },
+    /// Thickness following a surface, at the larger semi-diameter of it and the next one.
+    EdgeThickness {
+        /// Surface the thickness follows.
+        surface: usize,
+        /// Allowed thicknesses.
+        bounds: Bounds,
+    },
+    /// Distance from the first surface to the image surface.
+    TotalTrack {
+        /// Allowed lengths.
+        bounds: Bounds,
+    },
+}
+
+impl Constraint {
+    /// Constrained quantity of `system`, NaN when it lacks the constrained surfaces.
+    pub fn value(&self, system: &System) -> f32 {
+        let surfaces = &system.surfaces;
+
+        match *self {
+            Self::CenterThickness { surface, .. } => {
+                surfaces.get(surface).map_or(f32::NAN, |s| s.thickness)
+            }
+            Self::EdgeThickness { surface, .. } => {
+                let (Some(front), Some(back)) = (surfaces.get(surface), surfaces.get(surface + 1))
+                else {
+                    return f32::NAN;
+                };
+                let edge = Point2::new(0.0, front.semi_diameter.max(back.semi_diameter));
+
+                front.thickness + back.z(edge) - front.z(edge)
+            }
+            Self::TotalTrack { .. } => surfaces[..surfaces.len().saturating_sub(1)]
+                .iter()
+                .map(|surface| surface.thickness)
+                .sum(),
+        }
+    }
+
+    /// Interval the quantity is kept within.
+    pub fn bounds(&self) -> Bounds {
+        match *self {
+            Self::CenterThickness { bounds, .. }
+            | Self::EdgeThickness { bounds, .. }
+            | Self::TotalTrack { bounds } => bounds,
+        }
+    }
+
+    /// Checks that the constrained surfaces exist in `system`.
+    pub fn validate(&self, system: &System) -> anyhow::Result<()> {
+        let n_surfaces = system.surfaces.len();
+        let last = match *self {
+            Self::CenterThickness { surface, .. } => surface,
+            Self::EdgeThickness { surface, .. } => surface + 1,
+            Self::TotalTrack { .. } => 0,
+        };
+        anyhow::ensure!(
+            last < n_surfaces,
+            "no surface {last}, the system has {n_surfaces}"
+        );
+
+        Ok(())
+    }
+
+    /// Penalty residual, zero when the constraint is met.
+    pub fn residual(&self, system: &System) -> f32 {
+        PENALTY * self.bounds().violation(self.value(system))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_surfaces() {
+        let system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+        let edge = |surface| Constraint::EdgeThickness {
+            surface,
+            bounds: Bounds::at_least(0.5),
+        };
+        let error =
+            |constraint: Constraint| format!("{:#}", constraint.validate(&system).unwrap_err());
+
+        assert!(edge(0).validate(&system).is_ok());
+        assert!(edge(0).residual(&system).is_finite());
+        assert!(edge(3).value(&system).is_nan());
+        assert_eq!(error(edge(3)), "no surface 4, the system has 4");
+        let center = Constraint::CenterThickness {
+            surface: 4,
+            bounds: Bounds::default(),
+        };
+        assert_eq!(error(center), "no surface 4, the system has 4");
+    }
+}
@@ -0,0 +1,334 @@
+use std::fmt;
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    query::merit::{Merit, MeritFunction},
+    system::System,
+    utils::Plot,
+};
+
+/// Damping factors tried at every iteration, relative to the current one.
+const DAMPING_SCALES: [f32; 4] = [0.1, 1.0, 10.0, 100.0];
+
+/// Damped least-squares optimization settings.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Merit function minimized.
+    pub merit_function: MeritFunction,
+    /// Maximum number of iterations.
+    pub iterations: u32,
+    /// Initial Levenberg–Marquardt damping factor.
+    pub damping: f32,
+    /// Relative merit improvement under which the optimization stops.
+    pub tolerance: f32,
+    /// Whether to compute the Jacobian with [`MeritFunction::jacobian`] on the CPU rather than
+    /// by finite differences on the GPU.
+    pub analytic: bool,
+}
+
+impl Query {
+    /// Query minimizing `merit_function` with the default settings.
+    pub fn new(merit_function: MeritFunction) -> Self {
+        Self {
+            merit_function,
+            iterations: 50,
+            damping: 1e-3,
+            tolerance: 1e-6,
+            analytic: false,
+        }
+    }
+}
+
+/// State after an iteration.
+#[derive(Debug, Clone, Copy)]
+pub struct Iteration {
+    /// Merit value.
+    pub merit: f32,
+    /// Damping factor the step was taken with.
+    pub damping: f32,
+}
+
+/// Optimized system and its merit.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// System with the optimized variable values.
+    pub system: System,
+    /// Merit of the optimized system.
+    pub merit: Merit,
+    /// Merit before the first iteration, then after every iteration.
+    pub history: Vec<Iteration>,
+}
+
+impl Response {
+    /// Plots the merit against the iteration.
+    pub fn plot(&self) -> Plot {
+        let mut plot = Plot::new("Optimization", "Iteration", "Merit");
+        plot.line(
+            "merit",
+            self.history
+                .iter()
+                .enumerate()
+                .map(|(i, iteration)| (i as f32, iteration.merit))
+                .collect(),
+            "white",
+        );
+
+        plot
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>9} {:>14} {:>12}", "iteration", "merit", "damping")?;
+        for (i, iteration) in self.history.iter().enumerate() {
+            writeln!(
+                f,
+                "{i:>9} {:>14.8} {:>12.3e}",
+                iteration.merit, iteration.damping
+            )?;
+        }
+        write!(f, "Final merit: {:.8}", self.merit.value)
+    }
+}
+
+impl System {
+    /// Damped least-squares (Levenberg–Marquardt) optimization of the system variables, kept
+    /// within their bounds.
+    ///
+    /// Each iteration traces the finite difference Jacobian in one batch, or differentiates the
+    /// CPU tracer when [`Query::analytic`] is set, then traces the steps of several damping
+    /// factors in another batch, keeping the best one. Fails when the starting merit isn't finite,
+    /// or when no step has a finite merit however small.
+    pub async fn optimize(&self, query: &Query) -> anyhow::Result<Response> {
+        let merit_function = &query.merit_function;
+        let variables = &self.variables;
+
+        let mut system = self.solved().into_owned();
+        let mut values = system.variable_values()?;
+        let mut merit = merit_function
+            .evaluate_batch(std::slice::from_ref(&system))
+            .await?
+            .remove(0);
+        anyhow::ensure!(
+            merit.value.is_finite(),
+            "the merit of the starting system isn't finite"
+        );
+        let mut damping = query.damping;
+        let mut history = vec![Iteration {
+            merit: merit.value,
+            damping,
+        }];
+
+        for _ in 0..query.iterations {
+            if variables.is_empty() || merit.value == 0.0 {
+                break;
+            }
+
+            let residuals = DVector::from_iterator(
+                merit.residuals.len(),
+                merit.residuals.iter().map(|r| *r as f64),
+            );
+            let jacobian = if query.analytic {
+                merit_function.jacobian(&system)?.1
+            } else {
+                let steps = variables
+                    .iter()
+                    .zip(&values)
+                    .map(|(variable, value)| variable.step(*value))
+                    .collect::<Vec<_>>();
+                let perturbed = (0..variables.len())
+                    .map(|j| {
+                        let mut values = values.clone();
+                        values[j] += steps[j];
+                        system.with_variable_values(&values)
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let derivatives = merit_function.evaluate_batch(&perturbed).await?;
+
+                DMatrix::from_fn(residuals.len(), variables.len(), |i, j| {
+                    (derivatives[j].residuals[i] as f64 - residuals[i]) / steps[j] as f64
+                })
+            };
+            let normal = jacobian.transpose() * &jacobian;
+            let gradient = jacobian.transpose() * &residuals;
+
+            let candidates = DAMPING_SCALES
+                .iter()
+                .map(|scale| {
+                    let damping = damping * scale;
+                    let mut matrix = normal.clone();
+                    for i in 0..matrix.nrows() {
+                        matrix[(i, i)] += damping as f64 * normal[(i, i)].max(1e-12);
+                    }
+
+                    let delta = matrix
+                        .lu()
+                        .solve(&-&gradient)
+                        .unwrap_or_else(|| DVector::zeros(variables.len()));
+                    let values = variables
+                        .iter()
+                        .zip(&values)
+                        .zip(delta.iter())
+                        .map(|((variable, value), delta)| {
+                            variable.bounds.clamp(value + *delta as f32)
+                        })
+                        .collect::<Vec<_>>();
+
+                    (damping, values)
+                })
+                .collect::<Vec<_>>();
+            let systems = candidates
+                .iter()
+                .map(|(_, values)| system.with_variable_values(values))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let merits = merit_function.evaluate_batch(&systems).await?;
+
+            let best = merits
+                .iter()
+                .enumerate()
+                .filter(|(_, merit)| merit.value.is_finite())
+                .min_by(|(_, a), (_, b)| a.value.total_cmp(&b.value))
+                .map(|(i, _)| i);
+
+            match best {
+                Some(i) if merits[i].value < merit.value => {
+                    let improvement = (merit.value - merits[i].value) / merit.value;
+
+                    damping = candidates[i].0;
+                    values = candidates[i].1.clone();
+                    system = systems[i].clone();
+                    merit = merits[i].clone();
+                    history.push(Iteration {
+                        merit: merit.value,
+                        damping,
+                    });
+
+                    if improvement < query.tolerance {
+                        break;
+                    }
+                }
+                _ => {
+                    damping *= 1e3;
+                    history.push(Iteration {
+                        merit: merit.value,
+                        damping,
+                    });
+
+                    if damping > 1e10 {
+                        anyhow::ensure!(
+                            best.is_some(),
+                            "no step has a finite merit, even with a damping of {damping:.0e}"
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(Response {
+            system,
+            merit,
+            history,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point2;
+
+    use super::*;
+    use crate::{
+        compute::raytracing::{self, RayTracingResult},
+        optimization::{Parameter, Variable},
+        query::{
+            QueryParameter,
+            merit::{Operand, Target},
+            ray_height::RayHeight,
+        },
+    };
+
+    fn defocused() -> System {
+        let mut system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        system.surfaces[2].thickness = 14.0;
+        system.variables = vec![Variable::new(2, Parameter::Thickness)];
+
+        system
+    }
+
+    #[tokio::test]
+    async fn focuses_the_marginal_ray() {
+        let system = defocused();
+        let marginal = RayHeight {
+            field: 0,
+            pupil: Point2::new(0.0, 1.0),
+            surface: 3,
+        };
+        let rays = marginal.rays(&system);
+        let merit_function =
+            MeritFunction::new(vec![Operand::new(marginal, Target::Equal(0.0), 1.0)]);
+
+        // Back focus where the real marginal ray crosses the axis
+        let ray = system.trace_cpu(&raytracing::Query { rays }).intersections[3].ray;
+        let crossing = ray.origin.z - ray.origin.y * ray.direction.z / ray.direction.y;
+        let expected = crossing - system.surfaces[0].thickness - system.surfaces[1].thickness;
+
+        for analytic in [false, true] {
+            let query = Query {
+                analytic,
+                ..Query::new(merit_function.clone())
+            };
+            let response = system.optimize(&query).await.unwrap();
+
+            let start = response.history[0].merit;
+            assert!(start > 0.1, "{start}");
+            assert!(response.merit.value < 1e-4 * start, "{response}");
+            assert!(
+                response
+                    .history
+                    .windows(2)
+                    .all(|pair| pair[1].merit <= pair[0].merit),
+                "{response}"
+            );
+
+            let thickness = response.system.surfaces[2].thickness;
+            assert!(
+                (thickness - expected).abs() < 1e-3,
+                "{thickness} instead of {expected}"
+            );
+        }
+    }
+
+    /// Quantity only defined at the starting back focus.
+    #[derive(Debug)]
+    struct Fragile;
+
+    impl QueryParameter for Fragile {
+        type Output = f32;
+
+        fn query(&self, raytrace: &RayTracingResult) -> f32 {
+            if raytrace.system.surfaces[2].thickness == 14.0 {
+                1.0
+            } else {
+                f32::NAN
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_without_finite_steps() {
+        let system = defocused();
+        let query = Query::new(MeritFunction::new(vec![Operand::new(
+            Fragile,
+            Target::Equal(0.0),
+            1.0,
+        )]));
+
+        let error = system.optimize(&query).await.unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "no step has a finite merit, even with a damping of 1e12"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Global optimizers exploring the variable ranges for several good designs.
+
+use std::{
+    fmt,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use anyhow::Context;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use tokio::{runtime::Handle, sync::watch, task::JoinHandle};
+
+use crate::system::System;
+
+/// Exploration of the variable ranges.
+#[derive(Debug, Clone, Copy)]
+pub enum Strategy {
+    /// Random starting points within the variable ranges, each refined by damped least-squares.
+    MultiStart {
+        /// Number of starting points.
+        starts: u32,
+    },
+    /// Metropolis walk over the variable ranges with a geometrically decreasing temperature,
+    /// `proposals` neighbours being traced in one batch per step.
+    SimulatedAnnealing {
+        /// Number of temperature steps.
+        steps: u32,
+        /// Number of neighbours traced at every step.
+        proposals: u32,
+        /// Initial temperature, in merit units.
+        temperature: f32,
+        /// Factor applied to the temperature after every step.
+        cooling: f32,
+    },
+}
+
+/// Global optimization settings.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Merit function and settings of the local refinements.
+    pub local: super::Query,
+    /// How the variable ranges are explored.
+    pub strategy: Strategy,
+    /// Number of designs kept.
+    pub keep: usize,
+    /// Seed of the random number generator, for reproducible searches.
+    pub seed: u64,
+}
+
+/// Design found by a global search.
+#[derive(Debug, Clone)]
+pub struct Design {
+    /// System with the design variable values.
+    pub system: System,
+    /// Variable values of the design.
+    pub values: Vec<f32>,
+    /// Merit value of the design.
+    pub merit: f32,
+}
+
+/// Best designs found, by increasing merit.
+#[derive(Debug, Clone, Default)]
+pub struct Response {
+    /// Designs, the best first.
+    pub designs: Vec<Design>,
+}
+
+impl Response {
+    /// Saves every design as a TOML prescription `design-{rank}.toml` in `dir`, ranks starting at
+    /// 1 for the best design.
+    pub fn save(&self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+
+        for (i, design) in self.designs.iter().enumerate() {
+            let path = dir.join(format!("design-{}.toml", i + 1));
+            design
+                .system
+                .save(&path)
+                .with_context(|| format!("writing {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Inserts `design` by rank, returning whether it is among the `keep` best.
+    fn insert(&mut self, design: Design, keep: usize) -> bool {
+        if !design.merit.is_finite() || self.designs.iter().any(|d| d.values == design.values) {
+            return false;
+        }
+
+        let index = self.designs.partition_point(|d| d.merit <= design.merit);
+        if index < keep {
+            self.designs.insert(index, design);
+            self.designs.truncate(keep);
+        }
+
+        index < keep
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>6} {:>14}  variables", "rank", "merit")?;
+        for (i, design) in self.designs.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>6} {:>14.8}  {:?}",
+                i + 1,
+                design.merit,
+                design.values
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Global optimization running on a blocking thread of the tokio runtime, as it waits on the
+/// GPU.
+pub struct Search {
+    /// Best designs found so far.
+    pub best: watch::Receiver<Response>,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<anyhow::Result<Response>>,
+}
+
+impl Search {
+    /// Asks the search to return after the step in progress.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the search has returned.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the search to return its best designs.
+    pub async fn join(self) -> anyhow::Result<Response> {
+        self.handle.await?
+    }
+}
+
+impl System {
+    /// Global optimization of the system variables, returning the best designs found.
+    pub async fn optimize_global(&self, query: &Query) -> anyhow::Result<Response> {
+        let (sender, _) = watch::channel(Response::default());
+
+        self.solved()
+            .search(query, &sender, &AtomicBool::new(false))
+            .await
+    }
+
+    /// Same as [`System::optimize_global`], running in the background. Must be called from
+    /// within a tokio runtime.
+    pub fn spawn_global_optimization(&self, query: Query) -> Search {
+        let (sender, best) = watch::channel(Response::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let system = self.solved().into_owned();
+
+        let runtime = Handle::current();
+        let handle = tokio::task::spawn_blocking({
+            let stop = stop.clone();
+            move || runtime.block_on(system.search(&query, &sender, &stop))
+        });
+
+        Search { best, stop, handle }
+    }
+
+    async fn search(
+        &self,
+        query: &Query,
+        best: &watch::Sender<Response>,
+        stop: &AtomicBool,
+    ) -> anyhow::Result<Response> {
+        let merit_function = &query.local.merit_function;
+        let mut rng = StdRng::seed_from_u64(query.seed);
+
+        let start = self.variable_values()?;
+        let ranges = self
+            .variables
+            .iter()
+            .zip(&start)
+            .map(|(variable, value)| variable.range(*value))
+            .collect::<Vec<_>>();
+
+        // The best designs live in the channel, receivers being woken only when one is kept
+        let publish = |design: Design| {
+            best.send_if_modified(|response| response.insert(design, query.keep));
+        };
+
+        match query.strategy {
+            Strategy::MultiStart { starts } => {
+                for i in 0..starts {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    // The first start is the current design itself
+                    let values = if i == 0 {
+                        start.clone()
+                    } else {
+                        ranges
+                            .iter()
+                            .map(|&(min, max)| sample(&mut rng, min, max))
+                            .collect()
+                    };
+
+                    let local = self
+                        .with_variable_values(&values)?
+                        .optimize(&query.local)
+                        .await?;
+                    publish(Design {
+                        values: local.system.variable_values()?,
+                        merit: local.merit.value,
+                        system: local.system,
+                    });
+                }
+            }
+            Strategy::SimulatedAnnealing {
+                steps,
+                proposals,
+                temperature,
+                cooling,
+            } => {
+                let mut values = start.clone();
+                let mut merit = merit_function
+                    .evaluate_batch(std::slice::from_ref(self))
+                    .await?[0]
+                    .value;
+                let mut temperature = temperature;
+
+                publish(Design {
+                    system: self.clone(),
+                    values: values.clone(),
+                    merit,
+                });
+
+                for step in 0..steps {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    // Neighbourhood shrinking along with the temperature
+                    let scale = 1.0 - step as f32 / steps as f32;
+                    let candidates = (0..proposals.max(1))
+                        .map(|_| {
+                            values
+                                .iter()
+                                .zip(&ranges)
+                                .map(|(value, &(min, max))| {
+                                    let half = 0.5 * (max - min) * scale;
+                                    sample(&mut rng, value - half, value + half).clamp(min, max)
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .collect::<Vec<_>>();
+                    let systems = candidates
+                        .iter()
+                        .map(|values| self.with_variable_values(values))
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    let merits = merit_function.evaluate_batch(&systems).await?;
+
+                    for ((candidate, system), candidate_merit) in
+                        candidates.into_iter().zip(systems).zip(merits)
+                    {
+                        let delta = candidate_merit.value - merit;
+                        if !candidate_merit.value.is_finite() {
+                            continue;
+                        }
+
+                        if delta < 0.0 || rng.r#gen::<f32>() < (-delta / temperature).exp() {
+                            values = candidate.clone();
+                            merit = candidate_merit.value;
+                        }
+
+                        publish(Design {
+                            system,
+                            values: candidate,
+                            merit: candidate_merit.value,
+                        });
+                    }
+
+                    temperature *= cooling;
+                }
+
+                // Hammer the best designs down to their local minimum
+                let designs = best.borrow().designs.clone();
+                let mut refined = Response::default();
+                for design in &designs {
+                    let local = design.system.optimize(&query.local).await?;
+                    refined.insert(
+                        Design {
+                            values: local.system.variable_values()?,
+                            merit: local.merit.value,
+                            system: local.system,
+                        },
+                        query.keep,
+                    );
+                }
+                best.send_replace(refined);
+            }
+        }
+
+        Ok(best.borrow().clone())
+    }
+}
+
+fn sample(rng: &mut StdRng, min: f32, max: f32) -> f32 {
+    if min < max {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_designs_by_rank() {
+        let system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+        let design = |thickness: f32, merit: f32| {
+            let mut system = system.clone();
+            system.surfaces[2].thickness = thickness;
+
+            Design {
+                system,
+                values: vec![thickness],
+                merit,
+            }
+        };
+        let mut response = Response::default();
+        assert!(response.insert(design(11.0, 2.0), 2));
+        assert!(response.insert(design(10.0, 1.0), 2));
+        assert!(!response.insert(design(12.0, 3.0), 2));
+        assert!(!response.insert(design(10.0, 1.0), 2));
+
+        let dir = std::env::temp_dir().join(format!("light-designs-{}", std::process::id()));
+        response.save(&dir).unwrap();
+
+        let best = System::load(dir.join("design-1.toml"), &[]).unwrap();
+        let second = System::load(dir.join("design-2.toml"), &[]).unwrap();
+        assert_eq!(best.surfaces[2].thickness, 10.0);
+        assert_eq!(second.surfaces[2].thickness, 11.0);
+        assert!(!dir.join("design-3.toml").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
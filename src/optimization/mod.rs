@@ -0,0 +1,10 @@
+//! Variables, constraints and the local and global optimizers.
+
+mod constraint;
+mod dls;
+pub mod global;
+mod variable;
+
+pub use constraint::*;
+pub use dls::*;
+pub use variable::*;
@@ -0,0 +1,330 @@
+use super::Bounds;
+use crate::{
+    compute::{cpu::Parameters, dual::Dual},
+    query::Derivative,
+    system::{Material, Override, System},
+};
+
+/// Surface parameter the optimizers may change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parameter {
+    /// Inverse of the radius of curvature.
+    Curvature,
+    /// Distance to the next surface.
+    Thickness,
+    /// Conic constant.
+    Conic,
+    /// Index of the medium following the surface, `nd` for model glasses.
+    Index,
+    /// Abbe number of the model glass following the surface.
+    Abbe,
+}
+
+impl Parameter {
+    /// Finite difference step used to estimate derivatives.
+    pub fn step(self) -> f32 {
+        match self {
+            Self::Curvature => 1e-4,
+            Self::Thickness => 1e-3,
+            Self::Conic => 1e-3,
+            Self::Index => 1e-4,
+            Self::Abbe => 1e-2,
+        }
+    }
+
+    /// Half-width of the region explored by the global optimizers around the starting value,
+    /// when the bounds don't restrict it further.
+    pub fn span(self) -> f32 {
+        match self {
+            Self::Curvature => 0.05,
+            Self::Thickness => 2.0,
+            Self::Conic => 1.0,
+            Self::Index => 0.1,
+            Self::Abbe => 10.0,
+        }
+    }
+}
+
+/// Surface parameter marked as free during design.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variable {
+    /// Index of the surface.
+    pub surface: usize,
+    /// Parameter of the surface, or of the medium following it.
+    pub parameter: Parameter,
+    /// Values the optimizers keep the parameter within.
+    pub bounds: Bounds,
+    /// Configuration whose override of the parameter the variable edits, the surface itself
+    /// when `None`.
+    pub configuration: Option<usize>,
+}
+
+impl Variable {
+    /// Unbounded variable on `parameter` of `surface`.
+    pub fn new(surface: usize, parameter: Parameter) -> Self {
+        Self {
+            surface,
+            parameter,
+            bounds: Bounds::default(),
+            configuration: None,
+        }
+    }
+
+    /// The variable kept within `bounds`.
+    pub fn bounded(self, bounds: Bounds) -> Self {
+        Self { bounds, ..self }
+    }
+
+    /// The variable editing the override of the parameter in `configuration`, which must exist.
+    pub fn in_configuration(self, configuration: usize) -> Self {
+        Self {
+            configuration: Some(configuration),
+            ..self
+        }
+    }
+
+    /// Whether `value` replaces the parameter of the variable surface.
+    fn overrides(&self, value: &Override) -> bool {
+        match (self.parameter, value) {
+            (Parameter::Thickness, Override::Thickness { surface, .. })
+            | (Parameter::Curvature, Override::Curvature { surface, .. })
+            | (Parameter::Index | Parameter::Abbe, Override::Material { surface, .. }) => {
+                *surface == self.surface
+            }
+            _ => false,
+        }
+    }
+
+    /// First configuration of `system` replacing the parameter of the surface.
+    pub fn overridden_by(&self, system: &System) -> Option<usize> {
+        system.configurations.iter().position(|configuration| {
+            configuration
+                .overrides
+                .iter()
+                .any(|value| self.overrides(value))
+        })
+    }
+
+    /// Override the variable edits in `configuration`.
+    fn override_index(&self, system: &System, configuration: usize) -> anyhow::Result<usize> {
+        let overrides = &system
+            .configurations
+            .get(configuration)
+            .ok_or_else(|| anyhow::anyhow!("no configuration {configuration}"))?
+            .overrides;
+
+        overrides
+            .iter()
+            .position(|value| self.overrides(value))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "configuration {configuration} doesn't override the {:?} of surface {}",
+                    self.parameter,
+                    self.surface
+                )
+            })
+    }
+
+    /// Checks that the variable can be read from `system` and isn't replaced by a configuration.
+    pub fn validate(&self, system: &System) -> anyhow::Result<()> {
+        self.value(system)?;
+        if self.configuration.is_none()
+            && let Some(index) = self.overridden_by(system)
+        {
+            anyhow::bail!(
+                "the {:?} of surface {} is overridden by configuration {index} ({}), vary the \
+                 override instead",
+                self.parameter,
+                self.surface,
+                system.configurations[index].name
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Range explored by the global optimizers when starting from `value`.
+    pub fn range(&self, value: f32) -> (f32, f32) {
+        let span = self.parameter.span();
+
+        (
+            self.bounds.clamp(value - span),
+            self.bounds.clamp(value + span),
+        )
+    }
+
+    /// Finite difference step, pointing back inside the bounds near the upper one.
+    pub fn step(&self, value: f32) -> f32 {
+        let step = self.parameter.step();
+
+        if value + step > self.bounds.max {
+            -step
+        } else {
+            step
+        }
+    }
+
+    /// Whether the variable is a surface parameter the tracer can differentiate.
+    pub fn is_geometric(&self) -> bool {
+        self.configuration.is_none()
+            && matches!(
+                self.parameter,
+                Parameter::Curvature | Parameter::Thickness | Parameter::Conic
+            )
+    }
+
+    /// Whether the variable moves the paraxial entrance pupil of `system`, the image of the stop
+    /// through the surfaces before it.
+    pub fn moves_entrance_pupil(&self, system: &System) -> bool {
+        matches!(self.parameter, Parameter::Curvature | Parameter::Thickness)
+            && self.surface < system.stop_index as usize
+    }
+
+    /// Makes the variable the `index`-th input of the dual `parameters`.
+    pub fn seed(
+        &self,
+        parameters: &mut [Parameters<Derivative>],
+        index: usize,
+    ) -> anyhow::Result<()> {
+        let surface = parameters
+            .get_mut(self.surface)
+            .ok_or_else(|| anyhow::anyhow!("no surface {}", self.surface))?;
+
+        match self.parameter {
+            Parameter::Curvature => {
+                surface.curvature = Dual::variable(surface.curvature.value as f32, index)
+            }
+            Parameter::Thickness => {
+                surface.thickness = Dual::variable(surface.thickness.value as f32, index)
+            }
+            Parameter::Conic => surface.conic = Dual::variable(surface.conic.value as f32, index),
+            _ => anyhow::bail!("{:?} can't be differentiated by the tracer", self.parameter),
+        }
+
+        Ok(())
+    }
+
+    /// Current value of the parameter in `system`.
+    pub fn value(&self, system: &System) -> anyhow::Result<f32> {
+        if let Some(configuration) = self.configuration {
+            let index = self.override_index(system, configuration)?;
+
+            return match &system.configurations[configuration].overrides[index] {
+                Override::Thickness { value, .. } | Override::Curvature { value, .. } => Ok(*value),
+                Override::Material { material, .. } => self.material_value(material),
+                _ => unreachable!("overrides() only matches surface values"),
+            };
+        }
+
+        let surface = system
+            .surfaces
+            .get(self.surface)
+            .ok_or_else(|| anyhow::anyhow!("no surface {}", self.surface))?;
+
+        Ok(match self.parameter {
+            Parameter::Curvature => surface.curvature,
+            Parameter::Thickness => surface.thickness,
+            Parameter::Conic => surface.conic,
+            Parameter::Index | Parameter::Abbe => self.material_value(&surface.material)?,
+        })
+    }
+
+    /// Sets the parameter in `system` to `value`.
+    pub fn set(&self, system: &mut System, value: f32) -> anyhow::Result<()> {
+        if let Some(configuration) = self.configuration {
+            let index = self.override_index(system, configuration)?;
+
+            return match &mut system.configurations[configuration].overrides[index] {
+                Override::Thickness { value: old, .. } | Override::Curvature { value: old, .. } => {
+                    *old = value;
+                    Ok(())
+                }
+                Override::Material { material, .. } => self.set_material(material, value),
+                _ => unreachable!("overrides() only matches surface values"),
+            };
+        }
+
+        let surface = system
+            .surfaces
+            .get_mut(self.surface)
+            .ok_or_else(|| anyhow::anyhow!("no surface {}", self.surface))?;
+
+        match self.parameter {
+            Parameter::Curvature => surface.curvature = value,
+            Parameter::Thickness => surface.thickness = value,
+            Parameter::Conic => surface.conic = value,
+            Parameter::Index | Parameter::Abbe => {
+                self.set_material(&mut surface.material, value)?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn material_value(&self, material: &Material) -> anyhow::Result<f32> {
+        Ok(match (self.parameter, material) {
+            (Parameter::Index, Material::Constant(n)) => *n,
+            (Parameter::Index, Material::Model { nd, .. }) => *nd,
+            (Parameter::Abbe, Material::Model { vd, .. }) => *vd,
+            (Parameter::Index, _) => anyhow::bail!(
+                "the material of surface {} has no variable index",
+                self.surface
+            ),
+            _ => anyhow::bail!(
+                "the material of surface {} isn't a model glass",
+                self.surface
+            ),
+        })
+    }
+
+    fn set_material(&self, material: &mut Material, value: f32) -> anyhow::Result<()> {
+        match (self.parameter, material) {
+            (Parameter::Index, Material::Constant(n)) => *n = value,
+            (Parameter::Index, Material::Model { nd, .. }) => *nd = value,
+            (Parameter::Abbe, Material::Model { vd, .. }) => *vd = value,
+            (Parameter::Index, _) => anyhow::bail!(
+                "the material of surface {} has no variable index",
+                self.surface
+            ),
+            _ => anyhow::bail!(
+                "the material of surface {} isn't a model glass",
+                self.surface
+            ),
+        }
+
+        Ok(())
+    }
+}
+
+impl System {
+    /// Current values of the variables, failing on a variable a configuration overrides.
+    pub fn variable_values(&self) -> anyhow::Result<Vec<f32>> {
+        self.variables
+            .iter()
+            .map(|variable| {
+                variable.validate(self)?;
+                variable.value(self)
+            })
+            .collect()
+    }
+
+    /// Copy of the system with every variable set to the matching entry of `values`, clamped to
+    /// its bounds, and the solves evaluated.
+    pub fn with_variable_values(&self, values: &[f32]) -> anyhow::Result<System> {
+        let mut system = self.clone();
+        for (variable, value) in self.variables.iter().zip(values) {
+            variable.set(&mut system, variable.bounds.clamp(*value))?;
+        }
+        system.solve();
+
+        Ok(system)
+    }
+
+    /// Penalty residuals of the constraints.
+    pub fn constraint_residuals(&self) -> Vec<f32> {
+        self.constraints
+            .iter()
+            .map(|constraint| constraint.residual(self))
+            .collect()
+    }
+}
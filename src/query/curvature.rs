@@ -1,13 +1,31 @@
-use super::QueryParameter;
+//! Surface curvatures.
 
+use super::{Derivative, QueryParameter, check_surface};
+use crate::{compute::cpu::Parameters, system::System};
+
+/// Curvature of a surface.
+#[derive(Debug, Clone)]
 pub struct Curvature {
+    /// Index of the surface.
     pub index: usize,
 }
 
 impl QueryParameter for Curvature {
     type Output = f32;
 
+    fn validate(&self, system: &System) -> anyhow::Result<()> {
+        check_surface(system, self.index)
+    }
+
     fn query(&self, raytrace: &super::RayTracingResult) -> Self::Output {
         raytrace.system.surfaces[self.index].curvature
     }
+
+    fn query_dual(
+        &self,
+        _system: &System,
+        parameters: &[Parameters<Derivative>],
+    ) -> Option<Derivative> {
+        Some(parameters[self.index].curvature)
+    }
 }
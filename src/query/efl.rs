@@ -0,0 +1,17 @@
+//! Paraxial focal length.
+
+use super::QueryParameter;
+
+/// Paraxial effective focal length at the primary wavelength.
+#[derive(Debug, Clone)]
+pub struct EffectiveFocalLength;
+
+impl QueryParameter for EffectiveFocalLength {
+    type Output = f32;
+
+    fn query(&self, raytrace: &super::RayTracingResult) -> Self::Output {
+        let system = raytrace.system;
+
+        system.effective_focal_length(system.primary_wavelength())
+    }
+}
@@ -0,0 +1,641 @@
+//! Merit functions combining query parameters, and their derivatives.
+
+use std::{fmt, ops::Range, sync::Arc};
+
+use anyhow::Context;
+use nalgebra::DMatrix;
+
+use super::{DUAL_WIDTH, QueryParameter};
+use crate::{
+    compute::raytracing::{self, RayTracingResult},
+    system::System,
+};
+
+/// Value an operand is driven towards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    /// Exact value.
+    Equal(f32),
+    /// Upper bound, the operand only contributes above it.
+    AtMost(f32),
+    /// Lower bound, the operand only contributes below it.
+    AtLeast(f32),
+}
+
+impl Target {
+    /// Signed distance from `value` to the target, zero within bounds.
+    pub fn error(&self, value: f32) -> f32 {
+        match *self {
+            Self::Equal(target) => value - target,
+            Self::AtMost(max) => (value - max).max(0.0),
+            Self::AtLeast(min) => (value - min).min(0.0),
+        }
+    }
+
+    /// Derivative of [`Target::error`] with respect to the value.
+    pub fn slope(&self, value: f32) -> f32 {
+        match *self {
+            Self::Equal(_) => 1.0,
+            Self::AtMost(max) if value > max => 1.0,
+            Self::AtLeast(min) if value < min => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Query parameter driven towards a target, in one or every configuration.
+#[derive(Debug, Clone)]
+pub struct Operand {
+    /// Quantity the operand evaluates.
+    pub parameter: Arc<dyn QueryParameter<Output = f32> + Send + Sync>,
+    /// Value the quantity is driven towards.
+    pub target: Target,
+    /// Relative importance of the operand, its error being scaled by the square root.
+    pub weight: f32,
+    /// Configuration the operand applies to, every one when `None`.
+    pub configuration: Option<usize>,
+}
+
+impl Operand {
+    /// Operand of every configuration.
+    pub fn new(
+        parameter: impl QueryParameter<Output = f32> + Send + Sync + 'static,
+        target: Target,
+        weight: f32,
+    ) -> Self {
+        Self {
+            parameter: Arc::new(parameter),
+            target,
+            weight,
+            configuration: None,
+        }
+    }
+
+    /// The operand restricted to `configuration`.
+    pub fn in_configuration(self, configuration: usize) -> Self {
+        Self {
+            configuration: Some(configuration),
+            ..self
+        }
+    }
+}
+
+/// Every configuration of `system` with its solves evaluated, so that the operands and
+/// constraints read the same surfaces as the traces.
+fn solved_configurations(system: &System) -> anyhow::Result<Vec<System>> {
+    Ok(system
+        .all_configurations()?
+        .into_iter()
+        .map(|system| system.solved().into_owned())
+        .collect())
+}
+
+/// Weighted operands combined into a root-sum-square merit value.
+#[derive(Debug, Clone, Default)]
+pub struct MeritFunction {
+    /// Operands of the merit function, in report order.
+    pub operands: Vec<Operand>,
+}
+
+/// Merit function evaluated on a system.
+#[derive(Debug, Clone)]
+pub struct Merit {
+    /// Configuration and operand index of every value.
+    pub rows: Vec<(usize, usize)>,
+    /// Value of the operand of every row.
+    pub values: Vec<f32>,
+    /// Target errors scaled by the square root of the operand weights, followed by the penalties
+    /// of the system constraints in every configuration.
+    pub residuals: Vec<f32>,
+    /// Root sum of squares of the residuals.
+    pub value: f32,
+}
+
+impl MeritFunction {
+    /// Merit function of `operands`.
+    pub fn new(operands: Vec<Operand>) -> Self {
+        Self { operands }
+    }
+
+    /// Configuration and operand index of the values evaluated on `n_configurations`
+    /// configurations.
+    pub fn rows(&self, n_configurations: usize) -> Vec<(usize, usize)> {
+        (0..n_configurations)
+            .flat_map(|configuration| {
+                self.operands
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, operand)| {
+                        operand
+                            .configuration
+                            .is_none_or(|index| index == configuration)
+                    })
+                    .map(move |(i, _)| (configuration, i))
+            })
+            .collect()
+    }
+
+    /// Checks the operands and the constraints against the solved `configurations` of a system.
+    pub fn validate(&self, configurations: &[System]) -> anyhow::Result<()> {
+        for (configuration, system) in configurations.iter().enumerate() {
+            for (i, constraint) in system.constraints.iter().enumerate() {
+                constraint.validate(system).with_context(|| {
+                    if configurations.len() > 1 {
+                        format!("constraint {i} in configuration {configuration}")
+                    } else {
+                        format!("constraint {i}")
+                    }
+                })?;
+            }
+        }
+
+        for (configuration, i) in self.rows(configurations.len()) {
+            self.operands[i]
+                .parameter
+                .validate(&configurations[configuration])
+                .with_context(|| {
+                    if configurations.len() > 1 {
+                        format!("operand {i} in configuration {configuration}")
+                    } else {
+                        format!("operand {i}")
+                    }
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Gathers the rays the operands need in `configuration`, set up as `system`, in a single
+    /// query, along with the range each operand owns.
+    pub fn query(
+        &self,
+        system: &System,
+        configuration: usize,
+    ) -> (raytracing::Query, Vec<Range<usize>>) {
+        let mut rays = Vec::new();
+        let ranges = self
+            .operands
+            .iter()
+            .map(|operand| {
+                let start = rays.len();
+                if operand
+                    .configuration
+                    .is_none_or(|index| index == configuration)
+                {
+                    rays.extend(operand.parameter.rays(system));
+                }
+
+                start..rays.len()
+            })
+            .collect();
+
+        (raytracing::Query { rays }, ranges)
+    }
+
+    /// Evaluates the operands from the responses to [`MeritFunction::query`] of every
+    /// configuration.
+    pub fn merit(
+        &self,
+        configurations: &[System],
+        queries: &[raytracing::Query],
+        ranges: &[Vec<Range<usize>>],
+        responses: &[raytracing::Response],
+    ) -> Merit {
+        let rows = self.rows(configurations.len());
+
+        let values = rows
+            .iter()
+            .map(|(configuration, i)| {
+                let system = &configurations[*configuration];
+                let range = ranges[*configuration][*i].clone();
+                let n_surfaces = system.surfaces.len();
+
+                self.operands[*i].parameter.query(&RayTracingResult {
+                    system,
+                    rays: &queries[*configuration].rays[range.clone()],
+                    intersections: &responses[*configuration].intersections
+                        [range.start * n_surfaces..range.end * n_surfaces],
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let residuals = rows
+            .iter()
+            .zip(&values)
+            .map(|((_, i), value)| {
+                let operand = &self.operands[*i];
+
+                operand.weight.sqrt() * operand.target.error(*value)
+            })
+            .chain(configurations.iter().flat_map(System::constraint_residuals))
+            .collect::<Vec<_>>();
+
+        Merit {
+            value: residuals.iter().map(|r| r * r).sum::<f32>().sqrt(),
+            rows,
+            values,
+            residuals,
+        }
+    }
+
+    /// Evaluates the merit function over every configuration from one batched trace on the GPU.
+    pub async fn evaluate(&self, system: &System) -> anyhow::Result<Merit> {
+        Ok(self
+            .evaluate_batch(std::slice::from_ref(system))
+            .await?
+            .remove(0))
+    }
+
+    /// Evaluates the merit function of several systems sharing their object medium and number
+    /// of surfaces, from one batched trace on the GPU.
+    pub async fn evaluate_batch(&self, systems: &[System]) -> anyhow::Result<Vec<Merit>> {
+        let configurations = systems
+            .iter()
+            .map(solved_configurations)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for configurations in &configurations {
+            self.validate(configurations)?;
+        }
+        let (queries, ranges): (Vec<_>, Vec<_>) = configurations
+            .iter()
+            .flat_map(|configurations| {
+                configurations
+                    .iter()
+                    .enumerate()
+                    .map(|(index, system)| self.query(system, index))
+            })
+            .unzip();
+        let mut responses = System::trace_batch(&configurations.concat(), &queries)
+            .await?
+            .into_iter();
+
+        let mut start = 0;
+        Ok(configurations
+            .iter()
+            .map(|configurations| {
+                let end = start + configurations.len();
+                let responses = responses
+                    .by_ref()
+                    .take(configurations.len())
+                    .collect::<Vec<_>>();
+                let merit = self.merit(
+                    configurations,
+                    &queries[start..end],
+                    &ranges[start..end],
+                    &responses,
+                );
+                start = end;
+
+                merit
+            })
+            .collect())
+    }
+
+    /// Same as [`MeritFunction::evaluate`], traced on the CPU.
+    pub fn evaluate_cpu(&self, system: &System) -> anyhow::Result<Merit> {
+        let configurations = solved_configurations(system)?;
+        self.validate(&configurations)?;
+        let (queries, ranges): (Vec<_>, Vec<_>) = configurations
+            .iter()
+            .enumerate()
+            .map(|(index, system)| self.query(system, index))
+            .unzip();
+        let responses = configurations
+            .iter()
+            .zip(&queries)
+            .map(|(system, query)| system.trace_cpu(query))
+            .collect::<Vec<_>>();
+
+        Ok(self.merit(&configurations, &queries, &ranges, &responses))
+    }
+
+    /// Evaluates the merit function on the CPU along with the Jacobian of its residuals with
+    /// respect to the system variables.
+    ///
+    /// Derivatives with respect to curvatures, thicknesses and conics are traced with dual
+    /// numbers, [`DUAL_WIDTH`] variables at a time. Finite differences fill in the material
+    /// variables, the operands that don't support [`QueryParameter::query_dual`], the aimed
+    /// operands with respect to variables moving the entrance pupil and the constraints, or
+    /// everything when the system has solves or configurations, which the tracer doesn't
+    /// differentiate.
+    pub fn jacobian(&self, system: &System) -> anyhow::Result<(Merit, DMatrix<f64>)> {
+        let system = &*system.solved();
+        let merit = self.evaluate_cpu(system)?;
+        let variables = &system.variables;
+        let n_rows = merit.rows.len();
+        let mut jacobian = DMatrix::zeros(merit.residuals.len(), variables.len());
+
+        // Rows still lacking the derivatives of each variable
+        let mut missing = vec![vec![true; n_rows]; variables.len()];
+
+        let differentiable = system.solves.is_empty() && system.configurations.is_empty();
+        let geometric = (0..variables.len())
+            .filter(|j| differentiable && variables[*j].is_geometric())
+            .collect::<Vec<_>>();
+        for chunk in geometric.chunks(DUAL_WIDTH) {
+            let mut parameters = system.parameters();
+            for (k, j) in chunk.iter().enumerate() {
+                variables[*j].seed(&mut parameters, k)?;
+            }
+
+            for (i, ((_, operand), value)) in merit.rows.iter().zip(&merit.values).enumerate() {
+                let operand = &self.operands[*operand];
+                let Some(derivative) = operand.parameter.query_dual(system, &parameters) else {
+                    continue;
+                };
+
+                let scale = (operand.weight.sqrt() * operand.target.slope(*value)) as f64;
+                for (k, j) in chunk.iter().enumerate() {
+                    // The ray aiming isn't differentiated
+                    if operand.parameter.is_aimed() && variables[*j].moves_entrance_pupil(system) {
+                        continue;
+                    }
+
+                    jacobian[(i, *j)] = scale * derivative.derivatives[k];
+                    missing[*j][i] = false;
+                }
+            }
+        }
+
+        let values = system.variable_values()?;
+        for (j, variable) in variables.iter().enumerate() {
+            let step = variable.step(values[j]);
+            let mut perturbed = values.clone();
+            perturbed[j] += step;
+            let perturbed = system.with_variable_values(&perturbed)?;
+
+            // Constraints don't need tracing
+            let residuals = if missing[j].iter().any(|missing| *missing) {
+                self.evaluate_cpu(&perturbed)?.residuals
+            } else {
+                let mut residuals = merit.residuals[..n_rows].to_vec();
+                residuals.extend(perturbed.constraint_residuals());
+                residuals
+            };
+
+            for (i, residual) in residuals.iter().enumerate() {
+                if i >= n_rows || missing[j][i] {
+                    jacobian[(i, j)] = (residual - merit.residuals[i]) as f64 / step as f64;
+                }
+            }
+        }
+
+        Ok((merit, jacobian))
+    }
+
+    /// Table of the operands of `merit`, which must come from this function.
+    pub fn report<'a>(&'a self, merit: &'a Merit) -> Report<'a> {
+        Report {
+            function: self,
+            merit,
+        }
+    }
+}
+
+/// Table of the operands of `merit`, which must come from `function`.
+pub struct Report<'a> {
+    /// Merit function `merit` was evaluated from.
+    pub function: &'a MeritFunction,
+    /// Evaluated merit.
+    pub merit: &'a Merit,
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<48} {:>12} {:>20} {:>8} {:>12}",
+            "operand", "value", "target", "weight", "residual"
+        )?;
+
+        let configurations = self
+            .merit
+            .rows
+            .iter()
+            .any(|(configuration, _)| *configuration > 0);
+        for (((configuration, i), value), residual) in self
+            .merit
+            .rows
+            .iter()
+            .zip(&self.merit.values)
+            .zip(&self.merit.residuals)
+        {
+            let operand = &self.function.operands[*i];
+            let parameter = if configurations {
+                format!("{configuration}: {:?}", operand.parameter)
+            } else {
+                format!("{:?}", operand.parameter)
+            };
+            let target = format!("{:?}", operand.target);
+            writeln!(
+                f,
+                "{parameter:<48.48} {value:>12.6} {target:>20} {:>8.3} {residual:>12.6}",
+                operand.weight
+            )?;
+        }
+
+        let penalties = &self.merit.residuals[self.merit.rows.len()..];
+        if !penalties.is_empty() {
+            let penalty = penalties.iter().map(|r| r * r).sum::<f32>().sqrt();
+            writeln!(f, "constraint penalty: {penalty:.6}")?;
+        }
+
+        writeln!(f, "merit: {:.6}", self.merit.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point2;
+
+    use super::*;
+    use crate::{
+        optimization::{Bounds, Constraint, Parameter, Variable},
+        query::{
+            efl::EffectiveFocalLength, ray_height::RayHeight, spot::SpotRms, thickness::Thickness,
+        },
+        system::{Configuration, Override, Solve},
+    };
+
+    #[test]
+    fn solves_before_evaluating() {
+        let mut system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+        system.stop_index = 0;
+        system.solves = vec![Solve::MarginalHeight {
+            surface: 2,
+            height: 0.0,
+        }];
+        system.variables = vec![Variable::new(0, Parameter::Curvature)];
+
+        let merit_function = MeritFunction::new(vec![
+            Operand::new(
+                Thickness {
+                    from: 2,
+                    to: 3,
+                    origin: Point2::origin(),
+                },
+                Target::Equal(10.0),
+                1.0,
+            ),
+            Operand::new(
+                SpotRms {
+                    field: 0,
+                    resolution: 8,
+                },
+                Target::Equal(0.0),
+                1.0,
+            ),
+        ]);
+        let values = system.variable_values().unwrap();
+
+        let unsolved = merit_function.evaluate_cpu(&system).unwrap();
+        let solved = merit_function
+            .evaluate_cpu(&system.with_variable_values(&values).unwrap())
+            .unwrap();
+        assert_eq!(unsolved.values, solved.values);
+        assert_ne!(unsolved.values[0], system.surfaces[2].thickness);
+    }
+
+    #[test]
+    fn evaluates_every_configuration() {
+        let mut system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+        system.stop_index = 0;
+        system.configurations = vec![
+            Configuration::new("nominal", Vec::new()),
+            Configuration::new(
+                "refocused",
+                vec![Override::Thickness {
+                    surface: 2,
+                    value: 11.0,
+                }],
+            ),
+        ];
+        system.variables = vec![Variable::new(2, Parameter::Thickness).in_configuration(1)];
+
+        let thickness = Thickness {
+            from: 2,
+            to: 3,
+            origin: Point2::origin(),
+        };
+        let merit_function = MeritFunction::new(vec![
+            Operand::new(EffectiveFocalLength, Target::Equal(10.0), 1.0),
+            Operand::new(thickness, Target::Equal(10.0), 1.0).in_configuration(1),
+        ]);
+        assert_eq!(merit_function.rows(2), vec![(0, 0), (1, 0), (1, 1)]);
+
+        let (merit, jacobian) = merit_function.jacobian(&system).unwrap();
+        assert_eq!(merit.rows, merit_function.rows(2));
+        assert_eq!(merit.values[0], merit.values[1]);
+        assert!((merit.values[2] - 11.0).abs() < 1e-5);
+
+        // The variable only moves the refocused configuration
+        assert_eq!(jacobian[(0, 0)], 0.0);
+        assert_eq!(jacobian[(1, 0)], 0.0);
+        assert!((jacobian[(2, 0)] - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn jacobian_matches_central_differences() {
+        let merit_function = MeritFunction::new(vec![
+            Operand::new(EffectiveFocalLength, Target::Equal(10.0), 1.0),
+            Operand::new(
+                RayHeight {
+                    field: 2,
+                    pupil: Point2::new(0.0, 1.0),
+                    surface: 3,
+                },
+                Target::Equal(0.0),
+                4.0,
+            ),
+            Operand::new(
+                SpotRms {
+                    field: 1,
+                    resolution: 8,
+                },
+                Target::Equal(0.0),
+                1.0,
+            ),
+        ]);
+
+        // With the stop behind the lens, the variables move the entrance pupil
+        for stop in [0, 3] {
+            let mut system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+            system.stop_index = stop;
+            system.variables = vec![
+                Variable::new(0, Parameter::Curvature),
+                Variable::new(1, Parameter::Curvature),
+                Variable::new(0, Parameter::Thickness),
+                Variable::new(2, Parameter::Thickness),
+            ];
+
+            let (_, jacobian) = merit_function.jacobian(&system).unwrap();
+            let values = system.variable_values().unwrap();
+            for (j, value) in values.iter().enumerate() {
+                let step = system.variables[j].parameter.step();
+                let residuals = |delta: f32| {
+                    let mut values = values.clone();
+                    values[j] = value + delta;
+                    let system = system.with_variable_values(&values).unwrap();
+
+                    merit_function.evaluate_cpu(&system).unwrap().residuals
+                };
+                let (plus, minus) = (residuals(step), residuals(-step));
+
+                for i in 0..plus.len() {
+                    let expected = ((plus[i] - minus[i]) / (2.0 * step)) as f64;
+                    let error = (jacobian[(i, j)] - expected).abs();
+                    assert!(
+                        error <= 2e-2 * expected.abs() + 1e-3,
+                        "stop {stop}, row {i}, variable {j}: {} instead of {expected}",
+                        jacobian[(i, j)]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_missing_fields_and_surfaces() {
+        let system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+        let error = |operand: Operand| {
+            let merit_function = MeritFunction::new(vec![operand]);
+
+            format!("{:#}", merit_function.evaluate_cpu(&system).unwrap_err())
+        };
+
+        let spot = SpotRms {
+            field: 3,
+            resolution: 8,
+        };
+        assert_eq!(
+            error(Operand::new(spot, Target::Equal(0.0), 1.0)),
+            "operand 0: no field 3, the system has 3"
+        );
+
+        let height = RayHeight {
+            field: 0,
+            pupil: Point2::new(0.0, 1.0),
+            surface: 4,
+        };
+        assert_eq!(
+            error(Operand::new(height, Target::Equal(0.0), 1.0)),
+            "operand 0: no surface 4, the system has 4"
+        );
+
+        let mut constrained = system.clone();
+        constrained.constraints = vec![Constraint::EdgeThickness {
+            surface: 3,
+            bounds: Bounds::at_least(0.5),
+        }];
+        let merit_function = MeritFunction::new(vec![Operand::new(
+            EffectiveFocalLength,
+            Target::Equal(10.0),
+            1.0,
+        )]);
+        let error = merit_function.evaluate_cpu(&constrained).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "constraint 0: no surface 4, the system has 4"
+        );
+    }
+}
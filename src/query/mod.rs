@@ -1,11 +1,78 @@
-use crate::compute::raytracing::RayTracingResult;
+//! Scalar quantities traced from a system, and the merit functions built from them.
+
+use std::fmt;
+
+use crate::{
+    compute::{cpu::Parameters, dual::Dual, raytracing::RayTracingResult},
+    system::{Ray, System},
+};
 
 pub mod curvature;
+pub mod efl;
+pub mod merit;
 pub mod radius;
+pub mod ray_height;
+pub mod seidel;
+pub mod spot;
 pub mod thickness;
 
-pub trait QueryParameter {
+/// Number of variables differentiated together by [`QueryParameter::query_dual`].
+pub const DUAL_WIDTH: usize = 4;
+
+/// Dual number carrying derivatives with respect to [`DUAL_WIDTH`] variables.
+pub type Derivative = Dual<DUAL_WIDTH>;
+
+/// Scalar quantity of a traced system, such as an aberration or a paraxial property.
+pub trait QueryParameter: fmt::Debug {
+    /// Type of the quantity.
     type Output;
 
+    /// Rays the parameter needs traced, none by default.
+    fn rays(&self, _system: &System) -> Vec<Ray> {
+        Vec::new()
+    }
+
+    /// Checks that the fields and surfaces the parameter refers to exist in `system`.
+    fn validate(&self, _system: &System) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Evaluates the quantity from the traced rays and the system they went through.
     fn query(&self, raytrace: &RayTracingResult) -> Self::Output;
+
+    /// Whether the rays of the parameter are aimed at the paraxial entrance pupil, which
+    /// [`QueryParameter::query_dual`] holds fixed.
+    fn is_aimed(&self) -> bool {
+        false
+    }
+
+    /// Value and derivatives with respect to the seeded surface `parameters`, traced on the CPU.
+    /// `None` when the parameter can't be differentiated this way.
+    fn query_dual(
+        &self,
+        _system: &System,
+        _parameters: &[Parameters<Derivative>],
+    ) -> Option<Derivative> {
+        None
+    }
+}
+
+fn check_field(system: &System, field: usize) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        field < system.fields.len(),
+        "no field {field}, the system has {}",
+        system.fields.len()
+    );
+
+    Ok(())
+}
+
+fn check_surface(system: &System, surface: usize) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        surface < system.surfaces.len(),
+        "no surface {surface}, the system has {}",
+        system.surfaces.len()
+    );
+
+    Ok(())
 }
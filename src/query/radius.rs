@@ -1,13 +1,34 @@
-use super::QueryParameter;
+//! Surface radii of curvature.
 
+use super::{Derivative, QueryParameter, check_surface};
+use crate::{
+    compute::{cpu::Parameters, dual::Scalar},
+    system::System,
+};
+
+/// Radius of curvature of a surface, infinite for a plane.
+#[derive(Debug, Clone)]
 pub struct Radius {
+    /// Index of the surface.
     pub index: usize,
 }
 
 impl QueryParameter for Radius {
     type Output = f32;
 
+    fn validate(&self, system: &System) -> anyhow::Result<()> {
+        check_surface(system, self.index)
+    }
+
     fn query(&self, raytrace: &super::RayTracingResult) -> Self::Output {
         raytrace.system.surfaces[self.index].curvature.recip()
     }
+
+    fn query_dual(
+        &self,
+        _system: &System,
+        parameters: &[Parameters<Derivative>],
+    ) -> Option<Derivative> {
+        Some(Derivative::constant(1.0) / parameters[self.index].curvature)
+    }
 }
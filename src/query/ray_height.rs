@@ -0,0 +1,60 @@
+//! Real ray heights on a surface.
+
+use nalgebra::Point2;
+
+use super::{Derivative, QueryParameter, check_field, check_surface};
+use crate::{
+    compute::cpu::Parameters,
+    system::{Ray, System},
+};
+
+/// Height, along y, of a real ray of the primary wavelength on a surface.
+#[derive(Debug, Clone)]
+pub struct RayHeight {
+    /// Index of the field the ray comes from.
+    pub field: usize,
+    /// Normalized pupil coordinates.
+    pub pupil: Point2<f32>,
+    /// Surface the height is measured on.
+    pub surface: usize,
+}
+
+impl QueryParameter for RayHeight {
+    type Output = f32;
+
+    fn validate(&self, system: &System) -> anyhow::Result<()> {
+        check_field(system, self.field)?;
+        check_surface(system, self.surface)
+    }
+
+    fn rays(&self, system: &System) -> Vec<Ray> {
+        vec![system.field_ray(&system.fields[self.field], self.pupil)]
+    }
+
+    fn is_aimed(&self) -> bool {
+        true
+    }
+
+    fn query(&self, raytrace: &super::RayTracingResult) -> Self::Output {
+        raytrace
+            .paths()
+            .next()
+            .map_or(f32::NAN, |path| path[self.surface].point().y)
+    }
+
+    fn query_dual(
+        &self,
+        system: &System,
+        parameters: &[Parameters<Derivative>],
+    ) -> Option<Derivative> {
+        let ray = self.rays(system).pop()?;
+
+        Some(
+            system
+                .trace_ray(parameters, &ray)
+                .get(self.surface)?
+                .point
+                .y,
+        )
+    }
+}
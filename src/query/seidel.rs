@@ -0,0 +1,46 @@
+//! Seidel aberration sums.
+
+use super::{QueryParameter, check_surface};
+use crate::{
+    analysis::seidel::{Coefficients, Query},
+    system::System,
+};
+
+/// One of the Seidel sums, of a single surface or of the whole system.
+#[derive(Debug, Clone)]
+pub struct Seidel {
+    /// Analysis the sums are computed with.
+    pub query: Query,
+    /// Surface whose contribution is taken, the total when `None`.
+    pub surface: Option<usize>,
+    /// Index of the sum, in the order of [`Coefficients::LABELS`].
+    pub term: usize,
+}
+
+impl QueryParameter for Seidel {
+    type Output = f32;
+
+    fn validate(&self, system: &System) -> anyhow::Result<()> {
+        if let Some(surface) = self.surface {
+            check_surface(system, surface)?;
+        }
+        anyhow::ensure!(
+            self.term < Coefficients::LABELS.len(),
+            "no Seidel sum {}, there are {}",
+            self.term,
+            Coefficients::LABELS.len()
+        );
+
+        Ok(())
+    }
+
+    fn query(&self, raytrace: &super::RayTracingResult) -> Self::Output {
+        let response = raytrace.system.seidel(&self.query);
+        let coefficients: Coefficients = match self.surface {
+            Some(surface) => response.surfaces[surface],
+            None => response.total(),
+        };
+
+        coefficients.to_array()[self.term]
+    }
+}
@@ -0,0 +1,93 @@
+//! RMS spot radius on the image surface.
+
+use nalgebra::Point2;
+
+use super::{Derivative, QueryParameter, check_field};
+use crate::{
+    compute::{cpu::Parameters, dual::Scalar},
+    system::{Ray, System},
+};
+
+/// Polychromatic RMS spot radius of a field on the image surface, about the spot centroid.
+#[derive(Debug, Clone)]
+pub struct SpotRms {
+    /// Index of the field.
+    pub field: usize,
+    /// Number of samples across the pupil diameter.
+    pub resolution: u32,
+}
+
+impl QueryParameter for SpotRms {
+    type Output = f32;
+
+    fn validate(&self, system: &System) -> anyhow::Result<()> {
+        check_field(system, self.field)
+    }
+
+    fn rays(&self, system: &System) -> Vec<Ray> {
+        let n = self.resolution.max(2);
+        let step = |i: u32| 2.0 * i as f32 / (n - 1) as f32 - 1.0;
+        let field = &system.fields[self.field];
+
+        let rays = (0..n)
+            .flat_map(|j| (0..n).map(move |i| Point2::new(step(i), step(j))))
+            .filter(|pupil| pupil.coords.norm_squared() <= 1.0)
+            .map(|pupil| system.field_ray(field, pupil))
+            .collect::<Vec<_>>();
+
+        if system.wavelengths.is_empty() {
+            rays
+        } else {
+            system.polychromatic_rays(&rays)
+        }
+    }
+
+    fn is_aimed(&self) -> bool {
+        true
+    }
+
+    fn query(&self, raytrace: &super::RayTracingResult) -> Self::Output {
+        rms_radius(raytrace.paths().filter_map(|path| {
+            let last = path.last()?;
+            let point = last.point();
+
+            Some((point.x, point.y, last.ray.weight))
+        }))
+    }
+
+    fn query_dual(
+        &self,
+        system: &System,
+        parameters: &[Parameters<Derivative>],
+    ) -> Option<Derivative> {
+        Some(rms_radius(self.rays(system).iter().filter_map(|ray| {
+            let last = *system.trace_ray(parameters, ray).last()?;
+
+            Some((last.point.x, last.point.y, ray.weight))
+        })))
+    }
+}
+
+/// Weighted RMS distance of image points to their centroid, ignoring the rays that failed.
+fn rms_radius<T: Scalar>(points: impl Iterator<Item = (T, T, f32)>) -> T {
+    let points = points
+        .filter(|(x, y, _)| x.value().is_finite() && y.value().is_finite())
+        .collect::<Vec<_>>();
+
+    let zero = T::constant(0.0);
+    let total = T::constant(points.iter().map(|(_, _, weight)| weight).sum::<f32>());
+    let (cx, cy) = points
+        .iter()
+        .fold((zero, zero), |(cx, cy), (x, y, weight)| {
+            let weight = T::constant(*weight) / total;
+
+            (cx + *x * weight, cy + *y * weight)
+        });
+
+    (points.iter().fold(zero, |acc, (x, y, weight)| {
+        let (dx, dy) = (*x - cx, *y - cy);
+
+        acc + T::constant(*weight) * (dx * dx + dy * dy)
+    }) / total)
+        .sqrt()
+}
@@ -1,21 +1,65 @@
+//! Axial distances between surfaces.
+
 use nalgebra::Point2;
 
-use super::QueryParameter;
+use super::{Derivative, QueryParameter, check_surface};
+use crate::{
+    compute::{cpu::Parameters, dual::Scalar},
+    system::System,
+};
 
+/// Axial distance between two surfaces, measured at `origin`.
+#[derive(Debug, Clone)]
 pub struct Thickness {
+    /// Surface the distance is measured from.
     pub from: usize,
+    /// Surface the distance is measured to.
     pub to: usize,
+    /// Transverse point the distance is measured at, the origin being on the axis.
     pub origin: Point2<f32>,
 }
 
 impl QueryParameter for Thickness {
     type Output = f32;
 
+    fn validate(&self, system: &System) -> anyhow::Result<()> {
+        check_surface(system, self.from)?;
+        check_surface(system, self.to)?;
+        anyhow::ensure!(
+            self.from <= self.to,
+            "surface {} comes after surface {}",
+            self.from,
+            self.to
+        );
+
+        Ok(())
+    }
+
     fn query(&self, raytrace: &super::RayTracingResult) -> Self::Output {
-        raytrace.system.surfaces[self.from..self.to]
+        let surfaces = &raytrace.system.surfaces;
+
+        surfaces[self.from..self.to]
             .iter()
-            .fold(0.0, |acc, surface| {
-                acc + surface.thickness - surface.z(self.origin)
-            })
+            .fold(0.0, |acc, surface| acc + surface.thickness)
+            + surfaces[self.to].z(self.origin)
+            - surfaces[self.from].z(self.origin)
+    }
+
+    fn query_dual(
+        &self,
+        _system: &System,
+        parameters: &[Parameters<Derivative>],
+    ) -> Option<Derivative> {
+        let r2 = self.origin.coords.norm_squared();
+
+        Some(
+            parameters[self.from..self.to]
+                .iter()
+                .fold(Derivative::constant(0.0), |acc, surface| {
+                    acc + surface.thickness
+                })
+                + parameters[self.to].sag(r2)
+                - parameters[self.from].sag(r2),
+        )
     }
 }
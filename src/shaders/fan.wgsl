@@ -1,14 +1,26 @@
 /* Structures */
+struct Material {
+    formula: u32,
+    coefficients: array<f32, 10>,
+    thermal: array<f32, 6>,
+    environment: array<f32, 4>,
+}
+
 struct Surface {
     thickness: f32,
-    refractive_index: f32,
     curvature: f32,
+    conic: f32,
     semi_diameter: f32,
+    material: Material,
+    decenter: vec2<f32>,
+    rotation: mat3x3<f32>,
 }
 
 struct Ray {
     origin: vec3<f32>,
     direction: vec3<f32>,
+    wavelength: f32,
+    weight: f32,
 }
 
 struct Intersection {
@@ -20,12 +32,14 @@ struct Intersection {
 struct Object {
     distance: f32,
     semi_diameter: f32,
-    refractive_index: f32,
+    material: Material,
 }
 
 struct System {
     object: Object,
     stop_index: u32,
+    surface_count: u32,
+    rays_per_system: u32,
     surfaces: array<Surface>,
 }
 
@@ -35,6 +49,7 @@ struct Query {
     dir_a: vec3<f32>,
     dir_b: vec3<f32>,
     resolution: u32,
+    wavelength: f32,
 }
 
 struct Result {
@@ -57,6 +72,103 @@ var<storage, read> query: Query;
 var<storage, read_write> result: Result;
 
 /* Functions */
+fn dispersion(material: Material, wavelength: f32) -> f32 {
+    var c = material.coefficients;
+    let l2 = wavelength * wavelength;
+
+    switch material.formula {
+        // Sellmeier, with up to five interleaved (K, L) terms
+        case 1u: {
+            var n2 = 1.0;
+            for (var i = 0u; i < 5u; i++) {
+                n2 += c[2u * i] * l2 / (l2 - c[2u * i + 1u]);
+            }
+            return sqrt(n2);
+        }
+        // Schott
+        case 2u: {
+            return sqrt(c[0] + c[1] * l2 + c[2] / l2 + c[3] / pow(l2, 2.0) + c[4] / pow(l2, 3.0) + c[5] / pow(l2, 4.0));
+        }
+        // Conrady
+        case 3u: {
+            return c[0] + c[1] / wavelength + c[2] / pow(wavelength, 3.5);
+        }
+        // Cauchy
+        case 4u: {
+            return c[0] + c[1] / l2 + c[2] / (l2 * l2);
+        }
+        // Herzberger
+        case 5u: {
+            let l = 1.0 / (l2 - 0.028);
+            return c[0] + c[1] * l + c[2] * l * l + c[3] * l2 + c[4] * l2 * l2 + c[5] * l2 * l2 * l2;
+        }
+        // Sellmeier 2
+        case 6u: {
+            return sqrt(1.0 + c[0] + c[1] * l2 / (l2 - c[2] * c[2]) + c[3] / (l2 - c[4] * c[4]));
+        }
+        // Handbook of Optics 1
+        case 7u: {
+            return sqrt(c[0] + c[1] / (l2 - c[2]) - c[3] * l2);
+        }
+        // Handbook of Optics 2
+        case 8u: {
+            return sqrt(c[0] + c[1] * l2 / (l2 - c[2]) - c[3] * l2);
+        }
+        // Sellmeier 4
+        case 9u: {
+            return sqrt(c[0] + c[1] * l2 / (l2 - c[2]) + c[3] * l2 / (l2 - c[4]));
+        }
+        // Extended
+        case 10u: {
+            var n2 = c[0] + c[1] * l2;
+            for (var i = 2u; i < 8u; i++) {
+                n2 += c[i] / pow(l2, f32(i - 1u));
+            }
+            return sqrt(n2);
+        }
+        // Extended 2
+        case 11u: {
+            var n2 = c[0] + c[1] * l2 + c[6] * l2 * l2 + c[7] * l2 * l2 * l2;
+            for (var i = 2u; i < 6u; i++) {
+                n2 += c[i] / pow(l2, f32(i - 1u));
+            }
+            return sqrt(n2);
+        }
+        // Extended 3
+        case 12u: {
+            var n2 = c[0] + c[1] * l2 + c[2] * l2 * l2;
+            for (var i = 3u; i < 9u; i++) {
+                n2 += c[i] / pow(l2, f32(i - 2u));
+            }
+            return sqrt(n2);
+        }
+        // Constant
+        default: {
+            return c[0];
+        }
+    }
+}
+
+fn air_index(temperature: f32, pressure: f32, wavelength: f32) -> f32 {
+    let l2 = wavelength * wavelength;
+    let reference = 1.0 + (6432.8 + 2949810.0 * l2 / (146.0 * l2 - 1.0) + 25540.0 * l2 / (41.0 * l2 - 1.0)) * 1e-8;
+
+    return 1.0 + (reference - 1.0) * pressure / (1.0 + (temperature - 15.0) * 3.4785e-3);
+}
+
+// Index relative to the air of the material environment, see `Environment::refractive_index`
+fn refractive_index(material: Material, wavelength: f32) -> f32 {
+    let e = material.environment;
+    let d = material.thermal;
+
+    let n = dispersion(material, wavelength) * air_index(e[0], e[1], wavelength);
+    let dt = e[2] - e[0];
+    let dn = (n * n - 1.0) / (2.0 * n) * (d[0] * dt + d[1] * dt * dt + d[2] * dt * dt * dt
+        + (d[3] * dt + d[4] * dt * dt) / (wavelength * wavelength - d[5] * d[5]));
+
+    return (n + dn) / air_index(e[2], e[3], wavelength);
+}
+
 fn refract(dir: vec3<f32>, normal: vec3<f32>, mu: f32) -> vec3<f32> {
     let a = dot(normal, dir);
     return normalize(sqrt(1.0 - mu * mu * (1.0 - a * a)) * normal + mu * (dir - a * normal));
@@ -88,6 +200,27 @@ fn intersect_with_sphere(surface: Surface, ray: Ray, z: f32, n0: f32) -> Interse
     return Intersection(ray, normal, t);
 }
 
+fn intersect_with_conic(surface: Surface, ray: Ray, z: f32, n0: f32) -> Intersection {
+    // c (x² + y²) + c (1 + k) z² - 2 z = 0, about the vertex
+    let c = surface.curvature;
+    let ck = c * (1.0 + surface.conic);
+    let o = ray.origin - vec3<f32>(0.0, 0.0, z);
+    let d = ray.direction;
+
+    let a = c * (d.x * d.x + d.y * d.y) + ck * d.z * d.z;
+    let b = 2.0 * (c * (o.x * d.x + o.y * d.y) + ck * o.z * d.z - d.z);
+    let k = c * (o.x * o.x + o.y * o.y) + ck * o.z * o.z - 2.0 * o.z;
+
+    // Root on the sheet through the vertex, stable when a vanishes
+    let s = select(1.0, -1.0, b < 0.0);
+    let t = 2.0 * k / (-b - s * sqrt(b * b - 4.0 * a * k));
+
+    let p = o + d * t;
+    let normal = normalize(vec3<f32>(c * p.x, c * p.y, ck * p.z - 1.0));
+
+    return Intersection(ray, normal, t);
+}
+
 fn intersect_with_plane(surface: Surface, ray: Ray, z: f32, n0: f32) -> Intersection {
     let t = (z - ray.origin.z) / ray.direction.z;
     let normal = vec3(0.0, 0.0, -1.0);
@@ -95,6 +228,28 @@ fn intersect_with_plane(surface: Surface, ray: Ray, z: f32, n0: f32) -> Intersec
     return Intersection(ray, normal, t);
 }
 
+// Intersection with the surface in its decentered and tilted local coordinates
+fn intersect(surface: Surface, ray: Ray, z: f32, n0: f32) -> Intersection {
+    let inverse = transpose(surface.rotation);
+    let origin = inverse * (ray.origin - vec3<f32>(surface.decenter, z));
+    let direction = inverse * ray.direction;
+
+    // Start from the vertex plane, so that distant origins don't lose precision
+    let t0 = -origin.z / direction.z;
+    let local = Ray(origin + direction * t0, direction, ray.wavelength, ray.weight);
+
+    var intersection: Intersection;
+    if (surface.curvature == 0.0) {
+        intersection = intersect_with_plane(surface, local, 0.0, n0);
+    } else if (surface.conic == 0.0) {
+        intersection = intersect_with_sphere(surface, local, 0.0, n0);
+    } else {
+        intersection = intersect_with_conic(surface, local, 0.0, n0);
+    }
+
+    return Intersection(ray, surface.rotation * intersection.normal, t0 + intersection.t);
+}
+
 /* Entry points */
 @compute
 @workgroup_size(1, 1, 1)
@@ -106,32 +261,31 @@ fn main(
     let t = f32(index) / f32(query.resolution - 1);
     let dir = normalize(mix(query.dir_a, query.dir_b, t));
 
-    var ray = Ray(query.origin, dir);
-    var n0 = system.object.refractive_index;
+    var ray = Ray(query.origin, dir, query.wavelength, 1.0);
+    var n0 = refractive_index(system.object.material, ray.wavelength);
     var z = 0.0;
     var intersection: Intersection;
 
     for (var i = 0u; i <= system.stop_index; i++) {
         let surface = system.surfaces[i];
+        let n1 = refractive_index(surface.material, ray.wavelength);
 
-        if (surface.curvature == 0.0) {
-            intersection = intersect_with_plane(surface, ray, z, n0);
-        } else {
-            intersection = intersect_with_sphere(surface, ray, z, n0);
-        }
+        intersection = intersect(surface, ray, z, n0);
 
         ray = Ray(
             ray.direction * intersection.t + ray.origin, // origin
             refract(
                 ray.direction,
                 -intersection.normal,
-                n0 / surface.refractive_index
+                n0 / n1
             ), // direction
+            ray.wavelength,
+            ray.weight,
         );
 
 
         z += surface.thickness;
-        n0 = surface.refractive_index;
+        n0 = n1;
     }
 
     result.heights[index] = ray.origin.y;
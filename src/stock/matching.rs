@@ -0,0 +1,187 @@
+//! Replacing designed elements with stock lenses.
+
+use std::{fmt, ops::Range};
+
+use super::{StockCatalog, focal_length};
+use crate::{
+    optimization::{self, Bounds, Parameter, Variable},
+    query::merit::MeritFunction,
+    system::System,
+};
+
+/// Designed element to replace with stock lenses.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Surfaces of the designed element, the last one followed by the gap to the next element.
+    pub surfaces: Range<usize>,
+    /// Number of stock lenses tried, the closest in focal length first.
+    pub candidates: usize,
+    /// Merit function the spacings are re-optimized against.
+    pub merit_function: MeritFunction,
+    /// Damped least-squares iterations for each candidate and orientation.
+    pub iterations: u32,
+}
+
+impl Query {
+    /// Query trying five candidates with 20 iterations each.
+    pub fn new(surfaces: Range<usize>, merit_function: MeritFunction) -> Self {
+        Self {
+            surfaces,
+            candidates: 5,
+            merit_function,
+            iterations: 20,
+        }
+    }
+}
+
+/// Stock lens replacing the designed element.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// Part number of the lens.
+    pub part: String,
+    /// Whether the lens is mounted back to front.
+    pub reversed: bool,
+    /// Nominal focal length of the lens.
+    pub efl: f32,
+    /// Merit after re-optimizing the spacings.
+    pub merit: f32,
+    /// System with the lens inserted and the spacings re-optimized.
+    pub system: System,
+}
+
+/// Stock replacements, by increasing merit.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// Focal length of the designed element, at the d line.
+    pub designed_efl: f32,
+    /// Clear diameter of the designed element.
+    pub diameter: f32,
+    /// Candidates, the best first.
+    pub candidates: Vec<Candidate>,
+}
+
+impl Response {
+    /// Candidate with the lowest merit.
+    pub fn best(&self) -> Option<&Candidate> {
+        self.candidates.first()
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Designed element: EFL {:.4}, diameter {:.4}",
+            self.designed_efl, self.diameter
+        )?;
+        writeln!(
+            f,
+            "{:>6} {:>12} {:>9} {:>10} {:>14}",
+            "rank", "part", "reversed", "efl", "merit"
+        )?;
+        for (i, candidate) in self.candidates.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>6} {:>12} {:>9} {:>10.4} {:>14.8}",
+                i + 1,
+                candidate.part,
+                candidate.reversed,
+                candidate.efl,
+                candidate.merit
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl System {
+    /// Replaces the element made of [`Query::surfaces`] with the stock lenses closest in focal
+    /// length, in both orientations, re-optimizing the air spacings before and after it.
+    ///
+    /// Only lenses at least as wide as the element are tried. The other variables of the system
+    /// are kept fixed during the match, and carried over to the candidates.
+    pub async fn catalog_match(
+        &self,
+        catalog: &StockCatalog,
+        query: &Query,
+    ) -> anyhow::Result<Response> {
+        let range = query.surfaces.clone();
+        anyhow::ensure!(
+            !range.is_empty() && range.end < self.surfaces.len(),
+            "surfaces {range:?} aren't an element of the system"
+        );
+
+        let mut element = self.surfaces[range.clone()].to_vec();
+        let gap = element.last().map_or(0.0, |surface| surface.thickness);
+        if let Some(last) = element.last_mut() {
+            last.thickness = 0.0;
+        }
+        let designed_efl = focal_length(&element);
+        let diameter = 2.0
+            * element
+                .iter()
+                .map(|surface| surface.semi_diameter)
+                .fold(0.0, f32::max);
+
+        let mut lenses = catalog
+            .lenses
+            .iter()
+            .filter(|lens| lens.diameter >= diameter)
+            .collect::<Vec<_>>();
+        lenses.sort_by(|a, b| {
+            (a.efl - designed_efl)
+                .abs()
+                .total_cmp(&(b.efl - designed_efl).abs())
+        });
+        anyhow::ensure!(
+            !lenses.is_empty(),
+            "no stock lens of {} is {diameter} mm wide",
+            catalog.name
+        );
+
+        let mut candidates = Vec::new();
+        for lens in lenses.into_iter().take(query.candidates) {
+            for reversed in [false, true] {
+                let mut system = self.clone();
+                system.replace_surfaces(range.clone(), lens.surfaces(reversed, gap))?;
+                let variables = std::mem::take(&mut system.variables);
+
+                let last = range.start + lens.surfaces.len() - 1;
+                system.variables = range
+                    .start
+                    .checked_sub(1)
+                    .into_iter()
+                    .chain([last])
+                    .map(|surface| {
+                        Variable::new(surface, Parameter::Thickness).bounded(Bounds::at_least(0.0))
+                    })
+                    .collect();
+
+                let response = system
+                    .optimize(&optimization::Query {
+                        iterations: query.iterations,
+                        ..optimization::Query::new(query.merit_function.clone())
+                    })
+                    .await?;
+                let mut system = response.system;
+                system.variables = variables;
+
+                candidates.push(Candidate {
+                    part: lens.part.clone(),
+                    reversed,
+                    efl: lens.focal_length(),
+                    merit: response.merit.value,
+                    system,
+                });
+            }
+        }
+        candidates.sort_by(|a, b| a.merit.total_cmp(&b.merit));
+
+        Ok(Response {
+            designed_efl,
+            diameter,
+            candidates,
+        })
+    }
+}
@@ -0,0 +1,343 @@
+//! Off-the-shelf lenses.
+
+pub mod matching;
+
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{
+    glass::{self, Catalog},
+    system::{FRAUNHOFER_D, Material, Surface, System},
+};
+
+/// Catalogs compiled into the crate, from generic vendor data.
+const EMBEDDED: [(&str, &str); 2] = [
+    (
+        "singlets",
+        include_str!("../../fixtures/stock/singlets.csv"),
+    ),
+    (
+        "doublets",
+        include_str!("../../fixtures/stock/doublets.csv"),
+    ),
+];
+/// Lenses compiled into the crate from vendor ZMX files, by part number.
+const EMBEDDED_ZMX: [(&str, &str); 1] = [(
+    "ACH-06-012",
+    include_str!("../../fixtures/stock/ACH-06-012.zmx"),
+)];
+
+/// Paraxial focal length of lens surfaces in air at the d line.
+pub fn focal_length(surfaces: &[Surface]) -> f32 {
+    let system = System {
+        surfaces: surfaces
+            .iter()
+            .cloned()
+            .chain(std::iter::once(Surface::default()))
+            .collect(),
+        ..Default::default()
+    };
+
+    system.effective_focal_length(FRAUNHOFER_D)
+}
+
+/// Off-the-shelf lens.
+#[derive(Debug, Clone)]
+pub struct StockLens {
+    /// Vendor part number.
+    pub part: String,
+    /// Short description of the lens.
+    pub description: String,
+    /// Nominal focal length, in millimetres.
+    pub efl: f32,
+    /// Outer diameter, in millimetres.
+    pub diameter: f32,
+    /// Surfaces from front to back, the last one followed by air.
+    pub surfaces: Vec<Surface>,
+}
+
+impl StockLens {
+    /// Lens made of the surfaces of `system` from the first glass to the air behind the last one.
+    pub fn from_system(
+        part: impl Into<String>,
+        description: impl Into<String>,
+        system: &System,
+    ) -> anyhow::Result<Self> {
+        let part = part.into();
+        let is_glass = |surface: &Surface| surface.material != Material::Constant(1.0);
+        let first = system
+            .surfaces
+            .iter()
+            .position(is_glass)
+            .with_context(|| format!("{part}: no glass in the system"))?;
+        let last = system.surfaces.iter().rposition(is_glass).unwrap_or(first) + 1;
+        anyhow::ensure!(
+            last < system.surfaces.len(),
+            "{part}: the image surface is inside the lens"
+        );
+
+        let mut surfaces = system.surfaces[first..=last].to_vec();
+        surfaces[last - first].thickness = 0.0;
+        let diameter = 2.0
+            * surfaces
+                .iter()
+                .map(|surface| surface.semi_diameter)
+                .fold(0.0, f32::max);
+
+        Ok(Self {
+            part,
+            description: description.into(),
+            efl: focal_length(&surfaces),
+            diameter,
+            surfaces,
+        })
+    }
+
+    /// Focal length computed from the surfaces, at the d line.
+    pub fn focal_length(&self) -> f32 {
+        focal_length(&self.surfaces)
+    }
+
+    /// Surfaces of the lens, back to front when `reversed`, the last one followed by `spacing`.
+    pub fn surfaces(&self, reversed: bool, spacing: f32) -> Vec<Surface> {
+        let mut surfaces = if reversed {
+            let n = self.surfaces.len();
+
+            (0..n)
+                .map(|i| {
+                    let surface = &self.surfaces[n - 1 - i];
+                    let medium = (i + 1 < n).then(|| &self.surfaces[n - 2 - i]);
+
+                    Surface {
+                        curvature: -surface.curvature,
+                        thickness: medium.map_or(0.0, |medium| medium.thickness),
+                        material: medium
+                            .map_or_else(Material::default, |medium| medium.material.clone()),
+                        ..surface.clone()
+                    }
+                })
+                .collect()
+        } else {
+            self.surfaces.clone()
+        };
+
+        if let Some(last) = surfaces.last_mut() {
+            last.thickness = spacing;
+        }
+
+        surfaces
+    }
+}
+
+/// Stock lenses of a vendor, or of a kind.
+#[derive(Debug, Clone, Default)]
+pub struct StockCatalog {
+    /// Name of the catalog.
+    pub name: String,
+    /// Lenses of the catalog, in file order.
+    pub lenses: Vec<StockLens>,
+}
+
+impl StockCatalog {
+    /// Generic singlets and achromatic doublets shipped with the crate, using model glasses.
+    pub fn embedded() -> Self {
+        let mut catalog = Self {
+            name: "embedded".to_string(),
+            lenses: Vec::new(),
+        };
+
+        for (name, source) in EMBEDDED {
+            let parsed = Self::parse_csv(name, source, &[]).expect("embedded catalogs are valid");
+            catalog.lenses.extend(parsed.lenses);
+        }
+        for (part, source) in EMBEDDED_ZMX {
+            let system = System::from_zmx(source, &[]).expect("embedded lenses are valid");
+            catalog.lenses.push(
+                StockLens::from_system(part, "Achromatic doublet", &system)
+                    .expect("embedded lenses are valid"),
+            );
+        }
+
+        catalog
+    }
+
+    /// Reads a CSV catalog, see [`StockCatalog::parse_csv`].
+    pub fn load_csv(path: impl AsRef<Path>, glasses: &[Catalog]) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+
+        Self::parse_csv(name, &source, glasses)
+    }
+
+    /// Parses CSV lines of `part,description,efl,diameter,r1,t1,glass1,nd1,vd1,r2` for singlets,
+    /// followed by `t2,glass2,nd2,vd2,r3` for doublets.
+    ///
+    /// Plane surfaces have an `inf` radius. Glasses missing from `glasses` fall back to model
+    /// glasses of index `nd` and Abbe number `vd`. Lines starting with `#` and the header are
+    /// skipped.
+    pub fn parse_csv(
+        name: impl Into<String>,
+        source: &str,
+        glasses: &[Catalog],
+    ) -> anyhow::Result<Self> {
+        let name = name.into();
+        let mut lenses = Vec::new();
+
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("part,") {
+                continue;
+            }
+
+            let columns = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let context = |column: &str| format!("{name}, line {}: invalid {column}", number + 1);
+            let value = |index: usize, column: &str| -> anyhow::Result<f32> {
+                columns
+                    .get(index)
+                    .and_then(|value| value.parse().ok())
+                    .with_context(|| context(column))
+            };
+            let material = |index: usize| -> anyhow::Result<Material> {
+                let glass = columns.get(index).copied().unwrap_or_default();
+
+                Ok(match glass::find(glasses, glass) {
+                    Some(glass) => Material::Glass(glass.clone()),
+                    None => Material::Model {
+                        nd: value(index + 1, "nd")?,
+                        vd: value(index + 2, "vd")?,
+                    },
+                })
+            };
+            let curvature = |index: usize, column: &str| value(index, column).map(|r| 1.0 / r);
+
+            let diameter = value(3, "diameter")?;
+            let surface = |curvature, thickness, material| Surface {
+                thickness,
+                material,
+                curvature,
+                semi_diameter: 0.5 * diameter,
+                ..Default::default()
+            };
+            let mut surfaces = vec![surface(curvature(4, "r1")?, value(5, "t1")?, material(6)?)];
+            let doublet = columns.get(10).is_some_and(|t2| !t2.is_empty());
+            if doublet {
+                surfaces.push(surface(
+                    curvature(9, "r2")?,
+                    value(10, "t2")?,
+                    material(11)?,
+                ));
+                surfaces.push(surface(curvature(14, "r3")?, 0.0, Material::default()));
+            } else {
+                surfaces.push(surface(curvature(9, "r2")?, 0.0, Material::default()));
+            }
+
+            lenses.push(StockLens {
+                part: columns[0].to_string(),
+                description: columns.get(1).copied().unwrap_or_default().to_string(),
+                efl: value(2, "efl")?,
+                diameter,
+                surfaces,
+            });
+        }
+
+        Ok(Self { name, lenses })
+    }
+
+    /// Lenses of focal length within `tolerance` of `efl` and at least `diameter` wide, the
+    /// closest in focal length first.
+    pub fn search(&self, efl: f32, tolerance: f32, diameter: f32) -> Vec<&StockLens> {
+        let mut lenses = self
+            .lenses
+            .iter()
+            .filter(|lens| (lens.efl - efl).abs() <= tolerance && lens.diameter >= diameter)
+            .collect::<Vec<_>>();
+        lenses.sort_by(|a, b| (a.efl - efl).abs().total_cmp(&(b.efl - efl).abs()));
+
+        lenses
+    }
+
+    /// Lens `part`, ignoring case.
+    pub fn lens(&self, part: &str) -> Option<&StockLens> {
+        self.lenses
+            .iter()
+            .find(|lens| lens.part.eq_ignore_ascii_case(part))
+    }
+}
+
+impl System {
+    /// Inserts `lens` before surface `index`, the lens being followed by `spacing`.
+    pub fn insert_stock_lens(
+        &mut self,
+        index: usize,
+        lens: &StockLens,
+        reversed: bool,
+        spacing: f32,
+    ) -> anyhow::Result<()> {
+        self.replace_surfaces(index..index, lens.surfaces(reversed, spacing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-3 * b.abs()
+    }
+
+    #[test]
+    fn embedded_focal_lengths() {
+        let catalog = StockCatalog::embedded();
+        assert!(catalog.lens("ach-06-012").is_some());
+
+        for lens in &catalog.lenses {
+            assert!(
+                close(lens.focal_length(), lens.efl),
+                "{}: focal length {} instead of {}",
+                lens.part,
+                lens.focal_length(),
+                lens.efl
+            );
+        }
+    }
+
+    #[test]
+    fn reversed_focal_length() {
+        let catalog = StockCatalog::embedded();
+        for part in ["PCX-25-050", "DCX-13-020", "ACH-13-040", "ACH-06-012"] {
+            let lens = catalog.lens(part).unwrap();
+            let reversed = lens.surfaces(true, 0.0);
+
+            assert_eq!(reversed.len(), lens.surfaces.len());
+            assert_eq!(
+                reversed[0].curvature,
+                -lens.surfaces.last().unwrap().curvature
+            );
+            assert!(
+                close(focal_length(&reversed), lens.focal_length()),
+                "{part}"
+            );
+        }
+    }
+
+    #[test]
+    fn search_closest_first() {
+        let catalog = StockCatalog::embedded();
+        let lenses = catalog.search(48.0, 15.0, 20.0);
+        let efls = lenses.iter().map(|lens| lens.efl).collect::<Vec<_>>();
+
+        assert!(!lenses.is_empty());
+        assert!(lenses.iter().all(|lens| lens.diameter >= 20.0));
+        assert!(efls.iter().all(|efl| (efl - 48.0).abs() <= 15.0));
+        assert!(
+            efls.windows(2)
+                .all(|pair| (pair[0] - 48.0).abs() <= (pair[1] - 48.0).abs())
+        );
+        assert_eq!(efls[0], 50.0);
+    }
+}
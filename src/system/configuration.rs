@@ -0,0 +1,196 @@
+use super::{Field, Material, System};
+
+/// Prescription value a configuration replaces.
+#[derive(Debug, Clone)]
+pub enum Override {
+    /// Distance after a surface.
+    Thickness {
+        /// Index of the surface.
+        surface: usize,
+        /// Distance replacing the prescription one.
+        value: f32,
+    },
+    /// Curvature of a surface.
+    Curvature {
+        /// Index of the surface.
+        surface: usize,
+        /// Curvature replacing the prescription one.
+        value: f32,
+    },
+    /// Clear semi-diameter of a surface.
+    SemiDiameter {
+        /// Index of the surface.
+        surface: usize,
+        /// Semi-diameter replacing the prescription one.
+        value: f32,
+    },
+    /// Medium following the surface.
+    Material {
+        /// Index of the surface.
+        surface: usize,
+        /// Medium replacing the prescription one.
+        material: Material,
+    },
+    /// Index of the aperture stop.
+    StopIndex(u32),
+    /// Distance from the object to the first surface.
+    ObjectDistance(f32),
+    /// Fields replacing the system ones.
+    Fields(Vec<Field>),
+}
+
+/// Zoom position or other variant of the system, differing by the values it overrides.
+#[derive(Debug, Clone, Default)]
+pub struct Configuration {
+    /// Name the configuration is reported by.
+    pub name: String,
+    /// Values of the system the configuration replaces.
+    pub overrides: Vec<Override>,
+}
+
+impl Configuration {
+    /// Configuration `name` replacing the `overrides` values.
+    pub fn new(name: impl Into<String>, overrides: Vec<Override>) -> Self {
+        Self {
+            name: name.into(),
+            overrides,
+        }
+    }
+}
+
+impl System {
+    /// Copy of the system set up as configuration `index`, without the configuration table.
+    pub fn configuration(&self, index: usize) -> anyhow::Result<System> {
+        let configuration = self
+            .configurations
+            .get(index)
+            .ok_or_else(|| anyhow::anyhow!("no configuration {index}"))?;
+
+        let mut system = System {
+            configurations: Vec::new(),
+            ..self.clone()
+        };
+        for value in &configuration.overrides {
+            if let Override::Thickness { surface, .. }
+            | Override::Curvature { surface, .. }
+            | Override::SemiDiameter { surface, .. }
+            | Override::Material { surface, .. } = value
+            {
+                anyhow::ensure!(
+                    *surface < system.surfaces.len(),
+                    "configuration {index} ({}) overrides surface {surface} of {}",
+                    configuration.name,
+                    system.surfaces.len()
+                );
+            }
+
+            match value {
+                Override::Thickness { surface, value } => {
+                    system.surfaces[*surface].thickness = *value
+                }
+                Override::Curvature { surface, value } => {
+                    system.surfaces[*surface].curvature = *value
+                }
+                Override::SemiDiameter { surface, value } => {
+                    system.surfaces[*surface].semi_diameter = *value
+                }
+                Override::Material { surface, material } => {
+                    system.surfaces[*surface].material = material.clone()
+                }
+                Override::StopIndex(stop) => system.stop_index = *stop,
+                Override::ObjectDistance(distance) => system.object.distance = *distance,
+                Override::Fields(fields) => system.fields = fields.clone(),
+            }
+        }
+
+        Ok(system)
+    }
+
+    /// Every configuration of the system, or the system itself when it has a single one.
+    pub fn all_configurations(&self) -> anyhow::Result<Vec<System>> {
+        if self.configurations.is_empty() {
+            Ok(vec![self.clone()])
+        } else {
+            (0..self.configurations.len())
+                .map(|index| self.configuration(index))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimization::{Parameter, Variable};
+
+    fn zoom() -> System {
+        let mut system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+        system.configurations = vec![
+            Configuration::new("near", vec![Override::ObjectDistance(50.0)]),
+            Configuration::new(
+                "far",
+                vec![
+                    Override::Thickness {
+                        surface: 2,
+                        value: 9.0,
+                    },
+                    Override::Material {
+                        surface: 1,
+                        material: Material::Constant(1.7),
+                    },
+                    Override::StopIndex(0),
+                ],
+            ),
+        ];
+
+        system
+    }
+
+    #[test]
+    fn applies_overrides() {
+        let system = zoom();
+        let configurations = system.all_configurations().unwrap();
+
+        assert_eq!(configurations.len(), 2);
+        assert_eq!(configurations[0].object.distance, 50.0);
+        assert_eq!(configurations[0].surfaces[2].thickness, 10.55);
+        assert_eq!(configurations[1].object.distance, system.object.distance);
+        assert_eq!(configurations[1].surfaces[2].thickness, 9.0);
+        assert!(matches!(
+            configurations[1].surfaces[1].material,
+            Material::Constant(n) if n == 1.7
+        ));
+        assert_eq!(configurations[1].stop_index, 0);
+        assert!(configurations.iter().all(|c| c.configurations.is_empty()));
+        assert!(system.configuration(2).is_err());
+    }
+
+    #[test]
+    fn configuration_variables_edit_overrides() {
+        let mut system = zoom();
+        system.variables = vec![Variable::new(2, Parameter::Thickness).in_configuration(1)];
+
+        assert_eq!(system.variable_values().unwrap(), vec![9.0]);
+        let system = system.with_variable_values(&[8.5]).unwrap();
+        assert_eq!(system.configuration(1).unwrap().surfaces[2].thickness, 8.5);
+        assert_eq!(system.surfaces[2].thickness, 10.55);
+
+        // Configuration 0 doesn't override the thickness
+        let mut near = system.clone();
+        near.variables = vec![Variable::new(2, Parameter::Thickness).in_configuration(0)];
+        assert!(near.variable_values().is_err());
+    }
+
+    #[test]
+    fn rejects_overridden_variables() {
+        let mut system = zoom();
+        system.variables = vec![Variable::new(1, Parameter::Index)];
+
+        let error = system.variable_values().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the Index of surface 1 is overridden by configuration 1 (far), vary the override \
+             instead"
+        );
+    }
+}
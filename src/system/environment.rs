@@ -0,0 +1,239 @@
+use super::{Material, System};
+
+/// Conditions the system operates in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Environment {
+    /// Temperature, in °C.
+    pub temperature: f32,
+    /// Air pressure, in atmospheres.
+    pub pressure: f32,
+}
+
+impl Default for Environment {
+    /// Reference conditions of the prescription, 20 °C and 1 atm.
+    fn default() -> Self {
+        Self {
+            temperature: 20.0,
+            pressure: 1.0,
+        }
+    }
+}
+
+impl Environment {
+    /// Absolute index of air at `wavelength` (Kohlrausch).
+    pub fn air_index(&self, wavelength: f32) -> f32 {
+        let l2 = wavelength * wavelength;
+        let reference = 1.0
+            + (6432.8 + 2_949_810.0 * l2 / (146.0 * l2 - 1.0) + 25540.0 * l2 / (41.0 * l2 - 1.0))
+                * 1e-8;
+
+        1.0 + (reference - 1.0) * self.pressure / (1.0 + (self.temperature - 15.0) * 3.4785e-3)
+    }
+
+    /// Index of `material` relative to the air of this environment.
+    ///
+    /// The nominal index is taken relative to air at 1 atm and at the reference temperature of the
+    /// material, its absolute index changing with temperature following the Schott model.
+    pub fn refractive_index(&self, material: &Material, wavelength: f32) -> f32 {
+        let thermal = material.thermal();
+        let reference = Self {
+            temperature: thermal.reference_temperature,
+            pressure: 1.0,
+        };
+
+        let index = material.refractive_index(wavelength) * reference.air_index(wavelength);
+        let change = thermal.index_change(
+            index,
+            wavelength,
+            self.temperature - thermal.reference_temperature,
+        );
+
+        (index + change) / self.air_index(wavelength)
+    }
+}
+
+impl System {
+    /// Copy of the system with the indices, radii and spacings it has in its environment.
+    ///
+    /// Glasses expand with their own coefficient, air spaces with the one of their mount. The
+    /// copy is given in the reference environment so that it isn't perturbed twice.
+    pub fn perturbed(&self) -> System {
+        let delta = self.environment.temperature - Environment::default().temperature;
+        let perturb = |material: &Material| match material {
+            Material::Constant(1.0) => material.clone(),
+            _ => Material::Perturbed {
+                material: Box::new(material.clone()),
+                environment: self.environment,
+            },
+        };
+
+        let mut system = self.clone();
+        system.environment = Environment::default();
+        system.object.material = perturb(&self.object.material);
+
+        let mut previous = &self.object.material;
+        for (surface, nominal) in system.surfaces.iter_mut().zip(&self.surfaces) {
+            let following = nominal.material.expansion();
+
+            // Radii follow the glass bounding the surface, preferably the one behind it
+            let scale = 1.0
+                + if following != 0.0 {
+                    following
+                } else {
+                    previous.expansion()
+                } * delta;
+            surface.curvature /= scale;
+            surface.semi_diameter *= scale;
+
+            let spacer = if following != 0.0 {
+                following
+            } else {
+                nominal.mount_expansion
+            };
+            surface.thickness *= 1.0 + spacer * delta;
+            surface.material = perturb(&nominal.material);
+
+            previous = &nominal.material;
+        }
+
+        system
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Point3, Vector3};
+
+    use super::*;
+    use crate::{
+        compute::raytracing::Query,
+        glass::{Formula, Glass, Thermal},
+        system::Ray,
+    };
+
+    /// Schott N-BK7, with its dispersion, expansion and thermal coefficients.
+    fn n_bk7() -> Material {
+        Material::Glass(Arc::new(Glass {
+            name: "N-BK7".to_string(),
+            catalog: "SCHOTT".to_string(),
+            formula: Formula::Sellmeier1,
+            coefficients: [
+                1.039612, 0.0060007, 0.2317923, 0.02001791, 1.010469, 103.5607, 0.0, 0.0, 0.0, 0.0,
+            ],
+            nd: 1.5168,
+            vd: 64.17,
+            comment: String::new(),
+            expansion: 7.1e-6,
+            density: 2.51,
+            thermal: Thermal {
+                d0: 1.86e-6,
+                d1: 1.31e-8,
+                d2: -1.37e-11,
+                e0: 4.34e-7,
+                e1: 6.27e-10,
+                lambda_tk: 0.17,
+                reference_temperature: 20.0,
+            },
+            wavelength_range: (0.3, 2.5),
+            transmission: Vec::new(),
+        }))
+    }
+
+    fn heated() -> System {
+        let mut system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        system.surfaces[0].material = n_bk7();
+        for surface in &mut system.surfaces {
+            surface.mount_expansion = 23.6e-6;
+        }
+        system.environment.temperature = 40.0;
+
+        system
+    }
+
+    #[test]
+    fn expands_with_the_glass_and_the_mounts() {
+        let system = heated();
+        let perturbed = system.perturbed();
+        assert_eq!(perturbed.environment, Environment::default());
+
+        let assert_scaled = |value: f32, nominal: f32, expansion: f32| {
+            let expected = nominal * (1.0 + 20.0 * expansion);
+            assert!(
+                (value - expected).abs() <= 1e-6 * expected.abs(),
+                "{value} instead of {expected}"
+            );
+        };
+        let (nominal, surfaces) = (&system.surfaces, &perturbed.surfaces);
+
+        // The glass expands its surfaces and its thickness
+        assert_scaled(
+            surfaces[0].curvature.recip(),
+            nominal[0].curvature.recip(),
+            7.1e-6,
+        );
+        assert_scaled(surfaces[0].semi_diameter, nominal[0].semi_diameter, 7.1e-6);
+        assert_scaled(surfaces[0].thickness, nominal[0].thickness, 7.1e-6);
+
+        // The model glass behind has no expansion, the cemented surface following N-BK7 and
+        // the spacing the mount
+        assert_scaled(
+            surfaces[1].curvature.recip(),
+            nominal[1].curvature.recip(),
+            7.1e-6,
+        );
+        assert_scaled(surfaces[1].thickness, nominal[1].thickness, 23.6e-6);
+        assert_eq!(surfaces[2].curvature, nominal[2].curvature);
+        assert_scaled(surfaces[2].thickness, nominal[2].thickness, 23.6e-6);
+    }
+
+    #[test]
+    fn shifts_the_index_with_temperature() {
+        // Schott datasheet: absolute dn/dT of 1.6e-6 / K at the e line from 20 to 40 °C
+        let material = n_bk7();
+        let wavelength = 0.546074;
+        let absolute = |temperature: f32| {
+            let environment = Environment {
+                temperature,
+                pressure: 1.0,
+            };
+
+            environment.refractive_index(&material, wavelength) * environment.air_index(wavelength)
+        };
+
+        let slope = (absolute(40.0) - absolute(20.0)) / 20.0;
+        assert!((slope - 1.6e-6).abs() < 0.1e-6, "{slope}");
+    }
+
+    #[tokio::test]
+    async fn perturbed_materials_trace_alike() {
+        let system = heated().perturbed();
+        assert!(matches!(
+            system.surfaces[0].material,
+            Material::Perturbed { .. }
+        ));
+
+        let rays = [-0.05, 0.0, 0.05]
+            .into_iter()
+            .flat_map(|slope| {
+                [0.45, 0.55, 0.7].map(|wavelength| {
+                    Ray::new(
+                        Point3::new(0.0, 0.0, -20.0),
+                        Vector3::new(0.0, slope, 1.0).normalize(),
+                    )
+                    .with_wavelength(wavelength)
+                })
+            })
+            .collect();
+        let query = Query { rays };
+
+        let cpu = system.trace_cpu(&query);
+        let gpu = system.trace(&query).await.unwrap();
+        // Tight enough to see the 3e-5 index shift of the heated glass
+        for (cpu, gpu) in cpu.intersections.iter().zip(&gpu.intersections) {
+            let error = (cpu.ray.direction - gpu.ray.direction).norm();
+            assert!(error < 1e-6, "{error}");
+        }
+    }
+}
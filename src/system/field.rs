@@ -0,0 +1,45 @@
+use nalgebra::Point2;
+
+/// Vignetting factors, in normalized pupil coordinates.
+///
+/// A pupil point `p` is remapped to `decenter + (1 - compression) * p` before being traced.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Vignetting {
+    /// Shift of the pupil along x.
+    pub decenter_x: f32,
+    /// Shift of the pupil along y.
+    pub decenter_y: f32,
+    /// Fraction the pupil shrinks by along x.
+    pub compression_x: f32,
+    /// Fraction the pupil shrinks by along y.
+    pub compression_y: f32,
+}
+
+impl Vignetting {
+    /// Remaps the normalized `pupil` point.
+    pub fn apply(&self, pupil: Point2<f32>) -> Point2<f32> {
+        Point2::new(
+            self.decenter_x + (1.0 - self.compression_x) * pupil.x,
+            self.decenter_y + (1.0 - self.compression_y) * pupil.y,
+        )
+    }
+}
+
+/// Point of the object traced by the analyses.
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    /// Point of the object plane.
+    pub point: Point2<f32>,
+    /// Vignetting of the pupil seen from the point.
+    pub vignetting: Vignetting,
+}
+
+impl Field {
+    /// Field at `(x, y)` on the object plane, unvignetted.
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            point: Point2::new(x, y),
+            vignetting: Vignetting::default(),
+        }
+    }
+}
@@ -1,16 +1,28 @@
 use nalgebra::{Point3, Vector3};
 
-use super::Ray;
+use super::{Ray, Surface};
 
-#[derive(Debug, encase::ShaderType)]
+/// Ray reaching a surface.
+#[derive(Debug, Clone, Copy, encase::ShaderType)]
 pub struct Intersection {
+    /// Ray before refraction by the surface.
     pub ray: Ray,
+    /// Surface normal at the intersection.
     pub normal: Vector3<f32>,
+    /// Distance from the ray origin to the intersection.
     pub t: f32,
 }
 
 impl Intersection {
+    /// Point where the ray meets the surface.
     pub fn point(&self) -> Point3<f32> {
         (self.ray.origin + self.ray.direction * self.t).into()
     }
+
+    /// Whether the intersection exists and lies within the clear aperture of `surface`.
+    pub fn is_within(&self, surface: &Surface) -> bool {
+        let point = self.point();
+
+        point.x.hypot(point.y) <= surface.semi_diameter
+    }
 }
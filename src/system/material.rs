@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use super::{Environment, FRAUNHOFER_C, FRAUNHOFER_D, FRAUNHOFER_F};
+use crate::glass::{Glass, Thermal};
+
+/// Optical medium, its refractive index being a function of the wavelength in micrometres.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Material {
+    /// Non-dispersive medium.
+    Constant(f32),
+    /// Model glass described by its d line index and Abbe number.
+    Model {
+        /// Index at the d line.
+        nd: f32,
+        /// Abbe number at the d line.
+        vd: f32,
+    },
+    /// n² = 1 + Σ bᵢ λ² / (λ² - cᵢ)
+    Sellmeier {
+        /// Strengths of the resonances.
+        b: [f32; 3],
+        /// Squared resonance wavelengths, in µm².
+        c: [f32; 3],
+    },
+    /// n² = a₀ + a₁ λ² + a₂ λ⁻² + a₃ λ⁻⁴ + a₄ λ⁻⁶ + a₅ λ⁻⁸
+    Schott([f32; 6]),
+    /// n = n₀ + a / λ + b / λ^3.5
+    Conrady {
+        /// Index at infinite wavelength.
+        n0: f32,
+        /// Coefficient of λ⁻¹.
+        a: f32,
+        /// Coefficient of λ^-3.5.
+        b: f32,
+    },
+    /// n = a + b / λ² + c / λ⁴
+    Cauchy {
+        /// Index at infinite wavelength.
+        a: f32,
+        /// Coefficient of λ⁻².
+        b: f32,
+        /// Coefficient of λ⁻⁴.
+        c: f32,
+    },
+    /// n = a + b L + c L² + d λ² + e λ⁴ + f λ⁶, with L = 1 / (λ² - 0.028)
+    Herzberger([f32; 6]),
+    /// Glass read from a catalog.
+    Glass(Arc<Glass>),
+    /// Material taken to another environment, its index being relative to the air around it.
+    Perturbed {
+        /// Material at the reference conditions of the prescription.
+        material: Box<Material>,
+        /// Conditions the material is taken to.
+        environment: Environment,
+    },
+}
+
+impl Material {
+    /// Index relative to the air of the prescription at `wavelength`, in micrometres.
+    pub fn refractive_index(&self, wavelength: f32) -> f32 {
+        let l2 = wavelength * wavelength;
+
+        match self {
+            Self::Constant(n) => *n,
+            Self::Model { .. } => self.to_cauchy().refractive_index(wavelength),
+            Self::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f32>()).sqrt()
+            }
+            Self::Schott(a) => (a[0]
+                + a[1] * l2
+                + a[2] / l2
+                + a[3] / (l2 * l2)
+                + a[4] / (l2 * l2 * l2)
+                + a[5] / (l2 * l2 * l2 * l2))
+                .sqrt(),
+            Self::Conrady { n0, a, b } => n0 + a / wavelength + b / wavelength.powf(3.5),
+            Self::Cauchy { a, b, c } => a + b / l2 + c / (l2 * l2),
+            Self::Herzberger(a) => {
+                let l = 1.0 / (l2 - 0.028);
+                a[0] + a[1] * l + a[2] * l * l + a[3] * l2 + a[4] * l2 * l2 + a[5] * l2 * l2 * l2
+            }
+            Self::Glass(glass) => glass.refractive_index(wavelength),
+            Self::Perturbed {
+                material,
+                environment,
+            } => environment.refractive_index(material, wavelength),
+        }
+    }
+
+    /// Thermal expansion coefficient, in 1/K, zero when unknown.
+    pub fn expansion(&self) -> f32 {
+        match self {
+            Self::Glass(glass) => glass.expansion,
+            Self::Perturbed { material, .. } => material.expansion(),
+            _ => 0.0,
+        }
+    }
+
+    /// Temperature dependence of the index, none when unknown.
+    pub fn thermal(&self) -> Thermal {
+        match self {
+            Self::Glass(glass) => glass.thermal,
+            Self::Perturbed { material, .. } => material.thermal(),
+            _ => Thermal::default(),
+        }
+    }
+
+    /// Index at the helium d line.
+    pub fn nd(&self) -> f32 {
+        self.refractive_index(FRAUNHOFER_D)
+    }
+
+    /// Abbe number, infinite for non-dispersive media.
+    pub fn vd(&self) -> f32 {
+        (self.nd() - 1.0)
+            / (self.refractive_index(FRAUNHOFER_F) - self.refractive_index(FRAUNHOFER_C))
+    }
+
+    /// Two-term Cauchy formula matching a model glass at the d, F and C lines.
+    ///
+    /// Other materials are returned unchanged.
+    pub fn to_cauchy(&self) -> Self {
+        match *self {
+            Self::Model { nd, vd } => {
+                let b = (nd - 1.0) / vd / (FRAUNHOFER_F.powi(-2) - FRAUNHOFER_C.powi(-2));
+
+                Self::Cauchy {
+                    a: nd - b / (FRAUNHOFER_D * FRAUNHOFER_D),
+                    b,
+                    c: 0.0,
+                }
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::Constant(1.0)
+    }
+}
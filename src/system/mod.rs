@@ -1,25 +1,68 @@
+//! Optical systems: surfaces, materials, fields, wavelengths and their prescription files.
+
+mod configuration;
+mod environment;
+mod field;
 mod intersection;
+mod material;
 mod object;
+mod paraxial;
+mod prescription;
 mod ray;
+mod seq;
+mod solve;
+mod splice;
 mod surface;
+mod wavelength;
+mod zmx;
 
 use bytemuck::Contiguous;
+pub use configuration::*;
 use encase::ShaderSize;
+pub use environment::*;
+pub use field::*;
 pub use intersection::*;
+pub use material::*;
 use nalgebra::Point3;
 pub use object::*;
+pub use paraxial::*;
 pub use ray::*;
+pub use solve::*;
 pub use surface::*;
+pub use wavelength::*;
 use wgpu::{include_wgsl, util::DeviceExt};
+pub use zmx::*;
 
-use crate::compute;
+use crate::{
+    compute,
+    optimization::{Constraint, Variable},
+};
 
-#[derive(Debug, Default, encase::ShaderType)]
+/// Sequential optical system, from the object to the image surface.
+#[derive(Debug, Default, Clone)]
 pub struct System {
+    /// Object space, before the first surface.
     pub object: Object,
+    /// Index of the aperture stop in `surfaces`.
     pub stop_index: u32,
-    #[size(runtime)]
+    /// Surfaces in the order the light reaches them, the last one being the image.
     pub surfaces: Vec<Surface>,
+    /// Points of the object traced by the analyses.
+    pub fields: Vec<Field>,
+    /// Spectrum of the system.
+    pub wavelengths: Vec<Wavelength>,
+    /// Index of the primary wavelength in `wavelengths`.
+    pub primary: u32,
+    /// Conditions the system operates in.
+    pub environment: Environment,
+    /// Parameters free during design.
+    pub variables: Vec<Variable>,
+    /// Limits the optimizers keep the system within.
+    pub constraints: Vec<Constraint>,
+    /// Parameters computed from the rest of the system before every trace.
+    pub solves: Vec<Solve>,
+    /// Variants sharing the surfaces, empty for a single configuration.
+    pub configurations: Vec<Configuration>,
 }
 
 impl System {
@@ -34,11 +77,88 @@ impl System {
         ))
     }
 
+    /// Traces the rays of `query` on the GPU, returning their intersections with every surface.
     pub async fn trace(
         &self,
         query: &compute::raytracing::Query,
     ) -> anyhow::Result<compute::raytracing::Response> {
-        let n_intersections = self.surfaces.len() * query.rays.len();
+        Self::dispatch(&compute::layout::System::from(&*self.solved()), query).await
+    }
+
+    /// Traces `queries[i]` through `systems[i]` in a single dispatch.
+    ///
+    /// The systems must share their object medium and number of surfaces. Queries with fewer rays
+    /// than the longest one are padded, the padding being dropped from the responses.
+    pub async fn trace_batch(
+        systems: &[System],
+        queries: &[compute::raytracing::Query],
+    ) -> anyhow::Result<Vec<compute::raytracing::Response>> {
+        anyhow::ensure!(
+            !systems.is_empty() && systems.len() == queries.len(),
+            "expected one query per system"
+        );
+
+        let n_surfaces = systems[0].surfaces.len();
+        let n_rays = queries
+            .iter()
+            .map(|query| query.rays.len())
+            .max()
+            .unwrap_or_default();
+        anyhow::ensure!(
+            systems
+                .iter()
+                .all(|system| system.surfaces.len() == n_surfaces),
+            "batched systems must have the same number of surfaces"
+        );
+
+        if n_rays == 0 {
+            return Ok(queries.iter().map(|_| Default::default()).collect());
+        }
+
+        let query = compute::raytracing::Query {
+            rays: queries
+                .iter()
+                .flat_map(|query| {
+                    let padding = query
+                        .rays
+                        .first()
+                        .copied()
+                        .unwrap_or_else(|| Ray::new(Point3::origin(), nalgebra::Vector3::z()));
+
+                    query
+                        .rays
+                        .iter()
+                        .copied()
+                        .chain(std::iter::repeat(padding))
+                        .take(n_rays)
+                })
+                .collect(),
+        };
+        let systems = systems
+            .iter()
+            .map(|system| system.solved().into_owned())
+            .collect::<Vec<_>>();
+        let response = Self::dispatch(
+            &compute::layout::System::batch(&systems, n_rays as u32),
+            &query,
+        )
+        .await?;
+
+        Ok(response
+            .intersections
+            .chunks(n_rays * n_surfaces)
+            .zip(queries)
+            .map(|(intersections, query)| compute::raytracing::Response {
+                intersections: intersections[..query.rays.len() * n_surfaces].to_vec(),
+            })
+            .collect())
+    }
+
+    async fn dispatch(
+        system: &compute::layout::System,
+        query: &compute::raytracing::Query,
+    ) -> anyhow::Result<compute::raytracing::Response> {
+        let n_intersections = system.surface_count as usize * query.rays.len();
         let gpu = compute::Gpu::new().await?;
 
         let module = gpu
@@ -48,7 +168,7 @@ impl System {
         let mut system_bytes_buffer = encase::StorageBuffer::new(Vec::<u8>::new());
         let mut query_bytes_buffer = encase::StorageBuffer::new(Vec::<u8>::new());
 
-        system_bytes_buffer.write(&self)?;
+        system_bytes_buffer.write(system)?;
         query_bytes_buffer.write(&query)?;
 
         let system_buffer = gpu
@@ -56,9 +176,7 @@ impl System {
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("System Buffer"),
                 contents: system_bytes_buffer.as_ref(),
-                usage: wgpu::BufferUsages::MAP_READ
-                    | wgpu::BufferUsages::COPY_DST
-                    | wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE,
             });
 
         let query_buffer = gpu
@@ -66,17 +184,20 @@ impl System {
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Query Buffer"),
                 contents: query_bytes_buffer.as_ref(),
-                usage: wgpu::BufferUsages::MAP_READ
-                    | wgpu::BufferUsages::COPY_DST
-                    | wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE,
             });
 
         let result_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Result Buffer"),
             size: (n_intersections as u64) * Intersection::SHADER_SIZE.into_integer(),
-            usage: wgpu::BufferUsages::MAP_READ
-                | wgpu::BufferUsages::COPY_DST
-                | wgpu::BufferUsages::STORAGE,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let staging_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Staging Buffer"),
+            size: result_buffer.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
@@ -137,13 +258,15 @@ impl System {
             pass.set_pipeline(&pipeline);
             pass.set_bind_group(0, &bind_group0, &[]);
             pass.set_bind_group(1, &bind_group1, &[]);
-            pass.dispatch_workgroups(query.rays.len() as _, 1, 1);
+            pass.dispatch_workgroups((query.rays.len() as u32).div_ceil(64), 1, 1);
         }
 
+        encoder.copy_buffer_to_buffer(&result_buffer, 0, &staging_buffer, 0, result_buffer.size());
+
         gpu.queue.submit(Some(encoder.finish()));
 
         {
-            let buffer_slice = result_buffer.slice(..);
+            let buffer_slice = staging_buffer.slice(..);
             // let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read);
             let (sender, receiver) = tokio::sync::oneshot::channel();
 
@@ -162,6 +285,7 @@ impl System {
         }
     }
 
+    /// Traces a fan of rays on the GPU, returning their heights at the aperture stop.
     pub async fn fan(&self, query: &compute::fan::Query) -> anyhow::Result<compute::fan::Response> {
         let gpu = compute::Gpu::new().await?;
 
@@ -172,7 +296,7 @@ impl System {
         let mut system_bytes_buffer = encase::StorageBuffer::new(Vec::<u8>::new());
         let mut query_bytes_buffer = encase::StorageBuffer::new(Vec::<u8>::new());
 
-        system_bytes_buffer.write(&self)?;
+        system_bytes_buffer.write(&compute::layout::System::from(&*self.solved()))?;
         query_bytes_buffer.write(&query)?;
 
         let system_buffer = gpu
@@ -180,9 +304,7 @@ impl System {
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("System Buffer"),
                 contents: system_bytes_buffer.as_ref(),
-                usage: wgpu::BufferUsages::MAP_READ
-                    | wgpu::BufferUsages::COPY_DST
-                    | wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE,
             });
 
         let query_buffer = gpu
@@ -190,17 +312,20 @@ impl System {
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Query Buffer"),
                 contents: query_bytes_buffer.as_ref(),
-                usage: wgpu::BufferUsages::MAP_READ
-                    | wgpu::BufferUsages::COPY_DST
-                    | wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE,
             });
 
         let result_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Result Buffer"),
             size: (query.resolution as u64) * f32::SHADER_SIZE.into_integer(),
-            usage: wgpu::BufferUsages::MAP_READ
-                | wgpu::BufferUsages::COPY_DST
-                | wgpu::BufferUsages::STORAGE,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let staging_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Staging Buffer"),
+            size: result_buffer.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
@@ -264,10 +389,12 @@ impl System {
             pass.dispatch_workgroups(query.resolution, 1, 1);
         }
 
+        encoder.copy_buffer_to_buffer(&result_buffer, 0, &staging_buffer, 0, result_buffer.size());
+
         gpu.queue.submit(Some(encoder.finish()));
 
         {
-            let buffer_slice = result_buffer.slice(..);
+            let buffer_slice = staging_buffer.slice(..);
             // let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read);
             let (sender, receiver) = tokio::sync::oneshot::channel();
 
@@ -286,11 +413,14 @@ impl System {
         }
     }
 
+    /// Ray from the top of the object through the center of the aperture stop at the primary wavelength, found by narrowing fans of real rays.
     pub async fn find_chief_ray(&self) -> anyhow::Result<Ray> {
         let origin = self.object.top();
 
         let directions = {
-            let entry_limits = self.surface_edges(0).unwrap();
+            let entry_limits = self
+                .surface_edges(0)
+                .ok_or_else(|| anyhow::anyhow!("the system has no surfaces"))?;
 
             (
                 (entry_limits.0 - origin).normalize(),
@@ -303,6 +433,7 @@ impl System {
             dir_a: directions.0,
             dir_b: directions.1,
             resolution: 1024,
+            wavelength: self.primary_wavelength(),
         };
 
         let mut i = 0;
@@ -323,9 +454,10 @@ impl System {
                 });
 
             if let Some(index) = result.next() {
-                if result.next().is_some() {
-                    panic!("Unexpected");
-                }
+                anyhow::ensure!(
+                    result.next().is_none(),
+                    "several rays from the top of the object cross the center of the stop"
+                );
 
                 query = compute::fan::Query {
                     dir_a: query
@@ -344,17 +476,20 @@ impl System {
 
                 let height = response.heights[index];
                 if height.abs() < 1e-6 || i > 128 {
-                    dbg!(i, height);
                     break query.dir_a;
                 }
             } else {
-                dbg!(i);
+                // The crossing was lost between two samples of the narrowed fan
+                anyhow::ensure!(
+                    i > 0,
+                    "no ray from the top of the object crosses the center of the stop"
+                );
                 break query.dir_a.lerp(&query.dir_b, 0.5).normalize();
             }
 
             i += 1;
         };
 
-        Ok(Ray::new(origin, direction))
+        Ok(Ray::new(origin, direction).with_wavelength(query.wavelength))
     }
 }
@@ -1,14 +1,26 @@
 use nalgebra::Point3;
 
-#[derive(Debug, Default, encase::ShaderType)]
+use super::Material;
+
+/// Object plane and the medium it's in.
+#[derive(Debug, Default, Clone)]
 pub struct Object {
+    /// Distance from the object to the first surface.
     pub distance: f32,
+    /// Half the height of the object.
     pub semi_diameter: f32,
-    pub refractive_index: f32,
+    /// Medium before the first surface.
+    pub material: Material,
 }
 
 impl Object {
+    /// Top of the object, on the y axis.
     pub fn top(&self) -> Point3<f32> {
         Point3::new(0.0, self.semi_diameter, -self.distance)
     }
+
+    /// Refractive index of the object space at `wavelength`.
+    pub fn refractive_index_at(&self, wavelength: f32) -> f32 {
+        self.material.refractive_index(wavelength)
+    }
 }
@@ -0,0 +1,219 @@
+use nalgebra::{Point2, Point3};
+
+use super::{Field, Ray, System};
+
+/// Meridional paraxial ray, described by its height and slope.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParaxialRay {
+    /// Height above the axis.
+    pub height: f32,
+    /// Tangent of the angle to the axis.
+    pub slope: f32,
+}
+
+/// Paraxial image of the aperture stop.
+#[derive(Debug, Clone, Copy)]
+pub struct Pupil {
+    /// Position along the axis, relative to the first surface.
+    pub z: f32,
+    /// Radius of the pupil.
+    pub semi_diameter: f32,
+}
+
+impl System {
+    /// Traces a paraxial ray leaving the object plane at `wavelength`.
+    ///
+    /// Returns, for every surface, the height at its vertex plane and the slope after refraction.
+    pub fn paraxial_trace(&self, ray: ParaxialRay, wavelength: f32) -> Vec<ParaxialRay> {
+        let mut n0 = self.object.refractive_index_at(wavelength);
+        let mut height = ray.height + ray.slope * self.object.distance;
+        let mut slope = ray.slope;
+
+        self.surfaces
+            .iter()
+            .map(|surface| {
+                let n1 = surface.refractive_index_at(wavelength);
+                slope = (n0 * slope - height * surface.curvature * (n1 - n0)) / n1;
+
+                let ray = ParaxialRay { height, slope };
+
+                height += surface.thickness * slope;
+                n0 = n1;
+
+                ray
+            })
+            .collect()
+    }
+
+    /// Returns the `(height, slope)` coefficients that map an object ray to its height at the stop.
+    fn stop_coefficients(&self) -> (f32, f32) {
+        let stop = self.stop_index as usize;
+        let height = self.paraxial_trace(
+            ParaxialRay {
+                height: 1.0,
+                slope: 0.0,
+            },
+            self.primary_wavelength(),
+        )[stop]
+            .height;
+        let slope = self.paraxial_trace(
+            ParaxialRay {
+                height: 0.0,
+                slope: 1.0,
+            },
+            self.primary_wavelength(),
+        )[stop]
+            .height;
+
+        (height, slope)
+    }
+
+    /// Ray from the axial object point to the edge of the aperture stop.
+    pub fn paraxial_marginal_ray(&self) -> ParaxialRay {
+        let (_, b) = self.stop_coefficients();
+        let stop = &self.surfaces[self.stop_index as usize];
+
+        ParaxialRay {
+            height: 0.0,
+            slope: stop.semi_diameter / b,
+        }
+    }
+
+    /// Ray from the top of the object to the center of the aperture stop.
+    pub fn paraxial_chief_ray(&self) -> ParaxialRay {
+        let (a, b) = self.stop_coefficients();
+        let height = self.object.semi_diameter;
+
+        ParaxialRay {
+            height,
+            slope: -height * a / b,
+        }
+    }
+
+    /// Paraxial image of the stop in object space, at the primary wavelength.
+    pub fn entrance_pupil(&self) -> Pupil {
+        let (a, b) = self.stop_coefficients();
+        let stop = &self.surfaces[self.stop_index as usize];
+
+        Pupil {
+            z: b / a - self.object.distance,
+            semi_diameter: (stop.semi_diameter / a).abs(),
+        }
+    }
+
+    /// Paraxial image of the aperture stop in image space, `z` being measured like the surface
+    /// vertices.
+    pub fn exit_pupil(&self) -> Pupil {
+        let wavelength = self.primary_wavelength();
+        let last = self.surfaces[..self.surfaces.len().saturating_sub(1)]
+            .iter()
+            .map(|surface| surface.thickness)
+            .sum::<f32>();
+
+        // Heights at the image surface vertex and image space slopes, i.e. before the image
+        let image = |ray| {
+            let traced = self.paraxial_trace(ray, wavelength);
+            let n = traced.len();
+
+            (n >= 2).then(|| ParaxialRay {
+                height: traced[n - 1].height,
+                slope: traced[n - 2].slope,
+            })
+        };
+
+        match (
+            image(self.paraxial_chief_ray()),
+            image(self.paraxial_marginal_ray()),
+        ) {
+            (Some(chief), Some(marginal)) => {
+                let distance = -chief.height / chief.slope;
+
+                Pupil {
+                    z: last + distance,
+                    semi_diameter: (marginal.height + marginal.slope * distance).abs(),
+                }
+            }
+            _ => Pupil {
+                z: 0.0,
+                semi_diameter: 0.0,
+            },
+        }
+    }
+
+    /// Distance from the last surface to the paraxial focus at `wavelength`.
+    pub fn paraxial_focus(&self, wavelength: f32) -> f32 {
+        let marginal = ParaxialRay {
+            height: 0.0,
+            slope: 1.0,
+        };
+
+        self.paraxial_trace(marginal, wavelength)
+            .last()
+            .map_or(0.0, |image| -image.height / image.slope)
+    }
+
+    /// Effective focal length at `wavelength`, from a ray parallel to the axis at unit height.
+    pub fn effective_focal_length(&self, wavelength: f32) -> f32 {
+        let ray = ParaxialRay {
+            height: 1.0,
+            slope: 0.0,
+        };
+
+        self.paraxial_trace(ray, wavelength)
+            .last()
+            .map_or(f32::INFINITY, |image| -1.0 / image.slope)
+    }
+
+    /// Image space working F-number at `wavelength`, from the paraxial marginal ray.
+    pub fn working_f_number(&self, wavelength: f32) -> f32 {
+        let index = self
+            .surfaces
+            .last()
+            .map_or(1.0, |surface| surface.refractive_index_at(wavelength));
+
+        self.paraxial_trace(self.paraxial_marginal_ray(), wavelength)
+            .last()
+            .map_or(f32::INFINITY, |image| 0.5 / (index * image.slope.abs()))
+    }
+
+    /// Ratio between the paraxial chief ray height on the last surface and the object height.
+    pub fn paraxial_magnification(&self) -> f32 {
+        let (a, b) = self.stop_coefficients();
+        let chief = ParaxialRay {
+            height: 1.0,
+            slope: -a / b,
+        };
+
+        self.paraxial_trace(chief, self.primary_wavelength())
+            .last()
+            .map_or(0.0, |image| image.height)
+    }
+
+    /// Real ray at the primary wavelength from a point of the object plane aimed at the paraxial
+    /// entrance pupil.
+    ///
+    /// `pupil` is given in normalized coordinates, the unit circle being the pupil edge.
+    pub fn pupil_ray(&self, object: Point2<f32>, pupil: Point2<f32>) -> Ray {
+        let entrance = self.entrance_pupil();
+        let origin = Point3::new(object.x, object.y, -self.object.distance);
+
+        let direction = if entrance.z.is_finite() {
+            let target = Point3::new(
+                pupil.x * entrance.semi_diameter,
+                pupil.y * entrance.semi_diameter,
+                entrance.z,
+            );
+
+            (target - origin).normalize()
+        } else {
+            nalgebra::Vector3::z()
+        };
+
+        Ray::new(origin, direction).with_wavelength(self.primary_wavelength())
+    }
+
+    /// Same as [`System::pupil_ray`], with the pupil point remapped by the field vignetting factors.
+    pub fn field_ray(&self, field: &Field, pupil: Point2<f32>) -> Ray {
+        self.pupil_ray(field.point, field.vignetting.apply(pupil))
+    }
+}
@@ -0,0 +1,1170 @@
+//! Human-editable TOML prescription files.
+//!
+//! ```toml
+//! stop = 3
+//! primary = 1
+//!
+//! [object]
+//! distance = 2.0
+//! semi_diameter = 1.0
+//! material = 1.0
+//!
+//! [[wavelengths]]
+//! value = 0.5875618
+//!
+//! [[fields]]
+//! y = 0.7
+//!
+//! [[surfaces]]
+//! radius = 7.3895
+//! thickness = 1.05
+//! material = { nd = 1.517, vd = 64.2 }
+//! semi_diameter = 2.0
+//!
+//! [[variables]]
+//! surface = 0
+//! parameter = "curvature"
+//! ```
+//!
+//! Materials are written as a constant index, `{ glass = "N-BK7", catalog = "SCHOTT" }`,
+//! `{ nd, vd }` for model glasses, or the coefficients of a dispersion formula:
+//! `{ sellmeier = { b, c } }`, `{ schott }`, `{ conrady = { n0, a, b } }`,
+//! `{ cauchy = { a, b, c } }` and `{ herzberger }`. Surfaces without a radius are planes.
+//!
+//! A variable on a parameter that a configuration overrides names that `configuration`, and edits
+//! its override rather than the surface.
+
+use std::path::Path;
+
+use anyhow::Context;
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Configuration, Environment, Field, Material, Object, Override, Solve, Surface, System,
+    Vignetting, Wavelength,
+};
+use crate::{
+    glass::Catalog,
+    optimization::{Bounds, Constraint, Parameter, Variable},
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Prescription {
+    /// Index of the aperture stop in `surfaces`.
+    stop: u32,
+    /// Index of the primary wavelength in `wavelengths`.
+    #[serde(default)]
+    primary: u32,
+    object: ObjectEntry,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    environment: Option<EnvironmentEntry>,
+    #[serde(default)]
+    wavelengths: Vec<WavelengthEntry>,
+    #[serde(default)]
+    fields: Vec<FieldEntry>,
+    /// Read one at a time, for the errors to name the offending surface.
+    surfaces: Vec<toml::Table>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    variables: Vec<VariableEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    constraints: Vec<ConstraintEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    solves: Vec<SolveEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    configurations: Vec<ConfigurationEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectEntry {
+    distance: f64,
+    semi_diameter: f64,
+    material: toml::Value,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentEntry {
+    temperature: f64,
+    pressure: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WavelengthEntry {
+    value: f64,
+    #[serde(default = "one")]
+    weight: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldEntry {
+    #[serde(default)]
+    x: f64,
+    #[serde(default)]
+    y: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vignetting: Option<VignettingEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VignettingEntry {
+    #[serde(default)]
+    decenter_x: f64,
+    #[serde(default)]
+    decenter_y: f64,
+    #[serde(default)]
+    compression_x: f64,
+    #[serde(default)]
+    compression_y: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SurfaceEntry {
+    /// Radius of curvature, a plane when missing or infinite.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    radius: Option<f64>,
+    thickness: f64,
+    material: toml::Value,
+    semi_diameter: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    conic: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    mount_expansion: f64,
+    #[serde(default, skip_serializing_if = "is_origin")]
+    decenter: [f64; 2],
+    /// Rotations about the x and y axes, in radians.
+    #[serde(default, skip_serializing_if = "is_origin")]
+    tilt: [f64; 2],
+}
+
+/// Material in any of its forms, each variant a table of its own rejecting unknown keys, which
+/// `deny_unknown_fields` on an untagged enum doesn't.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum MaterialEntry {
+    Index(f64),
+    Glass(GlassMaterial),
+    Model(ModelMaterial),
+    Sellmeier(SellmeierMaterial),
+    Schott(SchottMaterial),
+    Conrady(ConradyMaterial),
+    Cauchy(CauchyMaterial),
+    Herzberger(HerzbergerMaterial),
+    Perturbed(PerturbedMaterial),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GlassMaterial {
+    glass: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    catalog: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelMaterial {
+    nd: f64,
+    vd: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SellmeierMaterial {
+    sellmeier: SellmeierEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchottMaterial {
+    schott: [f64; 6],
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConradyMaterial {
+    conrady: ConradyEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CauchyMaterial {
+    cauchy: CauchyEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HerzbergerMaterial {
+    herzberger: [f64; 6],
+}
+
+/// Material taken to another environment.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PerturbedMaterial {
+    material: Box<MaterialEntry>,
+    temperature: f64,
+    pressure: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SellmeierEntry {
+    b: [f64; 3],
+    c: [f64; 3],
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConradyEntry {
+    n0: f64,
+    a: f64,
+    b: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CauchyEntry {
+    a: f64,
+    b: f64,
+    c: f64,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ParameterEntry {
+    Curvature,
+    Thickness,
+    Conic,
+    Index,
+    Abbe,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VariableEntry {
+    surface: usize,
+    parameter: ParameterEntry,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max: Option<f64>,
+    /// Configuration whose override the variable edits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    configuration: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ConstraintEntry {
+    CenterThickness {
+        surface: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f64>,
+    },
+    EdgeThickness {
+        surface: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f64>,
+    },
+    TotalTrack {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f64>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum SolveEntry {
+    MarginalHeight {
+        surface: usize,
+        height: f64,
+    },
+    MarginalAngle {
+        surface: usize,
+        slope: f64,
+    },
+    /// Read as the equivalent marginal angle solve.
+    FNumber {
+        surface: usize,
+        f_number: f64,
+    },
+    ChiefHeight {
+        surface: usize,
+        height: f64,
+    },
+    Pickup {
+        surface: usize,
+        parameter: ParameterEntry,
+        from: usize,
+        #[serde(default = "one")]
+        scale: f64,
+        #[serde(default)]
+        offset: f64,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigurationEntry {
+    name: String,
+    #[serde(default)]
+    overrides: Vec<OverrideEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum OverrideEntry {
+    Thickness {
+        surface: usize,
+        value: f64,
+    },
+    Curvature {
+        surface: usize,
+        value: f64,
+    },
+    SemiDiameter {
+        surface: usize,
+        value: f64,
+    },
+    Material {
+        surface: usize,
+        material: toml::Value,
+    },
+    Stop {
+        surface: u32,
+    },
+    ObjectDistance {
+        value: f64,
+    },
+    Fields {
+        fields: Vec<FieldEntry>,
+    },
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+fn is_origin(value: &[f64; 2]) -> bool {
+    *value == [0.0; 2]
+}
+
+/// Shortest decimal value reading back as `value`, keeping single precision numbers readable.
+fn short(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+/// Shortest radius reading back as `curvature`.
+pub(super) fn radius(curvature: f32) -> f64 {
+    let radius = 1.0 / curvature as f64;
+
+    (1..17)
+        .filter_map(|digits| format!("{radius:.digits$e}").parse::<f64>().ok())
+        .find(|rounded| 1.0 / *rounded as f32 == curvature)
+        .unwrap_or(radius)
+}
+
+/// Bound written to the file, left out when infinite.
+fn finite(value: f32) -> Option<f64> {
+    value.is_finite().then(|| short(value))
+}
+
+fn bounds(min: Option<f64>, max: Option<f64>) -> anyhow::Result<Bounds> {
+    let bounds = Bounds::new(
+        min.map_or(f32::NEG_INFINITY, |min| min as f32),
+        max.map_or(f32::INFINITY, |max| max as f32),
+    );
+    anyhow::ensure!(
+        bounds.min <= bounds.max,
+        "minimum {} above maximum {}",
+        bounds.min,
+        bounds.max
+    );
+
+    Ok(bounds)
+}
+
+impl From<Parameter> for ParameterEntry {
+    fn from(parameter: Parameter) -> Self {
+        match parameter {
+            Parameter::Curvature => Self::Curvature,
+            Parameter::Thickness => Self::Thickness,
+            Parameter::Conic => Self::Conic,
+            Parameter::Index => Self::Index,
+            Parameter::Abbe => Self::Abbe,
+        }
+    }
+}
+
+impl From<ParameterEntry> for Parameter {
+    fn from(parameter: ParameterEntry) -> Self {
+        match parameter {
+            ParameterEntry::Curvature => Self::Curvature,
+            ParameterEntry::Thickness => Self::Thickness,
+            ParameterEntry::Conic => Self::Conic,
+            ParameterEntry::Index => Self::Index,
+            ParameterEntry::Abbe => Self::Abbe,
+        }
+    }
+}
+
+impl From<&Material> for MaterialEntry {
+    fn from(material: &Material) -> Self {
+        match material {
+            Material::Constant(n) => Self::Index(short(*n)),
+            Material::Model { nd, vd } => Self::Model(ModelMaterial {
+                nd: short(*nd),
+                vd: short(*vd),
+            }),
+            Material::Sellmeier { b, c } => Self::Sellmeier(SellmeierMaterial {
+                sellmeier: SellmeierEntry {
+                    b: b.map(short),
+                    c: c.map(short),
+                },
+            }),
+            Material::Schott(a) => Self::Schott(SchottMaterial {
+                schott: a.map(short),
+            }),
+            Material::Conrady { n0, a, b } => Self::Conrady(ConradyMaterial {
+                conrady: ConradyEntry {
+                    n0: short(*n0),
+                    a: short(*a),
+                    b: short(*b),
+                },
+            }),
+            Material::Cauchy { a, b, c } => Self::Cauchy(CauchyMaterial {
+                cauchy: CauchyEntry {
+                    a: short(*a),
+                    b: short(*b),
+                    c: short(*c),
+                },
+            }),
+            Material::Herzberger(a) => Self::Herzberger(HerzbergerMaterial {
+                herzberger: a.map(short),
+            }),
+            Material::Glass(glass) => Self::Glass(GlassMaterial {
+                glass: glass.name.clone(),
+                catalog: (!glass.catalog.is_empty()).then(|| glass.catalog.clone()),
+            }),
+            Material::Perturbed {
+                material,
+                environment,
+            } => Self::Perturbed(PerturbedMaterial {
+                material: Box::new(Self::from(&**material)),
+                temperature: short(environment.temperature),
+                pressure: short(environment.pressure),
+            }),
+        }
+    }
+}
+
+impl MaterialEntry {
+    fn material(self, catalogs: &[Catalog]) -> anyhow::Result<Material> {
+        let material = match self {
+            Self::Index(n) => Material::Constant(n as f32),
+            Self::Glass(GlassMaterial { glass, catalog }) => {
+                let found = match &catalog {
+                    Some(name) => catalogs
+                        .iter()
+                        .find(|catalog| catalog.name.eq_ignore_ascii_case(name))
+                        .ok_or_else(|| anyhow::anyhow!("catalog {name} isn't loaded"))?
+                        .glass(&glass),
+                    None => crate::glass::find(catalogs, &glass),
+                };
+
+                Material::Glass(
+                    found
+                        .ok_or_else(|| anyhow::anyhow!("glass {glass} not found in the catalogs"))?
+                        .clone(),
+                )
+            }
+            Self::Model(ModelMaterial { nd, vd }) => {
+                anyhow::ensure!(nd >= 1.0, "model glass index {nd} below 1");
+                anyhow::ensure!(vd > 0.0, "model glass Abbe number {vd} isn't positive");
+
+                Material::Model {
+                    nd: nd as f32,
+                    vd: vd as f32,
+                }
+            }
+            Self::Sellmeier(SellmeierMaterial { sellmeier }) => Material::Sellmeier {
+                b: sellmeier.b.map(|b| b as f32),
+                c: sellmeier.c.map(|c| c as f32),
+            },
+            Self::Schott(SchottMaterial { schott }) => Material::Schott(schott.map(|a| a as f32)),
+            Self::Conrady(ConradyMaterial { conrady }) => Material::Conrady {
+                n0: conrady.n0 as f32,
+                a: conrady.a as f32,
+                b: conrady.b as f32,
+            },
+            Self::Cauchy(CauchyMaterial { cauchy }) => Material::Cauchy {
+                a: cauchy.a as f32,
+                b: cauchy.b as f32,
+                c: cauchy.c as f32,
+            },
+            Self::Herzberger(HerzbergerMaterial { herzberger }) => {
+                Material::Herzberger(herzberger.map(|a| a as f32))
+            }
+            Self::Perturbed(PerturbedMaterial {
+                material,
+                temperature,
+                pressure,
+            }) => Material::Perturbed {
+                material: Box::new(material.material(catalogs)?),
+                environment: Environment {
+                    temperature: temperature as f32,
+                    pressure: pressure as f32,
+                },
+            },
+        };
+
+        let n = material.refractive_index(super::FRAUNHOFER_D);
+        anyhow::ensure!(n.is_finite() && n > 0.0, "invalid d line index {n}");
+
+        Ok(material)
+    }
+}
+
+fn material_value(material: &Material) -> anyhow::Result<toml::Value> {
+    Ok(toml::Value::try_from(MaterialEntry::from(material))?)
+}
+
+fn material(value: toml::Value, catalogs: &[Catalog]) -> anyhow::Result<Material> {
+    let text = value.to_string();
+    let entry: MaterialEntry = value.try_into().map_err(|_| {
+        anyhow::anyhow!(
+            "unrecognized material {text}, expected an index, {{ glass }}, {{ nd, vd }} or the \
+             coefficients of a dispersion formula"
+        )
+    })?;
+
+    entry.material(catalogs)
+}
+
+impl From<&Field> for FieldEntry {
+    fn from(field: &Field) -> Self {
+        let v = field.vignetting;
+
+        Self {
+            x: short(field.point.x),
+            y: short(field.point.y),
+            vignetting: [v.decenter_x, v.decenter_y, v.compression_x, v.compression_y]
+                .iter()
+                .any(|value| *value != 0.0)
+                .then(|| VignettingEntry {
+                    decenter_x: short(v.decenter_x),
+                    decenter_y: short(v.decenter_y),
+                    compression_x: short(v.compression_x),
+                    compression_y: short(v.compression_y),
+                }),
+        }
+    }
+}
+
+impl From<&FieldEntry> for Field {
+    fn from(field: &FieldEntry) -> Self {
+        Self {
+            point: Point2::new(field.x as f32, field.y as f32),
+            vignetting: field
+                .vignetting
+                .as_ref()
+                .map_or_else(Vignetting::default, |v| Vignetting {
+                    decenter_x: v.decenter_x as f32,
+                    decenter_y: v.decenter_y as f32,
+                    compression_x: v.compression_x as f32,
+                    compression_y: v.compression_y as f32,
+                }),
+        }
+    }
+}
+
+impl SurfaceEntry {
+    fn from_surface(surface: &Surface) -> anyhow::Result<Self> {
+        Ok(Self {
+            radius: (surface.curvature != 0.0).then(|| radius(surface.curvature)),
+            thickness: short(surface.thickness),
+            material: material_value(&surface.material)?,
+            semi_diameter: short(surface.semi_diameter),
+            conic: short(surface.conic),
+            mount_expansion: short(surface.mount_expansion),
+            decenter: [short(surface.decenter.x), short(surface.decenter.y)],
+            tilt: [short(surface.tilt.x), short(surface.tilt.y)],
+        })
+    }
+
+    fn surface(self, catalogs: &[Catalog]) -> anyhow::Result<Surface> {
+        let curvature = match self.radius {
+            Some(0.0) => {
+                anyhow::bail!("zero radius, leave it out for a plane")
+            }
+            Some(radius) => 1.0 / radius as f32,
+            None => 0.0,
+        };
+        anyhow::ensure!(
+            self.thickness.is_finite(),
+            "thickness {} isn't finite",
+            self.thickness
+        );
+        anyhow::ensure!(
+            self.semi_diameter > 0.0,
+            "semi-diameter {} isn't positive",
+            self.semi_diameter
+        );
+        anyhow::ensure!(
+            1.0 - (1.0 + self.conic) * (curvature * curvature) as f64 * self.semi_diameter.powi(2)
+                >= 0.0,
+            "semi-diameter {} beyond the edge of the surface",
+            self.semi_diameter
+        );
+
+        Ok(Surface {
+            thickness: self.thickness as f32,
+            material: material(self.material, catalogs).context("material")?,
+            curvature,
+            conic: self.conic as f32,
+            semi_diameter: self.semi_diameter as f32,
+            mount_expansion: self.mount_expansion as f32,
+            decenter: Vector2::new(self.decenter[0] as f32, self.decenter[1] as f32),
+            tilt: Vector2::new(self.tilt[0] as f32, self.tilt[1] as f32),
+        })
+    }
+}
+
+impl From<&Constraint> for ConstraintEntry {
+    fn from(constraint: &Constraint) -> Self {
+        let bounds = constraint.bounds();
+        let (min, max) = (finite(bounds.min), finite(bounds.max));
+
+        match *constraint {
+            Constraint::CenterThickness { surface, .. } => {
+                Self::CenterThickness { surface, min, max }
+            }
+            Constraint::EdgeThickness { surface, .. } => Self::EdgeThickness { surface, min, max },
+            Constraint::TotalTrack { .. } => Self::TotalTrack { min, max },
+        }
+    }
+}
+
+impl ConstraintEntry {
+    fn constraint(&self, n_surfaces: usize) -> anyhow::Result<Constraint> {
+        Ok(match *self {
+            Self::CenterThickness { surface, min, max } => {
+                check_surface(surface, n_surfaces)?;
+                Constraint::CenterThickness {
+                    surface,
+                    bounds: bounds(min, max)?,
+                }
+            }
+            Self::EdgeThickness { surface, min, max } => {
+                check_surface(surface + 1, n_surfaces)?;
+                Constraint::EdgeThickness {
+                    surface,
+                    bounds: bounds(min, max)?,
+                }
+            }
+            Self::TotalTrack { min, max } => Constraint::TotalTrack {
+                bounds: bounds(min, max)?,
+            },
+        })
+    }
+}
+
+impl From<&Solve> for SolveEntry {
+    fn from(solve: &Solve) -> Self {
+        match *solve {
+            Solve::MarginalHeight { surface, height } => Self::MarginalHeight {
+                surface,
+                height: short(height),
+            },
+            Solve::MarginalAngle { surface, slope } => Self::MarginalAngle {
+                surface,
+                slope: short(slope),
+            },
+            Solve::ChiefHeight { surface, height } => Self::ChiefHeight {
+                surface,
+                height: short(height),
+            },
+            Solve::Pickup {
+                surface,
+                parameter,
+                from,
+                scale,
+                offset,
+            } => Self::Pickup {
+                surface,
+                parameter: parameter.into(),
+                from,
+                scale: short(scale),
+                offset: short(offset),
+            },
+        }
+    }
+}
+
+impl SolveEntry {
+    fn solve(&self, n_surfaces: usize) -> anyhow::Result<Solve> {
+        Ok(match *self {
+            Self::MarginalHeight { surface, height } => {
+                check_surface(surface + 1, n_surfaces)?;
+                Solve::MarginalHeight {
+                    surface,
+                    height: height as f32,
+                }
+            }
+            Self::MarginalAngle { surface, slope } => {
+                check_surface(surface, n_surfaces)?;
+                Solve::MarginalAngle {
+                    surface,
+                    slope: slope as f32,
+                }
+            }
+            Self::FNumber { surface, f_number } => {
+                check_surface(surface, n_surfaces)?;
+                anyhow::ensure!(f_number > 0.0, "F-number {f_number} isn't positive");
+                Solve::f_number(surface, f_number as f32)
+            }
+            Self::ChiefHeight { surface, height } => {
+                check_surface(surface + 1, n_surfaces)?;
+                Solve::ChiefHeight {
+                    surface,
+                    height: height as f32,
+                }
+            }
+            Self::Pickup {
+                surface,
+                parameter,
+                from,
+                scale,
+                offset,
+            } => {
+                check_surface(surface, n_surfaces)?;
+                check_surface(from, n_surfaces)?;
+                Solve::Pickup {
+                    surface,
+                    parameter: parameter.into(),
+                    from,
+                    scale: scale as f32,
+                    offset: offset as f32,
+                }
+            }
+        })
+    }
+}
+
+impl OverrideEntry {
+    fn from_override(value: &Override) -> anyhow::Result<Self> {
+        Ok(match value {
+            Override::Thickness { surface, value } => Self::Thickness {
+                surface: *surface,
+                value: short(*value),
+            },
+            Override::Curvature { surface, value } => Self::Curvature {
+                surface: *surface,
+                value: short(*value),
+            },
+            Override::SemiDiameter { surface, value } => Self::SemiDiameter {
+                surface: *surface,
+                value: short(*value),
+            },
+            Override::Material { surface, material } => Self::Material {
+                surface: *surface,
+                material: material_value(material)?,
+            },
+            Override::StopIndex(surface) => Self::Stop { surface: *surface },
+            Override::ObjectDistance(value) => Self::ObjectDistance {
+                value: short(*value),
+            },
+            Override::Fields(fields) => Self::Fields {
+                fields: fields.iter().map(FieldEntry::from).collect(),
+            },
+        })
+    }
+
+    fn value(self, n_surfaces: usize, catalogs: &[Catalog]) -> anyhow::Result<Override> {
+        Ok(match self {
+            Self::Thickness { surface, value } => {
+                check_surface(surface, n_surfaces)?;
+                Override::Thickness {
+                    surface,
+                    value: value as f32,
+                }
+            }
+            Self::Curvature { surface, value } => {
+                check_surface(surface, n_surfaces)?;
+                Override::Curvature {
+                    surface,
+                    value: value as f32,
+                }
+            }
+            Self::SemiDiameter { surface, value } => {
+                check_surface(surface, n_surfaces)?;
+                Override::SemiDiameter {
+                    surface,
+                    value: value as f32,
+                }
+            }
+            Self::Material { surface, material } => {
+                check_surface(surface, n_surfaces)?;
+                Override::Material {
+                    surface,
+                    material: self::material(material, catalogs)?,
+                }
+            }
+            Self::Stop { surface } => {
+                check_surface(surface as usize, n_surfaces)?;
+                Override::StopIndex(surface)
+            }
+            Self::ObjectDistance { value } => Override::ObjectDistance(value as f32),
+            Self::Fields { fields } => Override::Fields(fields.iter().map(Field::from).collect()),
+        })
+    }
+}
+
+fn check_surface(surface: usize, n_surfaces: usize) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        surface < n_surfaces,
+        "surface {surface} out of the {n_surfaces} surfaces"
+    );
+
+    Ok(())
+}
+
+impl System {
+    /// Reads a TOML prescription, looking the glasses it names up in `catalogs`.
+    pub fn load(path: impl AsRef<Path>, catalogs: &[Catalog]) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+
+        Self::from_toml(&source, catalogs).with_context(|| format!("in {}", path.display()))
+    }
+
+    /// Parses and validates a TOML prescription, errors naming the offending entry.
+    pub fn from_toml(source: &str, catalogs: &[Catalog]) -> anyhow::Result<Self> {
+        let prescription: Prescription = toml::from_str(source)?;
+
+        let surfaces = prescription
+            .surfaces
+            .into_iter()
+            .enumerate()
+            .map(|(i, table)| {
+                table
+                    .try_into::<SurfaceEntry>()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|surface| surface.surface(catalogs))
+                    .with_context(|| format!("surface {i}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let n_surfaces = surfaces.len();
+        anyhow::ensure!(n_surfaces > 0, "no surfaces, the last one being the image");
+        anyhow::ensure!(
+            (prescription.stop as usize) < n_surfaces,
+            "stop on surface {} out of the {n_surfaces} surfaces",
+            prescription.stop
+        );
+
+        let object = &prescription.object;
+        anyhow::ensure!(
+            object.distance > 0.0,
+            "object distance {} isn't positive",
+            object.distance
+        );
+        let object = Object {
+            distance: object.distance as f32,
+            semi_diameter: object.semi_diameter as f32,
+            material: material(prescription.object.material, catalogs)
+                .context("object material")?,
+        };
+
+        for (i, wavelength) in prescription.wavelengths.iter().enumerate() {
+            anyhow::ensure!(
+                wavelength.value > 0.0 && wavelength.weight >= 0.0,
+                "wavelength {i}: expected a positive value and weight, got {} and {}",
+                wavelength.value,
+                wavelength.weight
+            );
+        }
+        anyhow::ensure!(
+            prescription.wavelengths.is_empty()
+                || (prescription.primary as usize) < prescription.wavelengths.len(),
+            "primary wavelength {} out of the {} wavelengths",
+            prescription.primary,
+            prescription.wavelengths.len()
+        );
+
+        let mut system = System {
+            object,
+            stop_index: prescription.stop,
+            surfaces,
+            fields: prescription.fields.iter().map(Field::from).collect(),
+            wavelengths: prescription
+                .wavelengths
+                .iter()
+                .map(|w| Wavelength::new(w.value as f32, w.weight as f32))
+                .collect(),
+            primary: prescription.primary,
+            environment: prescription.environment.map_or_else(
+                Environment::default,
+                |environment| Environment {
+                    temperature: environment.temperature as f32,
+                    pressure: environment.pressure as f32,
+                },
+            ),
+            ..Default::default()
+        };
+
+        system.constraints = prescription
+            .constraints
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                entry
+                    .constraint(n_surfaces)
+                    .with_context(|| format!("constraint {i}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        system.solves = prescription
+            .solves
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                entry
+                    .solve(n_surfaces)
+                    .with_context(|| format!("solve {i}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        system.configurations = prescription
+            .configurations
+            .into_iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let overrides = entry
+                    .overrides
+                    .into_iter()
+                    .map(|value| value.value(n_surfaces, catalogs))
+                    .collect::<anyhow::Result<_>>()
+                    .with_context(|| format!("configuration {i} ({})", entry.name))?;
+
+                Ok(Configuration::new(entry.name, overrides))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        system.variables = prescription
+            .variables
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let variable = Variable {
+                    configuration: entry.configuration,
+                    ..Variable::new(entry.surface, entry.parameter.into())
+                };
+                bounds(entry.min, entry.max)
+                    .and_then(|bounds| {
+                        let variable = variable.bounded(bounds);
+                        variable.validate(&system).map(|_| variable)
+                    })
+                    .with_context(|| format!("variable {i}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(system)
+    }
+
+    /// Writes the prescription as TOML, see [`System::from_toml`].
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        let environment = self.environment;
+        let prescription = Prescription {
+            stop: self.stop_index,
+            primary: self.primary,
+            object: ObjectEntry {
+                distance: short(self.object.distance),
+                semi_diameter: short(self.object.semi_diameter),
+                material: material_value(&self.object.material)?,
+            },
+            environment: (environment != Environment::default()).then(|| EnvironmentEntry {
+                temperature: short(environment.temperature),
+                pressure: short(environment.pressure),
+            }),
+            wavelengths: self
+                .wavelengths
+                .iter()
+                .map(|w| WavelengthEntry {
+                    value: short(w.value),
+                    weight: short(w.weight),
+                })
+                .collect(),
+            fields: self.fields.iter().map(FieldEntry::from).collect(),
+            surfaces: self
+                .surfaces
+                .iter()
+                .map(|surface| Ok(toml::Table::try_from(SurfaceEntry::from_surface(surface)?)?))
+                .collect::<anyhow::Result<_>>()?,
+            variables: self
+                .variables
+                .iter()
+                .map(|variable| VariableEntry {
+                    surface: variable.surface,
+                    parameter: variable.parameter.into(),
+                    min: finite(variable.bounds.min),
+                    max: finite(variable.bounds.max),
+                    configuration: variable.configuration,
+                })
+                .collect(),
+            constraints: self.constraints.iter().map(ConstraintEntry::from).collect(),
+            solves: self.solves.iter().map(SolveEntry::from).collect(),
+            configurations: self
+                .configurations
+                .iter()
+                .map(|configuration| {
+                    Ok(ConfigurationEntry {
+                        name: configuration.name.clone(),
+                        overrides: configuration
+                            .overrides
+                            .iter()
+                            .map(OverrideEntry::from_override)
+                            .collect::<anyhow::Result<_>>()?,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+        };
+
+        Ok(toml::to_string_pretty(&prescription)?)
+    }
+
+    /// Writes the prescription as TOML to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, self.to_toml()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOUBLET: &str = "prescriptions/doublet.toml";
+
+    /// The doublet with one of every kind of entry.
+    fn doublet() -> System {
+        let mut system = System::load(DOUBLET, &[]).unwrap();
+        system.surfaces[1].material = Material::Sellmeier {
+            b: [1.04, 0.23, 1.01],
+            c: [0.006, 0.02, 103.56],
+        };
+        system.surfaces[2].material = Material::Perturbed {
+            material: Box::new(Material::Model { nd: 1.6, vd: 40.0 }),
+            environment: Environment {
+                temperature: 40.0,
+                pressure: 0.9,
+            },
+        };
+        system.constraints = vec![Constraint::EdgeThickness {
+            surface: 0,
+            bounds: Bounds {
+                min: 0.5,
+                max: f32::INFINITY,
+            },
+        }];
+        system.solves = vec![Solve::MarginalHeight {
+            surface: 2,
+            height: 0.0,
+        }];
+        system.configurations = vec![Configuration::new(
+            "near",
+            vec![
+                Override::ObjectDistance(50.0),
+                Override::Curvature {
+                    surface: 0,
+                    value: 0.14,
+                },
+            ],
+        )];
+        system.variables = vec![
+            Variable::new(1, Parameter::Curvature),
+            Variable::new(0, Parameter::Curvature).in_configuration(0),
+        ];
+
+        system
+    }
+
+    #[test]
+    fn round_trip() {
+        let system = doublet();
+        let source = system.to_toml().unwrap();
+        let read = System::from_toml(&source, &[]).unwrap();
+
+        assert_eq!(read.to_toml().unwrap(), source);
+        assert_eq!(read.surfaces.len(), 4);
+        for (read, surface) in read.surfaces.iter().zip(&system.surfaces) {
+            assert!((read.curvature - surface.curvature).abs() < 1e-6);
+            assert_eq!(read.thickness, surface.thickness);
+            assert_eq!(read.material, surface.material);
+        }
+        assert_eq!(read.constraints, system.constraints);
+        assert_eq!(read.solves, system.solves);
+        assert_eq!(read.configurations.len(), 1);
+        assert_eq!(read.configurations[0].name, "near");
+        assert!(matches!(
+            read.configurations[0].overrides[..],
+            [
+                Override::ObjectDistance(50.0),
+                Override::Curvature {
+                    surface: 0,
+                    value: 0.14
+                }
+            ]
+        ));
+        assert_eq!(read.variables, system.variables);
+    }
+
+    fn error(source: &str) -> String {
+        format!("{:#}", System::from_toml(source, &[]).unwrap_err())
+    }
+
+    #[test]
+    fn errors() {
+        let source = std::fs::read_to_string(DOUBLET).unwrap();
+
+        let negative = source.replacen(
+            "radius = -16.2225\nthickness = 10.55\nmaterial = 1.0\nsemi_diameter = 2.0",
+            "radius = -16.2225\nthickness = 10.55\nmaterial = 1.0\nsemi_diameter = -2.0",
+            1,
+        );
+        assert_eq!(
+            error(&negative),
+            "surface 2: semi-diameter -2 isn't positive"
+        );
+
+        let unknown = source.replacen("primary = 1", "primary = 1\naperture = 2.0", 1);
+        assert!(error(&unknown).contains("unknown field `aperture`"));
+
+        let typo = source.replacen(
+            "\n[surfaces.material]\nnd = 1.649\nvd = 33.8",
+            "material = { nd = 1.649, vd = 33.8, typo = 1 }",
+            1,
+        );
+        assert_eq!(
+            error(&typo),
+            "surface 1: material: unrecognized material { nd = 1.649, vd = 33.8, typo = 1 }, \
+             expected an index, { glass }, { nd, vd } or the coefficients of a dispersion formula"
+        );
+
+        let variable = format!("{source}\n[[variables]]\nsurface = 9\nparameter = \"curvature\"\n");
+        assert_eq!(error(&variable), "variable 0: no surface 9");
+    }
+}
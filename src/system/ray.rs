@@ -1,16 +1,38 @@
 use nalgebra::{Point3, Vector3};
 
-#[derive(Debug, encase::ShaderType)]
+use super::FRAUNHOFER_D;
+
+/// Real ray traced through the system.
+#[derive(Debug, Clone, Copy, encase::ShaderType)]
 pub struct Ray {
+    /// Point the ray starts from.
     pub origin: Vector3<f32>,
+    /// Unit direction of the ray.
     pub direction: Vector3<f32>,
+    /// Wavelength at which the media are evaluated, in micrometres.
+    pub wavelength: f32,
+    /// Relative contribution of the ray to polychromatic results.
+    pub weight: f32,
 }
 
 impl Ray {
+    /// Ray of unit weight at the d line.
     pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
         Self {
             origin: origin.coords,
             direction,
+            wavelength: FRAUNHOFER_D,
+            weight: 1.0,
         }
     }
+
+    /// The ray at `wavelength`, in micrometres.
+    pub fn with_wavelength(self, wavelength: f32) -> Self {
+        Self { wavelength, ..self }
+    }
+
+    /// The ray with `weight`.
+    pub fn with_weight(self, weight: f32) -> Self {
+        Self { weight, ..self }
+    }
 }
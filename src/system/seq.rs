@@ -0,0 +1,216 @@
+//! Code V sequence files.
+
+use std::{fmt::Write, path::Path};
+
+use super::{Environment, Material, System, Vignetting, prescription::radius};
+
+/// Whether `material` is written as a private glass, having no catalog name or glass code.
+fn is_private(material: &Material) -> bool {
+    !matches!(material, Material::Glass(_) | Material::Model { .. })
+        && *material != Material::Constant(1.0)
+}
+
+impl System {
+    /// Writes the system as a Code V sequence, see [`System::to_seq`].
+    pub fn save_seq(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, self.to_seq()?)?)
+    }
+
+    /// Code V sequence rebuilding the system with its solves evaluated.
+    ///
+    /// The aperture is the paraxial entrance pupil diameter and fields are object heights.
+    /// Model glasses are written as `nd:vd` glass codes, and materials other than catalog glasses
+    /// as private glasses sampled at the system wavelengths. Variables, constraints and
+    /// configurations aren't exported.
+    pub fn to_seq(&self) -> anyhow::Result<String> {
+        let system = self.solved();
+        system.ensure_centered()?;
+
+        let mut private = Vec::new();
+        for material in std::iter::once(&system.object.material)
+            .chain(system.surfaces.iter().map(|surface| &surface.material))
+        {
+            if is_private(material) && !private.contains(&material) {
+                private.push(material);
+            }
+        }
+        let glass = |material: &Material| match material {
+            Material::Glass(glass) => format!(" {}_{}", glass.name.replace('-', ""), glass.catalog),
+            Material::Model { nd, vd } => format!(" {nd}:{vd}"),
+            material => private
+                .iter()
+                .position(|other| *other == material)
+                .map_or_else(String::new, |i| format!(" PRIVATE{}", i + 1)),
+        };
+        let join = |values: &mut dyn Iterator<Item = String>| values.collect::<Vec<_>>().join(" ");
+        let nanometres = || {
+            system
+                .wavelengths
+                .iter()
+                .map(|wavelength| format!("{:.4}", wavelength.value as f64 * 1e3))
+        };
+
+        let mut seq = String::new();
+        writeln!(seq, "LEN NEW")?;
+        writeln!(seq, "DIM M")?;
+        if system.environment != Environment::default() {
+            writeln!(seq, "TEM {}", system.environment.temperature)?;
+            writeln!(seq, "PRE {}", system.environment.pressure)?;
+        }
+        writeln!(seq, "EPD {}", 2.0 * system.entrance_pupil().semi_diameter)?;
+        writeln!(seq, "WL {}", join(&mut nanometres()))?;
+        writeln!(
+            seq,
+            "WTW {}",
+            join(&mut system.wavelengths.iter().map(|w| w.weight.to_string()))
+        )?;
+        writeln!(seq, "REF {}", system.primary + 1)?;
+
+        let fields = &system.fields;
+        writeln!(
+            seq,
+            "XOB {}",
+            join(&mut fields.iter().map(|f| f.point.x.to_string()))
+        )?;
+        writeln!(
+            seq,
+            "YOB {}",
+            join(&mut fields.iter().map(|f| f.point.y.to_string()))
+        )?;
+        // Fractions of the pupil cut off at each edge
+        let mut edge = |record: &str, cut: fn(&Vignetting) -> f32| {
+            let values = fields
+                .iter()
+                .map(|f| cut(&f.vignetting))
+                .collect::<Vec<_>>();
+            if values.iter().any(|value| *value != 0.0) {
+                writeln!(
+                    seq,
+                    "{record} {}",
+                    join(&mut values.iter().map(f32::to_string))
+                )?;
+            }
+
+            anyhow::Ok(())
+        };
+        edge("VUX", |v| v.compression_x - v.decenter_x)?;
+        edge("VLX", |v| v.compression_x + v.decenter_x)?;
+        edge("VUY", |v| v.compression_y - v.decenter_y)?;
+        edge("VLY", |v| v.compression_y + v.decenter_y)?;
+
+        if !private.is_empty() {
+            writeln!(seq, "PRV")?;
+            writeln!(seq, "PWL {}", join(&mut nanometres()))?;
+            for (i, material) in private.iter().enumerate() {
+                let indices = system
+                    .wavelengths
+                    .iter()
+                    .map(|wavelength| material.refractive_index(wavelength.value).to_string());
+                writeln!(seq, "'PRIVATE{}' {}", i + 1, join(&mut indices.into_iter()))?;
+            }
+            writeln!(seq, "END")?;
+        }
+
+        writeln!(
+            seq,
+            "SO 0 {}{}",
+            system.object.distance,
+            glass(&system.object.material)
+        )?;
+        let last = system.surfaces.len() - 1;
+        for (i, surface) in system.surfaces.iter().enumerate() {
+            let command = if i == last { "SI" } else { "S" };
+            let radius = if surface.curvature == 0.0 {
+                0.0
+            } else {
+                radius(surface.curvature)
+            };
+
+            writeln!(
+                seq,
+                "{command} {radius} {}{}",
+                surface.thickness,
+                glass(&surface.material)
+            )?;
+            writeln!(seq, "  CIR {}", surface.semi_diameter)?;
+            if surface.conic != 0.0 {
+                writeln!(seq, "  K {}", surface.conic)?;
+            }
+            if i == system.stop_index as usize {
+                writeln!(seq, "  STO")?;
+            }
+        }
+        writeln!(seq, "GO")?;
+
+        Ok(seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doublet() -> System {
+        let mut system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+        system.stop_index = 0;
+
+        system
+    }
+
+    #[test]
+    fn surfaces() {
+        let seq = doublet().to_seq().unwrap();
+        let lines = seq.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.first(), Some(&"LEN NEW"));
+        assert_eq!(lines.last(), Some(&"GO"));
+        assert!(lines.contains(&"WL 486.1327 587.5618 656.2725"));
+        assert!(lines.contains(&"REF 2"));
+        assert!(lines.contains(&"YOB 0 0.7 1"));
+        assert!(lines.contains(&"SO 0 2"));
+
+        let surfaces = lines
+            .iter()
+            .filter(|line| line.starts_with("S ") || line.starts_with("SI "))
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            surfaces,
+            [
+                "S 7.3895 1.05 1.517:64.2",
+                "S -5.1784 0.4 1.649:33.8",
+                "S -16.2225 10.55",
+                "SI 0 0",
+            ]
+        );
+
+        let stop = lines.iter().position(|line| line.trim() == "STO").unwrap();
+        assert_eq!(lines[stop - 2], surfaces[0]);
+    }
+
+    #[test]
+    fn private_glasses() {
+        let mut system = doublet();
+        let sellmeier = Material::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_5],
+            c: [0.006_000_699, 0.020_017_914, 103.560_65],
+        };
+        system.surfaces[0].material = sellmeier.clone();
+        system.surfaces[1].material = sellmeier.clone();
+
+        let seq = system.to_seq().unwrap();
+        let indices = system
+            .wavelengths
+            .iter()
+            .map(|wavelength| sellmeier.refractive_index(wavelength.value).to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        assert!(seq.contains(&format!(
+            "PRV\nPWL 486.1327 587.5618 656.2725\n'PRIVATE1' {indices}\nEND\n"
+        )));
+        assert!(seq.contains("S 7.3895 1.05 PRIVATE1\n"));
+        assert!(seq.contains("S -5.1784 0.4 PRIVATE1\n"));
+        assert!(!seq.contains("PRIVATE2"));
+    }
+}
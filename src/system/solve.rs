@@ -0,0 +1,157 @@
+use std::borrow::Cow;
+
+use super::{ParaxialRay, System};
+use crate::optimization::{Parameter, Variable};
+
+/// Passes over the solves, for the ones changing the paraxial rays of the solves before them.
+const PASSES: usize = 16;
+
+/// Surface parameter computed from the rest of the system rather than set directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Solve {
+    /// Thickness after `surface` bringing the paraxial marginal ray to `height` on the next
+    /// surface, zero for the paraxial focus.
+    MarginalHeight {
+        /// Surface whose following thickness is solved.
+        surface: usize,
+        /// Height of the ray on the next surface.
+        height: f32,
+    },
+    /// Curvature of `surface` giving the paraxial marginal ray `slope` after refraction.
+    MarginalAngle {
+        /// Surface whose curvature is solved.
+        surface: usize,
+        /// Slope of the ray after the surface.
+        slope: f32,
+    },
+    /// Thickness after `surface` bringing the paraxial chief ray to `height` on the next surface.
+    ChiefHeight {
+        /// Surface whose following thickness is solved.
+        surface: usize,
+        /// Height of the ray on the next surface.
+        height: f32,
+    },
+    /// `parameter` of `surface` set to `scale` times the one of `from`, plus `offset`.
+    Pickup {
+        /// Surface whose parameter is solved.
+        surface: usize,
+        /// Parameter picked up.
+        parameter: Parameter,
+        /// Surface the parameter is picked up from.
+        from: usize,
+        /// Factor applied to the picked up value.
+        scale: f32,
+        /// Value added after scaling.
+        offset: f32,
+    },
+}
+
+impl Solve {
+    /// Marginal angle solve on the curvature of `surface` giving the image space working
+    /// F-number.
+    pub fn f_number(surface: usize, f_number: f32) -> Self {
+        Self::MarginalAngle {
+            surface,
+            slope: -0.5 / f_number,
+        }
+    }
+
+    /// Value of the solved parameter, `None` when it can't be satisfied.
+    fn value(&self, system: &System) -> Option<(Variable, f32)> {
+        let wavelength = system.primary_wavelength();
+
+        match *self {
+            Self::MarginalHeight { surface, height } | Self::ChiefHeight { surface, height } => {
+                let ray = match self {
+                    Self::MarginalHeight { .. } => system.paraxial_marginal_ray(),
+                    _ => system.paraxial_chief_ray(),
+                };
+                let traced = system.paraxial_trace(ray, wavelength);
+                let ray = traced.get(surface)?;
+
+                (ray.slope != 0.0).then(|| {
+                    (
+                        Variable::new(surface, Parameter::Thickness),
+                        (height - ray.height) / ray.slope,
+                    )
+                })
+            }
+            Self::MarginalAngle { surface, slope } => {
+                let marginal = system.paraxial_marginal_ray();
+                let traced = system.paraxial_trace(marginal, wavelength);
+                let ParaxialRay { height, .. } = *traced.get(surface)?;
+
+                let (n, incoming) = match surface.checked_sub(1) {
+                    Some(i) => (
+                        system.surfaces[i].refractive_index_at(wavelength),
+                        traced[i].slope,
+                    ),
+                    None => (
+                        system.object.refractive_index_at(wavelength),
+                        marginal.slope,
+                    ),
+                };
+                let n1 = system.surfaces[surface].refractive_index_at(wavelength);
+
+                // From n' u' = n u - h c (n' - n)
+                let curvature = (n * incoming - n1 * slope) / (height * (n1 - n));
+
+                curvature
+                    .is_finite()
+                    .then(|| (Variable::new(surface, Parameter::Curvature), curvature))
+            }
+            Self::Pickup {
+                surface,
+                parameter,
+                from,
+                scale,
+                offset,
+            } => {
+                let value = Variable::new(from, parameter).value(system).ok()?;
+
+                Some((Variable::new(surface, parameter), scale * value + offset))
+            }
+        }
+    }
+}
+
+impl System {
+    /// Evaluates the solves in order, passing over them again while they change the system.
+    ///
+    /// Solves that can't be satisfied, referring to missing surfaces or with a ray parallel to
+    /// the axis, leave their parameter unchanged.
+    pub fn solve(&mut self) {
+        for _ in 0..PASSES {
+            let mut change = 0.0f32;
+
+            for solve in self.solves.clone() {
+                let Some((variable, value)) = solve.value(self) else {
+                    continue;
+                };
+                let Ok(previous) = variable.value(self) else {
+                    continue;
+                };
+
+                if variable.set(self, value).is_ok() {
+                    change = change.max((value - previous).abs() / previous.abs().max(1.0));
+                }
+            }
+
+            if change < 1e-7 {
+                break;
+            }
+        }
+    }
+
+    /// The system with its solves evaluated, borrowed when there are none.
+    pub fn solved(&self) -> Cow<'_, System> {
+        if self.solves.is_empty() {
+            Cow::Borrowed(self)
+        } else {
+            let mut system = self.clone();
+            system.solve();
+
+            Cow::Owned(system)
+        }
+    }
+}
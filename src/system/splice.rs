@@ -0,0 +1,237 @@
+use std::ops::Range;
+
+use super::{Override, Solve, Surface, System};
+use crate::optimization::{Constraint, Parameter, Variable};
+
+impl System {
+    /// Replaces the surfaces in `range` with `surfaces`, moving the references to the following
+    /// ones.
+    ///
+    /// References to the thickness or medium after the last replaced surface, the gap following
+    /// the element, move to the last new surface. Other variables, constraints, solves and
+    /// configuration overrides of the replaced surfaces are dropped, and a stop among them moves
+    /// to the first new surface.
+    pub fn replace_surfaces(
+        &mut self,
+        range: Range<usize>,
+        surfaces: Vec<Surface>,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            range.start <= range.end && range.end <= self.surfaces.len(),
+            "can't replace surfaces {range:?} of {}",
+            self.surfaces.len()
+        );
+        anyhow::ensure!(
+            !surfaces.is_empty() || range.is_empty(),
+            "can't remove surfaces, only replace them"
+        );
+
+        let (start, end, count) = (range.start, range.end, surfaces.len());
+        // New index of `surface`, `gap` telling whether the reference is to the gap following it
+        let map = |surface: usize, gap: bool| {
+            if surface < start {
+                Some(surface)
+            } else if surface >= end {
+                Some(surface - end + start + count)
+            } else if gap && surface + 1 == end {
+                Some(start + count - 1)
+            } else {
+                None
+            }
+        };
+        let follows = |parameter: Parameter| {
+            matches!(
+                parameter,
+                Parameter::Thickness | Parameter::Index | Parameter::Abbe
+            )
+        };
+
+        self.surfaces.splice(range, surfaces);
+        self.stop_index = map(self.stop_index as usize, false).unwrap_or(start) as u32;
+
+        self.variables = self
+            .variables
+            .iter()
+            .filter_map(|variable| {
+                let surface = map(variable.surface, follows(variable.parameter))?;
+
+                Some(Variable {
+                    surface,
+                    ..*variable
+                })
+            })
+            .collect();
+
+        self.constraints = self
+            .constraints
+            .iter()
+            .filter_map(|constraint| {
+                Some(match *constraint {
+                    Constraint::CenterThickness { surface, bounds } => {
+                        Constraint::CenterThickness {
+                            surface: map(surface, true)?,
+                            bounds,
+                        }
+                    }
+                    Constraint::EdgeThickness { surface, bounds } => Constraint::EdgeThickness {
+                        surface: map(surface, true)?,
+                        bounds,
+                    },
+                    Constraint::TotalTrack { bounds } => Constraint::TotalTrack { bounds },
+                })
+            })
+            .collect();
+
+        self.solves = self
+            .solves
+            .iter()
+            .filter_map(|solve| {
+                Some(match *solve {
+                    Solve::MarginalHeight { surface, height } => Solve::MarginalHeight {
+                        surface: map(surface, true)?,
+                        height,
+                    },
+                    Solve::MarginalAngle { surface, slope } => Solve::MarginalAngle {
+                        surface: map(surface, false)?,
+                        slope,
+                    },
+                    Solve::ChiefHeight { surface, height } => Solve::ChiefHeight {
+                        surface: map(surface, true)?,
+                        height,
+                    },
+                    Solve::Pickup {
+                        surface,
+                        parameter,
+                        from,
+                        scale,
+                        offset,
+                    } => Solve::Pickup {
+                        surface: map(surface, follows(parameter))?,
+                        parameter,
+                        from: map(from, follows(parameter))?,
+                        scale,
+                        offset,
+                    },
+                })
+            })
+            .collect();
+
+        for configuration in &mut self.configurations {
+            configuration.overrides = configuration
+                .overrides
+                .iter()
+                .filter_map(|value| {
+                    Some(match value {
+                        Override::Thickness { surface, value } => Override::Thickness {
+                            surface: map(*surface, true)?,
+                            value: *value,
+                        },
+                        Override::Curvature { surface, value } => Override::Curvature {
+                            surface: map(*surface, false)?,
+                            value: *value,
+                        },
+                        Override::SemiDiameter { surface, value } => Override::SemiDiameter {
+                            surface: map(*surface, false)?,
+                            value: *value,
+                        },
+                        Override::Material { surface, material } => Override::Material {
+                            surface: map(*surface, true)?,
+                            material: material.clone(),
+                        },
+                        Override::StopIndex(stop) => {
+                            Override::StopIndex(map(*stop as usize, false).unwrap_or(start) as u32)
+                        }
+                        value => value.clone(),
+                    })
+                })
+                .collect();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        optimization::Bounds,
+        system::{Configuration, Material},
+    };
+
+    #[test]
+    fn remaps_references() {
+        let mut system = System::load("prescriptions/doublet.toml", &[]).unwrap();
+        system.variables = vec![
+            Variable::new(0, Parameter::Curvature),
+            Variable::new(1, Parameter::Curvature),
+            Variable::new(2, Parameter::Thickness),
+        ];
+        system.solves = vec![Solve::MarginalHeight {
+            surface: 2,
+            height: 0.0,
+        }];
+        system.constraints = vec![Constraint::CenterThickness {
+            surface: 0,
+            bounds: Bounds::default(),
+        }];
+        system.configurations = vec![Configuration::new(
+            "far",
+            vec![
+                Override::Curvature {
+                    surface: 1,
+                    value: 0.0,
+                },
+                Override::Thickness {
+                    surface: 2,
+                    value: 9.0,
+                },
+                Override::StopIndex(3),
+            ],
+        )];
+
+        // Replace the cemented surface by an air-spaced pair
+        let surfaces = vec![
+            Surface {
+                material: Material::Constant(1.0),
+                ..system.surfaces[1].clone()
+            },
+            Surface {
+                thickness: 0.4,
+                material: Material::Constant(1.649),
+                ..Surface::default()
+            },
+        ];
+        system.replace_surfaces(1..2, surfaces).unwrap();
+
+        assert_eq!(system.surfaces.len(), 5);
+        assert_eq!(system.stop_index, 4);
+        let variables = system
+            .variables
+            .iter()
+            .map(|variable| (variable.surface, variable.parameter))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            variables,
+            vec![(0, Parameter::Curvature), (3, Parameter::Thickness)]
+        );
+        assert!(matches!(
+            system.solves[..],
+            [Solve::MarginalHeight { surface: 3, .. }]
+        ));
+        assert!(matches!(
+            system.constraints[..],
+            [Constraint::CenterThickness { surface: 0, .. }]
+        ));
+        assert!(matches!(
+            system.configurations[0].overrides[..],
+            [
+                Override::Thickness {
+                    surface: 3,
+                    value: 9.0
+                },
+                Override::StopIndex(4)
+            ]
+        ));
+    }
+}
@@ -1,26 +1,54 @@
-use nalgebra::Point2;
+use nalgebra::{Matrix3, Point2, Rotation3, Vector2, Vector3};
 
-#[derive(Debug, Clone, encase::ShaderType)]
+use super::Material;
+
+/// Refracting surface along with the medium following it.
+#[derive(Debug, Clone)]
 pub struct Surface {
+    /// Distance along the axis to the next surface.
     pub thickness: f32,
-    pub refractive_index: f32,
+    /// Medium following the surface.
+    pub material: Material,
+    /// Inverse of the radius of curvature, zero for a plane.
     pub curvature: f32,
+    /// Conic constant, zero for a sphere and -1 for a paraboloid.
+    pub conic: f32,
+    /// Radius of the clear aperture, rays beyond it being vignetted.
     pub semi_diameter: f32,
+    /// Thermal expansion coefficient of the spacer following the surface, in 1/K, used when the
+    /// medium isn't a glass.
+    pub mount_expansion: f32,
+    /// Offset of the vertex from the axis, the following surfaces staying centered.
+    pub decenter: Vector2<f32>,
+    /// Rotations about the x and y axes through the vertex, in radians.
+    pub tilt: Vector2<f32>,
 }
 
 impl Surface {
     /// Returns the z-coordinate of the surface at the given point.
     pub fn z(&self, point: Point2<f32>) -> f32 {
-        let radius = self.curvature.recip();
+        let r2 = point.coords.norm_squared();
+
+        let c = self.curvature;
 
-        radius - (radius * radius - point.x * point.x - point.y * point.y).sqrt() * radius.signum()
+        c * r2 / (1.0 + (1.0 - (1.0 + self.conic) * c * c * r2).sqrt())
     }
 
+    /// Orientation of the surface, mapping its local coordinates to the system ones.
+    pub fn rotation(&self) -> Matrix3<f32> {
+        (Rotation3::from_axis_angle(&Vector3::x_axis(), self.tilt.x)
+            * Rotation3::from_axis_angle(&Vector3::y_axis(), self.tilt.y))
+        .into_inner()
+    }
+
+    /// Sag of the surface at the edge of its clear aperture.
     pub fn sagitta(&self) -> f32 {
-        let radius = self.curvature.recip();
+        self.z(Point2::new(0.0, self.semi_diameter))
+    }
 
-        radius.signum()
-            * (radius.abs() - (radius * radius - self.semi_diameter * self.semi_diameter).sqrt())
+    /// Refractive index of the medium following the surface at `wavelength`.
+    pub fn refractive_index_at(&self, wavelength: f32) -> f32 {
+        self.material.refractive_index(wavelength)
     }
 }
 
@@ -28,9 +56,13 @@ impl Default for Surface {
     fn default() -> Self {
         Self {
             thickness: 0.0,
-            refractive_index: 1.0,
+            material: Material::default(),
             curvature: 0.0,
+            conic: 0.0,
             semi_diameter: 0.0,
+            mount_expansion: 0.0,
+            decenter: Vector2::zeros(),
+            tilt: Vector2::zeros(),
         }
     }
 }
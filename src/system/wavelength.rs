@@ -0,0 +1,60 @@
+//! Wavelengths are given in micrometres throughout the crate.
+
+/// Hydrogen F line.
+pub const FRAUNHOFER_F: f32 = 0.486_132_7;
+/// Helium d line, used as the reference wavelength.
+pub const FRAUNHOFER_D: f32 = 0.587_561_8;
+/// Hydrogen C line.
+pub const FRAUNHOFER_C: f32 = 0.656_272_5;
+
+use super::{Ray, System};
+
+/// Wavelength of the system spectrum along with its relative weight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wavelength {
+    /// Wavelength, in micrometres.
+    pub value: f32,
+    /// Relative weight in polychromatic results.
+    pub weight: f32,
+}
+
+impl Wavelength {
+    /// Wavelength of `value` micrometres and relative `weight`.
+    pub fn new(value: f32, weight: f32) -> Self {
+        Self { value, weight }
+    }
+
+    /// F, d and C lines with equal weights, d being the second one.
+    pub fn visible() -> Vec<Self> {
+        [FRAUNHOFER_F, FRAUNHOFER_D, FRAUNHOFER_C]
+            .into_iter()
+            .map(|value| Self::new(value, 1.0))
+            .collect()
+    }
+}
+
+impl System {
+    /// Primary wavelength of the system, the d line if the spectrum is empty.
+    pub fn primary_wavelength(&self) -> f32 {
+        self.wavelengths
+            .get(self.primary as usize)
+            .map_or(FRAUNHOFER_D, |wavelength| wavelength.value)
+    }
+
+    /// Copies every ray once per system wavelength, weights being normalized to sum to one.
+    ///
+    /// Rays are grouped by wavelength, in the order of `wavelengths`.
+    pub fn polychromatic_rays(&self, rays: &[Ray]) -> Vec<Ray> {
+        let total: f32 = self.wavelengths.iter().map(|w| w.weight).sum();
+
+        self.wavelengths
+            .iter()
+            .flat_map(|wavelength| {
+                rays.iter().map(move |ray| {
+                    ray.with_wavelength(wavelength.value)
+                        .with_weight(ray.weight * wavelength.weight / total)
+                })
+            })
+            .collect()
+    }
+}
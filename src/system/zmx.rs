@@ -0,0 +1,643 @@
+//! Zemax OpticStudio sequential ZMX files.
+
+use std::{fmt::Write, path::Path};
+
+use anyhow::Context;
+use nalgebra::{Point2, Vector2};
+
+use super::{Environment, Field, Material, Object, Surface, System, Vignetting, Wavelength};
+use crate::glass::{self, Catalog};
+
+/// Distance of the object plane standing for an object at infinity, in millimetres.
+pub const INFINITE_DISTANCE: f32 = 1e4;
+
+/// Entries of the field and vignetting lists, padded with zeros.
+const FIELD_SLOTS: usize = 12;
+
+/// System aperture, set through the stop semi-diameter once the lens is read.
+#[derive(Debug, Clone, Copy)]
+enum Aperture {
+    EntrancePupilDiameter(f32),
+    /// Paraxial image space F-number for an object at infinity.
+    FNumber(f32),
+    /// Numerical aperture of the marginal ray in object space.
+    ObjectSpaceNa(f32),
+    /// Set by the stop semi-diameter.
+    Float,
+}
+
+/// SURF block, its glass still to be looked up.
+#[derive(Debug, Default)]
+struct Block {
+    number: usize,
+    kind: Option<String>,
+    curvature: f32,
+    thickness: f32,
+    glass: Vec<String>,
+    semi_diameter: f32,
+    conic: f32,
+    parameters: Vec<(u32, f32)>,
+    stop: bool,
+}
+
+fn number(fields: &[&str], index: usize) -> Option<f32> {
+    fields.get(index)?.parse().ok()
+}
+
+fn numbers(fields: &[&str]) -> anyhow::Result<Vec<f32>> {
+    fields
+        .iter()
+        .map(|field| {
+            field
+                .parse()
+                .with_context(|| format!("{field:?} isn't a number"))
+        })
+        .collect()
+}
+
+impl Block {
+    fn material(&self, catalogs: &[Catalog], catalog_names: &[String]) -> anyhow::Result<Material> {
+        let Some(name) = self.glass.first() else {
+            return Ok(Material::Constant(1.0));
+        };
+        anyhow::ensure!(
+            !name.eq_ignore_ascii_case("MIRROR"),
+            "mirrors aren't supported"
+        );
+
+        if let Some(glass) = glass::find(catalogs, name) {
+            return Ok(Material::Glass(glass.clone()));
+        }
+
+        // Model glass, or fallback for a catalog glass missing from `catalogs`
+        let fields = self.glass.iter().map(String::as_str).collect::<Vec<_>>();
+        match (number(&fields, 3), number(&fields, 4)) {
+            (Some(nd), Some(vd)) if nd > 0.0 && vd > 0.0 => Ok(Material::Model { nd, vd }),
+            (Some(nd), _) if nd > 0.0 => Ok(Material::Constant(nd)),
+            _ => anyhow::bail!(
+                "glass {name} not found in the loaded catalogs, the file using {}",
+                catalog_names.join(", ")
+            ),
+        }
+    }
+
+    fn surface(&self, catalogs: &[Catalog], catalog_names: &[String]) -> anyhow::Result<Surface> {
+        match self.kind.as_deref().unwrap_or("STANDARD") {
+            "STANDARD" => {}
+            "EVENASPH" => anyhow::ensure!(
+                self.parameters.iter().all(|(_, value)| *value == 0.0),
+                "even asphere coefficients aren't supported"
+            ),
+            kind => anyhow::bail!("unsupported surface type {kind}"),
+        }
+
+        Ok(Surface {
+            thickness: self.thickness,
+            material: self.material(catalogs, catalog_names)?,
+            curvature: self.curvature,
+            conic: self.conic,
+            semi_diameter: self.semi_diameter,
+            ..Default::default()
+        })
+    }
+}
+
+impl System {
+    /// Reads a sequential ZMX file, in UTF-8 or UTF-16, looking its glasses up in `catalogs`.
+    pub fn load_zmx(path: impl AsRef<Path>, catalogs: &[Catalog]) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+        Self::from_zmx(&glass::agf::decode(&bytes)?, catalogs)
+            .with_context(|| format!("in {}", path.display()))
+    }
+
+    /// Parses a sequential ZMX file.
+    ///
+    /// Glasses missing from `catalogs` fall back to the model glass of their GLAS record. Fields
+    /// must be angles or object heights, and objects at infinity are moved to
+    /// [`INFINITE_DISTANCE`].
+    pub fn from_zmx(source: &str, catalogs: &[Catalog]) -> anyhow::Result<Self> {
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut scale = 1.0;
+        let mut aperture = Aperture::Float;
+        let mut environment = Environment::default();
+        let mut catalog_names = Vec::new();
+        let mut field_type = 0;
+        let (mut n_fields, mut n_wavelengths) = (None, None);
+        let (mut x, mut y) = (Vec::new(), Vec::new());
+        let mut vignetting: [Vec<f32>; 4] = Default::default();
+        let mut wavelengths = Vec::new();
+        let mut weights = Vec::new();
+        let mut primary = 1;
+
+        for (line, text) in source.lines().enumerate() {
+            let mut fields = text.split_whitespace();
+            let Some(record) = fields.next() else {
+                continue;
+            };
+            let fields = fields.collect::<Vec<_>>();
+            let invalid = || format!("line {}: invalid {record} record", line + 1);
+            let value = |index| number(&fields, index).with_context(invalid);
+
+            match record {
+                "MODE" => anyhow::ensure!(
+                    fields.first() == Some(&"SEQ"),
+                    "line {}: only sequential files are supported",
+                    line + 1
+                ),
+                "UNIT" => {
+                    scale = match fields.first().copied() {
+                        Some("MM") => 1.0,
+                        Some("CM") => 10.0,
+                        Some("IN") => 25.4,
+                        Some("METER") => 1000.0,
+                        unit => anyhow::bail!("line {}: unsupported unit {unit:?}", line + 1),
+                    }
+                }
+                "ENPD" => aperture = Aperture::EntrancePupilDiameter(value(0)?),
+                "FNUM" => aperture = Aperture::FNumber(value(0)?),
+                "OBNA" => aperture = Aperture::ObjectSpaceNa(value(0)?),
+                "FLOA" => aperture = Aperture::Float,
+                "ENVD" => {
+                    environment = Environment {
+                        temperature: value(0)?,
+                        pressure: value(1)?,
+                    }
+                }
+                "GCAT" => catalog_names.extend(fields.iter().map(|name| name.to_string())),
+                "FTYP" => {
+                    field_type = value(0)? as u32;
+                    n_fields = number(&fields, 2).map(|n| n as usize);
+                    n_wavelengths = number(&fields, 3).map(|n| n as usize);
+                }
+                "XFLN" | "XFLD" => x = numbers(&fields).with_context(invalid)?,
+                "YFLN" | "YFLD" => y = numbers(&fields).with_context(invalid)?,
+                "VDXN" => vignetting[0] = numbers(&fields).with_context(invalid)?,
+                "VDYN" => vignetting[1] = numbers(&fields).with_context(invalid)?,
+                "VCXN" => vignetting[2] = numbers(&fields).with_context(invalid)?,
+                "VCYN" => vignetting[3] = numbers(&fields).with_context(invalid)?,
+                "WAVL" => wavelengths = numbers(&fields).with_context(invalid)?,
+                "WWGT" => weights = numbers(&fields).with_context(invalid)?,
+                "WAVM" => {
+                    let index = value(0)? as usize;
+                    if index >= 1 {
+                        wavelengths.resize(wavelengths.len().max(index), 0.0);
+                        weights.resize(wavelengths.len(), 1.0);
+                        wavelengths[index - 1] = value(1)?;
+                        weights[index - 1] = number(&fields, 2).unwrap_or(1.0);
+                    }
+                }
+                "PWAV" => primary = value(0)? as u32,
+                "SURF" => blocks.push(Block {
+                    number: value(0)? as usize,
+                    ..Default::default()
+                }),
+                _ => {
+                    let Some(block) = blocks.last_mut() else {
+                        continue;
+                    };
+
+                    match record {
+                        "TYPE" => block.kind = fields.first().map(|kind| kind.to_string()),
+                        "CURV" => block.curvature = value(0)?,
+                        "DISZ" => block.thickness = value(0)?,
+                        "DIAM" => block.semi_diameter = value(0)?,
+                        "CONI" => block.conic = value(0)?,
+                        "PARM" => block.parameters.push((value(0)? as u32, value(1)?)),
+                        "GLAS" => block.glass = fields.iter().map(|f| f.to_string()).collect(),
+                        "STOP" => block.stop = true,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        anyhow::ensure!(
+            blocks.len() >= 2,
+            "expected an object and an image surface, found {} SURF blocks",
+            blocks.len()
+        );
+        for block in &mut blocks {
+            block.curvature /= scale;
+            block.thickness *= scale;
+            block.semi_diameter *= scale;
+        }
+
+        let object = &blocks[0];
+        let distance = if object.thickness.is_finite() {
+            object.thickness
+        } else {
+            INFINITE_DISTANCE
+        };
+        let object = Object {
+            distance,
+            semi_diameter: object.semi_diameter,
+            material: object
+                .material(catalogs, &catalog_names)
+                .with_context(|| format!("SURF {}", object.number))?,
+        };
+        let surfaces = blocks[1..]
+            .iter()
+            .map(|block| {
+                block
+                    .surface(catalogs, &catalog_names)
+                    .with_context(|| format!("SURF {}", block.number))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let stop_index = blocks[1..].iter().position(|block| block.stop).unwrap_or(0) as u32;
+
+        let n_wavelengths = n_wavelengths.unwrap_or(wavelengths.len());
+        let wavelengths = wavelengths
+            .iter()
+            .zip(weights.iter().chain(std::iter::repeat(&1.0)))
+            .take(n_wavelengths)
+            .map(|(value, weight)| Wavelength::new(*value, *weight))
+            .collect::<Vec<_>>();
+        anyhow::ensure!(!wavelengths.is_empty(), "no wavelengths");
+        anyhow::ensure!(
+            (1..=wavelengths.len() as u32).contains(&primary),
+            "primary wavelength {primary} out of the {} wavelengths",
+            wavelengths.len()
+        );
+
+        let mut system = System {
+            object,
+            stop_index,
+            surfaces,
+            wavelengths,
+            primary: primary - 1,
+            environment,
+            ..Default::default()
+        };
+
+        let stop = &mut system.surfaces[stop_index as usize];
+        if stop.semi_diameter == 0.0 {
+            stop.semi_diameter = 1.0;
+        }
+        let wavelength = system.primary_wavelength();
+        let ratio = match aperture {
+            Aperture::EntrancePupilDiameter(diameter) => {
+                0.5 * diameter * scale / system.entrance_pupil().semi_diameter
+            }
+            Aperture::FNumber(f_number) => {
+                0.5 * system.effective_focal_length(wavelength)
+                    / f_number
+                    / system.entrance_pupil().semi_diameter
+            }
+            Aperture::ObjectSpaceNa(na) => {
+                let n = system.object.refractive_index_at(wavelength);
+
+                (na / n).asin().tan() / system.paraxial_marginal_ray().slope
+            }
+            Aperture::Float => 1.0,
+        };
+        anyhow::ensure!(
+            ratio.is_finite() && ratio > 0.0,
+            "can't size the stop for {aperture:?}"
+        );
+        system.surfaces[stop_index as usize].semi_diameter *= ratio;
+
+        // Angles are those of the chief rays, through the center of the entrance pupil
+        let n_fields = n_fields.unwrap_or(x.len().max(y.len()));
+        let depth = system.object.distance + system.entrance_pupil().z;
+        let height = |values: &[f32], i: usize| {
+            let value = values.get(i).copied().unwrap_or(0.0);
+
+            match field_type {
+                0 => Ok(depth * value.to_radians().tan()),
+                1 => Ok(value * scale),
+                _ => anyhow::bail!(
+                    "unsupported field type {field_type}, expected angles (0) or object heights (1)"
+                ),
+            }
+        };
+        system.fields = (0..n_fields)
+            .map(|i| {
+                let factor = |values: &Vec<f32>| values.get(i).copied().unwrap_or(0.0);
+
+                Ok(Field {
+                    point: Point2::new(height(&x, i)?, height(&y, i)?),
+                    vignetting: Vignetting {
+                        decenter_x: factor(&vignetting[0]),
+                        decenter_y: factor(&vignetting[1]),
+                        compression_x: factor(&vignetting[2]),
+                        compression_y: factor(&vignetting[3]),
+                    },
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        if let Some(extent) = system
+            .fields
+            .iter()
+            .map(|field| field.point.coords.norm())
+            .max_by(f32::total_cmp)
+            .filter(|extent| *extent > 0.0)
+        {
+            system.object.semi_diameter = extent;
+        }
+
+        Ok(system)
+    }
+
+    /// Writes the system as a ZMX file, see [`System::to_zmx`].
+    pub fn save_zmx(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, self.to_zmx()?)?)
+    }
+
+    /// Sequential ZMX file of the system with its solves evaluated.
+    ///
+    /// Fields are written as object heights, the stop semi-diameter setting the aperture.
+    /// Materials other than catalog and model glasses become model glasses of the same d line
+    /// index and Abbe number. Variables, constraints and configurations aren't exported.
+    pub fn to_zmx(&self) -> anyhow::Result<String> {
+        let system = self.solved();
+        system.ensure_centered()?;
+
+        let mut catalogs = Vec::new();
+        for surface in &system.surfaces {
+            if let Material::Glass(glass) = &surface.material
+                && !catalogs.contains(&glass.catalog)
+            {
+                catalogs.push(glass.catalog.clone());
+            }
+        }
+
+        let mut zmx = String::new();
+        let fields = &system.fields;
+        let list = |value: fn(&Field) -> f32| {
+            fields
+                .iter()
+                .map(value)
+                .chain(std::iter::repeat(0.0))
+                .take(fields.len().max(FIELD_SLOTS))
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        writeln!(zmx, "MODE SEQ")?;
+        writeln!(zmx, "UNIT MM X W X CM MR CPMM")?;
+        writeln!(zmx, "FLOA")?;
+        writeln!(
+            zmx,
+            "ENVD {} {} 0",
+            system.environment.temperature, system.environment.pressure
+        )?;
+        if !catalogs.is_empty() {
+            writeln!(zmx, "GCAT {}", catalogs.join(" "))?;
+        }
+        writeln!(
+            zmx,
+            "FTYP 1 0 {} {} 0 0 0",
+            fields.len(),
+            system.wavelengths.len()
+        )?;
+        writeln!(zmx, "XFLN {}", list(|f| f.point.x))?;
+        writeln!(zmx, "YFLN {}", list(|f| f.point.y))?;
+        writeln!(zmx, "FWGN {}", list(|_| 1.0))?;
+        writeln!(zmx, "VDXN {}", list(|f| f.vignetting.decenter_x))?;
+        writeln!(zmx, "VDYN {}", list(|f| f.vignetting.decenter_y))?;
+        writeln!(zmx, "VCXN {}", list(|f| f.vignetting.compression_x))?;
+        writeln!(zmx, "VCYN {}", list(|f| f.vignetting.compression_y))?;
+        for (i, wavelength) in system.wavelengths.iter().enumerate() {
+            writeln!(
+                zmx,
+                "WAVM {} {} {}",
+                i + 1,
+                wavelength.value,
+                wavelength.weight
+            )?;
+        }
+        writeln!(zmx, "PWAV {}", system.primary + 1)?;
+
+        let object = Surface {
+            thickness: system.object.distance,
+            material: system.object.material.clone(),
+            semi_diameter: system.object.semi_diameter,
+            ..Default::default()
+        };
+        for (i, surface) in std::iter::once(&object).chain(&system.surfaces).enumerate() {
+            writeln!(zmx, "SURF {i}")?;
+            if i == system.stop_index as usize + 1 {
+                writeln!(zmx, "  STOP")?;
+            }
+            writeln!(zmx, "  TYPE STANDARD")?;
+            writeln!(zmx, "  CURV {} 0 0 0 0 \"\"", surface.curvature)?;
+            writeln!(zmx, "  DISZ {}", surface.thickness)?;
+            if let Some(glass) = zmx_glass(&surface.material) {
+                writeln!(zmx, "  GLAS {glass}")?;
+            }
+            writeln!(zmx, "  DIAM {} 1 0 0 1 \"\"", surface.semi_diameter)?;
+            if surface.conic != 0.0 {
+                writeln!(zmx, "  CONI {}", surface.conic)?;
+            }
+        }
+
+        Ok(zmx)
+    }
+
+    /// Fails on the first decentered or tilted surface, which the exporters don't support.
+    pub(super) fn ensure_centered(&self) -> anyhow::Result<()> {
+        for (i, surface) in self.surfaces.iter().enumerate() {
+            anyhow::ensure!(
+                surface.decenter == Vector2::zeros() && surface.tilt == Vector2::zeros(),
+                "surface {i}: decentered and tilted surfaces can't be exported"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// GLAS record fields of `material`, `None` for air.
+fn zmx_glass(material: &Material) -> Option<String> {
+    let (name, code, nd, vd) = match material {
+        Material::Constant(n) if *n == 1.0 => return None,
+        Material::Glass(glass) => (glass.name.as_str(), 0, glass.nd, glass.vd),
+        Material::Model { nd, vd } => ("___BLANK", 1, *nd, *vd),
+        material => {
+            let vd = material.vd();
+
+            (
+                "___BLANK",
+                1,
+                material.nd(),
+                if vd.is_finite() { vd } else { 0.0 },
+            )
+        }
+    };
+
+    Some(format!("{name} {code} 0 {nd} {vd} 0 0 0 0 0 0"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AGF: &str = "NM N-BK7 2 517642 1.5168 64.17 0 0\n\
+                       CD 1.03961212 0.00600069867 0.231792344 0.0200179144 1.01046945 \
+                       103.560653 0 0 0 0\n";
+
+    fn doublet() -> System {
+        System::load("prescriptions/doublet.toml", &[]).unwrap()
+    }
+
+    fn assert_same(a: &System, b: &System) {
+        assert_eq!(a.stop_index, b.stop_index);
+        assert_eq!(a.object.distance, b.object.distance);
+        assert_eq!(a.object.semi_diameter, b.object.semi_diameter);
+        assert_eq!(a.surfaces.len(), b.surfaces.len());
+        for (a, b) in a.surfaces.iter().zip(&b.surfaces) {
+            assert_eq!(a.curvature, b.curvature);
+            assert_eq!(a.thickness, b.thickness);
+            assert_eq!(a.semi_diameter, b.semi_diameter);
+            assert_eq!(a.conic, b.conic);
+            assert_eq!(a.material, b.material);
+        }
+        assert_eq!(a.wavelengths, b.wavelengths);
+        assert_eq!(a.primary, b.primary);
+        assert_eq!(a.fields.len(), b.fields.len());
+        for (a, b) in a.fields.iter().zip(&b.fields) {
+            assert_eq!(a.point, b.point);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut system = doublet();
+        system.surfaces[2].conic = -0.5;
+        system.fields[1].vignetting.compression_y = 0.25;
+
+        let imported = System::from_zmx(&system.to_zmx().unwrap(), &[]).unwrap();
+
+        assert_same(&system, &imported);
+        assert_eq!(imported.fields[1].vignetting.compression_y, 0.25);
+    }
+
+    #[test]
+    fn round_trip_utf16() {
+        let zmx = doublet().to_zmx().unwrap();
+        let bytes = [0xFF, 0xFE]
+            .into_iter()
+            .chain(zmx.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect::<Vec<_>>();
+
+        let imported = System::from_zmx(&glass::agf::decode(&bytes).unwrap(), &[]).unwrap();
+
+        assert_same(&doublet(), &imported);
+    }
+
+    #[test]
+    fn catalog_glasses() {
+        let catalog = Catalog::parse("SCHOTT", AGF).unwrap();
+        let mut system = doublet();
+        system.surfaces[0].material = catalog.material("N-BK7").unwrap();
+
+        let zmx = system.to_zmx().unwrap();
+        assert!(zmx.contains("GCAT SCHOTT"));
+
+        let imported = System::from_zmx(&zmx, std::slice::from_ref(&catalog)).unwrap();
+        assert_same(&system, &imported);
+
+        // Model glass fallback without the catalog
+        let imported = System::from_zmx(&zmx, &[]).unwrap();
+        assert_eq!(
+            imported.surfaces[0].material,
+            Material::Model {
+                nd: 1.5168,
+                vd: 64.17
+            }
+        );
+    }
+
+    #[test]
+    fn unsupported_surfaces() {
+        let zmx = doublet().to_zmx().unwrap().replacen(
+            "SURF 2\n  TYPE STANDARD",
+            "SURF 2\n  TYPE TOROIDAL",
+            1,
+        );
+
+        let error = System::from_zmx(&zmx, &[]).unwrap_err();
+
+        assert_eq!(
+            format!("{error:#}"),
+            "SURF 2: unsupported surface type TOROIDAL"
+        );
+    }
+
+    #[test]
+    fn tilted_surfaces() {
+        let mut system = doublet();
+        system.surfaces[1].tilt.x = 0.01;
+
+        assert!(system.to_zmx().is_err());
+    }
+
+    #[test]
+    fn import_doublet() {
+        let system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+
+        assert_eq!(system.object.distance, INFINITE_DISTANCE);
+        assert_eq!(system.stop_index, 0);
+        assert_eq!(system.surfaces.len(), 4);
+        // ENPD 3 with the stop in front of the lens
+        assert!((system.surfaces[0].semi_diameter - 1.5).abs() < 1e-5);
+
+        let curvatures = [0.13532715, -0.19310984, -0.06164278, 0.0];
+        for (surface, curvature) in system.surfaces.iter().zip(curvatures) {
+            assert!((surface.curvature - curvature).abs() < 1e-6);
+        }
+
+        // Model glasses from the GLAS records, SCHOTT not being loaded
+        assert_eq!(
+            system.surfaces[0].material,
+            Material::Model {
+                nd: 1.5168,
+                vd: 64.17
+            }
+        );
+        assert_eq!(
+            system.surfaces[1].material,
+            Material::Model {
+                nd: 1.64769,
+                vd: 33.82
+            }
+        );
+        assert_eq!(system.surfaces[2].material, Material::Constant(1.0));
+
+        // Field angles of 0, 3.5 and 5 degrees at the distance of the entrance pupil
+        let depth = INFINITE_DISTANCE + system.entrance_pupil().z;
+        for (field, angle) in system.fields.iter().zip([0f32, 3.5, 5.0]) {
+            let height = depth * angle.to_radians().tan();
+            assert_eq!(field.point.x, 0.0);
+            assert!((field.point.y - height).abs() < 1e-3 * height.max(1.0));
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_and_invalid_records() {
+        let source = std::fs::read_to_string("prescriptions/doublet.zmx").unwrap();
+
+        let coordinate_break =
+            source.replacen("SURF 3\n  TYPE STANDARD", "SURF 3\n  TYPE COORDBRK", 1);
+        let error = System::from_zmx(&coordinate_break, &[]).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "SURF 3: unsupported surface type COORDBRK"
+        );
+
+        let mirror = source.replacen("GLAS N-SF2 0 0 1.64769 33.82", "GLAS MIRROR 0 0", 1);
+        let error = System::from_zmx(&mirror, &[]).unwrap_err();
+        assert_eq!(format!("{error:#}"), "SURF 2: mirrors aren't supported");
+
+        let fields = source.replacen("YFLN 0 3.5 5", "YFLN 0 3.5 five", 1);
+        let error = System::from_zmx(&fields, &[]).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "line 12: invalid YFLN record: \"five\" isn't a number: invalid float literal"
+        );
+    }
+}
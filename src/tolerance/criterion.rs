@@ -0,0 +1,111 @@
+use nalgebra::Point2;
+
+use crate::{
+    analysis::mtf,
+    compute::raytracing::{Query, RayTracingResult},
+    query::{QueryParameter, spot::SpotRms},
+    system::{Ray, System},
+};
+
+/// Image quality measure the tolerances are judged by, averaged over the fields of every
+/// configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Criterion {
+    /// Polychromatic RMS spot radius, in lens units.
+    RmsSpot,
+    /// RMS wavefront error at the primary wavelength with the piston removed, in waves.
+    RmsWavefront,
+    /// Polychromatic geometric MTF at `frequency`, in cycles per lens unit, averaged over the
+    /// tangential and sagittal directions.
+    Mtf {
+        /// Spatial frequency, in cycles per lens unit.
+        frequency: f32,
+    },
+}
+
+impl Criterion {
+    /// Whether the performance degrades as the criterion grows, false for the MTF.
+    pub fn is_minimized(&self) -> bool {
+        !matches!(self, Self::Mtf { .. })
+    }
+
+    /// Change from `nominal` to `value`, positive when the performance degrades.
+    pub fn degradation(&self, nominal: f32, value: f32) -> f32 {
+        if self.is_minimized() {
+            value - nominal
+        } else {
+            nominal - value
+        }
+    }
+
+    /// Evaluates the criterion on the CPU, the pupils being sampled by `resolution` rays across
+    /// their diameter. NaN when a configuration can't be applied.
+    pub fn evaluate(&self, system: &System, resolution: u32) -> f32 {
+        let Ok(configurations) = system.all_configurations() else {
+            return f32::NAN;
+        };
+        let values = configurations
+            .iter()
+            .flat_map(|configuration| {
+                let configuration = configuration.solved();
+
+                (0..configuration.fields.len())
+                    .map(|field| self.evaluate_field(&configuration, field, resolution))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+
+    fn evaluate_field(&self, system: &System, field: usize, resolution: u32) -> f32 {
+        let spot = SpotRms { field, resolution };
+
+        match *self {
+            Self::RmsSpot => {
+                let query = Query {
+                    rays: spot.rays(system),
+                };
+                let response = system.trace_cpu(&query);
+
+                spot.query(&RayTracingResult {
+                    system,
+                    rays: &query.rays,
+                    intersections: &response.intersections,
+                })
+            }
+            Self::RmsWavefront => {
+                let field = &system.fields[field];
+                let n = resolution.max(2);
+                let step = |i: u32| 2.0 * i as f32 / (n - 1) as f32 - 1.0;
+
+                let rays = (0..n)
+                    .flat_map(|j| (0..n).map(move |i| Point2::new(step(i), step(j))))
+                    .filter(|pupil| pupil.coords.norm_squared() <= 1.0)
+                    .map(|pupil| system.field_ray(field, pupil))
+                    .collect::<Vec<Ray>>();
+                let chief = system.field_ray(field, Point2::origin());
+
+                let opd = system
+                    .opd(&system.parameters::<f32>(), &rays, &chief)
+                    .into_iter()
+                    .filter(|opd| opd.is_finite())
+                    .collect::<Vec<_>>();
+                let mean = opd.iter().sum::<f32>() / opd.len() as f32;
+                let variance =
+                    opd.iter().map(|opd| (opd - mean).powi(2)).sum::<f32>() / opd.len() as f32;
+
+                // Lens units are millimetres, wavelengths micrometres
+                variance.sqrt() / (system.primary_wavelength() * 1e-3)
+            }
+            Self::Mtf { frequency } => {
+                let rays = spot.rays(system);
+                let response = system.trace_cpu(&Query { rays });
+                let points = mtf::image_points(&response.intersections, system.surfaces.len());
+                let (tangential, sagittal) = mtf::geometric_mtf(&points, frequency);
+
+                0.5 * (tangential + sagittal)
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Inverse tolerancing, sharing a degradation budget between the tolerances.
+
+use std::fmt;
+
+use anyhow::Context;
+
+use super::{Criterion, Sensitivity, Tolerance};
+use crate::system::System;
+
+/// Largest factor a tolerance is loosened by, past which the fitted sensitivities are unreliable.
+const MAX_SCALE: f32 = 10.0;
+
+/// Tolerances to loosen or tighten until they share a degradation budget.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Image quality measure the budget applies to.
+    pub criterion: Criterion,
+    /// Tolerances whose magnitudes are the ones the sensitivities are measured at.
+    pub tolerances: Vec<Tolerance>,
+    /// Allowed degradation of the criterion, shared by the tolerances as a root sum of squares.
+    pub budget: f32,
+    /// Number of pupil samples across the diameter.
+    pub resolution: u32,
+}
+
+/// Suggested magnitude of one tolerance.
+#[derive(Debug, Clone, Copy)]
+pub struct Suggestion {
+    /// Tolerance with the suggested magnitude.
+    pub tolerance: Tolerance,
+    /// Degradation predicted by the fitted sensitivity.
+    pub predicted: f32,
+    /// Degradation traced at the suggested magnitude.
+    pub degradation: f32,
+}
+
+/// Suggested tolerances and the budget they share.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// Image quality measure the budget applies to.
+    pub criterion: Criterion,
+    /// Criterion of the nominal system.
+    pub nominal: f32,
+    /// Allowed degradation of the criterion.
+    pub budget: f32,
+    /// Suggestion of every tolerance, in query order.
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Response {
+    /// Tolerances with their suggested magnitudes.
+    pub fn tolerances(&self) -> Vec<Tolerance> {
+        self.suggestions
+            .iter()
+            .map(|suggestion| suggestion.tolerance)
+            .collect()
+    }
+
+    /// Root sum of squares of the traced degradations.
+    pub fn estimated_change(&self) -> f32 {
+        self.suggestions
+            .iter()
+            .map(|suggestion| suggestion.degradation.max(0.0).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    /// Writes the suggested tolerance table as CSV.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), std::io::Error> {
+        let mut csv = String::from("surface,parameter,tolerance,degradation\n");
+        for suggestion in &self.suggestions {
+            let tolerance = &suggestion.tolerance;
+            csv += &format!(
+                "{},{},{},{}\n",
+                tolerance.surface,
+                tolerance.perturbation.label(),
+                tolerance.magnitude,
+                suggestion.degradation
+            );
+        }
+
+        std::fs::write(path, csv)
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Criterion: {:?}, nominal {:.6}, budget {:.6}",
+            self.criterion, self.nominal, self.budget
+        )?;
+        writeln!(
+            f,
+            "{:>6} {:<12} {:>12} {:>12} {:>12}",
+            "surf", "parameter", "tolerance", "predicted", "degradation"
+        )?;
+
+        for suggestion in &self.suggestions {
+            let tolerance = &suggestion.tolerance;
+            writeln!(
+                f,
+                "{:>6} {:<12} {:>12.6} {:>12.6} {:>12.6}",
+                tolerance.surface,
+                tolerance.perturbation.label(),
+                tolerance.magnitude,
+                suggestion.predicted,
+                suggestion.degradation
+            )?;
+        }
+
+        write!(f, "Estimated change (RSS): {:.6}", self.estimated_change())
+    }
+}
+
+/// Rounds `value` down to two significant digits.
+fn round_down(value: f32) -> f32 {
+    if value <= 0.0 || !value.is_finite() {
+        return value;
+    }
+
+    let scale = 10f32.powf(value.log10().floor() - 1.0);
+    (value / scale).floor() * scale
+}
+
+/// Fits the degradation of `sensitivity` by `a t + b t²` and solves it for `share`, returning
+/// the suggested tolerance and its predicted degradation.
+fn suggest(
+    criterion: Criterion,
+    nominal: f32,
+    sensitivity: &Sensitivity,
+    share: f32,
+) -> (Tolerance, f32) {
+    let tolerance = sensitivity.tolerance;
+    let m = tolerance.magnitude;
+    let minus = criterion.degradation(nominal, sensitivity.minus);
+    let plus = criterion.degradation(nominal, sensitivity.plus);
+    let a = ((plus - minus) / (2.0 * m)).abs();
+    let b = ((plus + minus) / (2.0 * m * m)).max(0.0);
+
+    let magnitude = if b > 0.0 {
+        (-a + (a * a + 4.0 * b * share).sqrt()) / (2.0 * b)
+    } else if a > 0.0 {
+        share / a
+    } else {
+        f32::INFINITY
+    };
+    let magnitude = round_down(magnitude.min(MAX_SCALE * m));
+
+    (
+        Tolerance {
+            magnitude,
+            ..tolerance
+        },
+        a * magnitude + b * magnitude * magnitude,
+    )
+}
+
+impl System {
+    /// Derives the loosest tolerances keeping the criterion within the budget.
+    ///
+    /// The degradation of every parameter is fitted by `a t + b t²` from its sensitivity at both
+    /// ends of the given tolerance, then solved for an equal share of the budget. The suggested
+    /// tolerances are traced again to report their actual degradations.
+    ///
+    /// Fails unless the budget and every magnitude are positive, or when a parameter is
+    /// toleranced twice.
+    pub fn inverse_tolerance(&self, query: &Query) -> anyhow::Result<Response> {
+        anyhow::ensure!(
+            query.budget.is_finite() && query.budget > 0.0,
+            "the budget must be positive, got {}",
+            query.budget
+        );
+        for (i, tolerance) in query.tolerances.iter().enumerate() {
+            anyhow::ensure!(
+                tolerance.magnitude.is_finite() && tolerance.magnitude > 0.0,
+                "tolerance {i} must have a positive magnitude, got {}",
+                tolerance.magnitude
+            );
+            if let Some(j) = query.tolerances[..i].iter().position(|other| {
+                (other.surface, other.perturbation) == (tolerance.surface, tolerance.perturbation)
+            }) {
+                anyhow::bail!(
+                    "tolerances {j} and {i} both perturb the {} of surface {}",
+                    tolerance.perturbation.label(),
+                    tolerance.surface
+                );
+            }
+        }
+
+        let sensitivity = |tolerances: Vec<Tolerance>| {
+            self.sensitivity(&super::Query {
+                criterion: query.criterion,
+                tolerances,
+                resolution: query.resolution,
+            })
+        };
+
+        let measured = sensitivity(query.tolerances.clone())?;
+        let share = query.budget / (query.tolerances.len().max(1) as f32).sqrt();
+
+        let predictions = query
+            .tolerances
+            .iter()
+            .enumerate()
+            .map(|(i, tolerance)| {
+                let sensitivity = measured
+                    .sensitivities
+                    .iter()
+                    .find(|sensitivity| sensitivity.tolerance == *tolerance)
+                    .with_context(|| format!("no sensitivity measured for tolerance {i}"))?;
+
+                Ok(suggest(
+                    query.criterion,
+                    measured.nominal,
+                    sensitivity,
+                    share,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let traced = sensitivity(
+            predictions
+                .iter()
+                .map(|(tolerance, _)| *tolerance)
+                .collect(),
+        )?;
+        let suggestions = predictions
+            .into_iter()
+            .map(|(tolerance, predicted)| Suggestion {
+                tolerance,
+                predicted,
+                degradation: traced
+                    .sensitivities
+                    .iter()
+                    .find(|sensitivity| sensitivity.tolerance == tolerance)
+                    .map_or(f32::NAN, |sensitivity| sensitivity.worst),
+            })
+            .collect();
+
+        Ok(Response {
+            criterion: query.criterion,
+            nominal: measured.nominal,
+            budget: query.budget,
+            suggestions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tolerance::Perturbation;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() <= 1e-5 * expected.abs(),
+            "{value} instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn rounds_down_to_two_digits() {
+        assert_close(round_down(0.0347), 0.034);
+        assert_close(round_down(123.0), 120.0);
+        assert_close(round_down(7.0), 7.0);
+        assert_eq!(round_down(0.0), 0.0);
+        assert_eq!(round_down(-1.0), -1.0);
+        assert_eq!(round_down(f32::INFINITY), f32::INFINITY);
+    }
+
+    #[test]
+    fn solves_quadratic_budget() {
+        // Degradation 0.1 t + 2 t², sampled at t = ±0.1
+        let degradation = |t: f32| 0.1 * t + 2.0 * t * t;
+        let tolerance = Tolerance::new(1, Perturbation::Thickness, 0.1);
+        let sensitivity = |criterion: Criterion, nominal: f32| {
+            let value = |t| nominal + criterion.degradation(0.0, degradation(t));
+
+            Sensitivity {
+                tolerance,
+                minus: value(-0.1),
+                plus: value(0.1),
+                worst: degradation(0.1),
+            }
+        };
+
+        // Exact root 0.1351, rounded down
+        for (criterion, nominal) in [
+            (Criterion::RmsSpot, 0.01),
+            (Criterion::Mtf { frequency: 50.0 }, 0.8),
+        ] {
+            let (suggested, predicted) =
+                suggest(criterion, nominal, &sensitivity(criterion, nominal), 0.05);
+            assert_eq!(suggested.surface, 1);
+            assert_close(suggested.magnitude, 0.13);
+            assert_close(predicted, degradation(0.13));
+        }
+
+        // Insensitive tolerances are only loosened up to MAX_SCALE
+        let flat = Sensitivity {
+            tolerance,
+            minus: 0.01,
+            plus: 0.01,
+            worst: 0.0,
+        };
+        let (suggested, predicted) = suggest(Criterion::RmsSpot, 0.01, &flat, 0.05);
+        assert_close(suggested.magnitude, 1.0);
+        assert_eq!(predicted, 0.0);
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        let system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        let query = |budget, tolerances| Query {
+            criterion: Criterion::RmsSpot,
+            tolerances,
+            budget,
+            resolution: 8,
+        };
+        let thickness = |magnitude| Tolerance::new(2, Perturbation::Thickness, magnitude);
+        let error = |query: Query| format!("{:#}", system.inverse_tolerance(&query).unwrap_err());
+
+        assert_eq!(
+            error(query(0.0, vec![thickness(0.1)])),
+            "the budget must be positive, got 0"
+        );
+        assert_eq!(
+            error(query(f32::NAN, vec![thickness(0.1)])),
+            "the budget must be positive, got NaN"
+        );
+        for magnitude in [0.0, -0.1, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                error(query(0.01, vec![thickness(0.1), thickness(magnitude)])),
+                format!("tolerance 1 must have a positive magnitude, got {magnitude}")
+            );
+        }
+        assert_eq!(
+            error(query(0.01, vec![thickness(0.1), thickness(0.2)])),
+            "tolerances 0 and 1 both perturb the thickness of surface 2"
+        );
+    }
+}
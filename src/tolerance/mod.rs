@@ -0,0 +1,32 @@
+//! Tolerance sensitivity, inverse tolerancing and Monte Carlo analyses.
+
+mod criterion;
+pub mod inverse;
+pub mod monte_carlo;
+mod perturbation;
+mod sensitivity;
+
+use std::thread;
+
+pub use criterion::*;
+pub use perturbation::*;
+pub use sensitivity::*;
+
+/// Maps `f` over `items` on every available core, keeping their order.
+fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = items.len().div_ceil(threads).max(1);
+    let f = &f;
+
+    thread::scope(|scope| {
+        let handles = items
+            .chunks(chunk)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("tolerance worker panicked"))
+            .collect()
+    })
+}
@@ -0,0 +1,409 @@
+//! Monte Carlo tolerancing, tracing systems with random errors.
+
+use std::fmt;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rand_distr::Normal;
+
+use super::{Criterion, Tolerance, parallel_map};
+use crate::{optimization::Variable, system::System, utils::Plot};
+
+/// Golden section iterations per compensator and pass.
+const ITERATIONS: usize = 20;
+/// Passes over several compensators, for the ones interacting.
+const PASSES: usize = 2;
+const PERCENTILES: [f32; 5] = [0.5, 0.8, 0.9, 0.95, 0.98];
+
+/// How the errors are drawn within their tolerances.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    /// Uniform within the tolerance.
+    Uniform,
+    /// Normal, the tolerance being `sigmas` standard deviations and truncating the errors.
+    Normal {
+        /// Number of standard deviations the tolerance spans.
+        sigmas: f32,
+    },
+    /// Either end of the tolerance with equal probability.
+    EndPoint,
+}
+
+impl Distribution {
+    fn sample(&self, magnitude: f32, rng: &mut impl Rng) -> f32 {
+        match *self {
+            Self::Uniform => rng.gen_range(-1.0..=1.0) * magnitude,
+            Self::Normal { sigmas } => Normal::new(0.0, magnitude.abs() / sigmas)
+                .map_or(0.0, |normal| rng.sample(normal))
+                .clamp(-magnitude.abs(), magnitude.abs()),
+            Self::EndPoint => {
+                if rng.r#gen::<bool>() {
+                    magnitude
+                } else {
+                    -magnitude
+                }
+            }
+        }
+    }
+}
+
+/// Monte Carlo analysis settings.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Image quality measure of the trials.
+    pub criterion: Criterion,
+    /// Tolerances the errors of every trial are drawn within.
+    pub tolerances: Vec<Tolerance>,
+    /// How the errors are drawn.
+    pub distribution: Distribution,
+    /// Parameters adjusted on every trial to recover the criterion, such as the thickness
+    /// before the image surface for the back focus.
+    pub compensators: Vec<Variable>,
+    /// Number of systems drawn.
+    pub trials: usize,
+    /// Number of pupil samples across the diameter.
+    pub resolution: u32,
+    /// Seed of the random number generator, trial `i` using `seed + i`.
+    pub seed: u64,
+}
+
+impl Query {
+    /// Query of 100 uniform trials without compensators.
+    pub fn new(criterion: Criterion, tolerances: Vec<Tolerance>) -> Self {
+        Self {
+            criterion,
+            tolerances,
+            distribution: Distribution::Uniform,
+            compensators: Vec::new(),
+            trials: 100,
+            resolution: 16,
+            seed: 0,
+        }
+    }
+}
+
+/// System drawn with random errors.
+#[derive(Debug, Clone)]
+pub struct Trial {
+    /// Error drawn for every tolerance.
+    pub errors: Vec<f32>,
+    /// Final value of every compensator.
+    pub compensators: Vec<f32>,
+    /// Criterion after compensation.
+    pub criterion: f32,
+}
+
+/// Criterion of every trial.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// Image quality measure of the trials.
+    pub criterion: Criterion,
+    /// Criterion of the nominal system.
+    pub nominal: f32,
+    /// Trials, in drawing order.
+    pub trials: Vec<Trial>,
+}
+
+impl Response {
+    /// Criteria of the trials from the best to the worst, ignoring failed traces.
+    fn sorted(&self) -> Vec<f32> {
+        let mut values = self
+            .trials
+            .iter()
+            .map(|trial| trial.criterion)
+            .filter(|value| value.is_finite())
+            .collect::<Vec<_>>();
+        values.sort_by(|a, b| a.total_cmp(b));
+        if !self.criterion.is_minimized() {
+            values.reverse();
+        }
+
+        values
+    }
+
+    /// Mean criterion of the trials.
+    pub fn mean(&self) -> f32 {
+        let values = self.sorted();
+
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+
+    /// Standard deviation of the criterion of the trials.
+    pub fn standard_deviation(&self) -> f32 {
+        let values = self.sorted();
+        let mean = self.mean();
+
+        (values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f32>()
+            / values.len() as f32)
+            .sqrt()
+    }
+
+    /// Criterion met by `fraction` of the trials, e.g. 0.9 for a 90 % yield.
+    pub fn percentile(&self, fraction: f32) -> f32 {
+        let values = self.sorted();
+        if values.is_empty() {
+            return f32::NAN;
+        }
+
+        let index = (fraction.clamp(0.0, 1.0) * values.len() as f32).ceil() as usize;
+        values[index.clamp(1, values.len()) - 1]
+    }
+
+    /// Fraction of the trials meeting `threshold`, failed traces counting as rejects.
+    pub fn yield_at(&self, threshold: f32) -> f32 {
+        let passing = self
+            .sorted()
+            .into_iter()
+            .filter(|value| self.criterion.degradation(threshold, *value) <= 0.0)
+            .count();
+
+        passing as f32 / self.trials.len() as f32
+    }
+
+    /// Cumulative distribution of the criterion.
+    pub fn plot(&self) -> Plot {
+        let values = self.sorted();
+        let mut plot = Plot::new("Monte Carlo", format!("{:?}", self.criterion), "Yield");
+
+        plot.line(
+            "yield",
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (*value, (i + 1) as f32 / self.trials.len() as f32))
+                .collect(),
+            "cyan",
+        );
+        plot.marker(self.nominal, 0.0, "yellow");
+
+        plot
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self.sorted();
+
+        writeln!(
+            f,
+            "Criterion: {:?}, nominal {:.6}",
+            self.criterion, self.nominal
+        )?;
+        writeln!(
+            f,
+            "Trials: {} ({} failed)",
+            self.trials.len(),
+            self.trials.len() - values.len()
+        )?;
+        writeln!(
+            f,
+            "Mean: {:.6}, standard deviation: {:.6}",
+            self.mean(),
+            self.standard_deviation()
+        )?;
+        if let (Some(best), Some(worst)) = (values.first(), values.last()) {
+            writeln!(f, "Best: {best:.6}, worst: {worst:.6}")?;
+        }
+
+        writeln!(f, "{:>8} {:>12}", "yield", "criterion")?;
+        for fraction in PERCENTILES {
+            writeln!(
+                f,
+                "{:>7.0}% {:>12.6}",
+                100.0 * fraction,
+                self.percentile(fraction)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl System {
+    /// Evaluates the criterion on randomly perturbed copies of the system, adjusting the
+    /// compensators of each, on every available core.
+    pub fn monte_carlo(&self, query: &Query) -> anyhow::Result<Response> {
+        self.all_configurations()?;
+        let trials = (0..query.trials)
+            .map(|trial| {
+                let mut rng = StdRng::seed_from_u64(query.seed.wrapping_add(trial as u64));
+
+                query
+                    .tolerances
+                    .iter()
+                    .map(|tolerance| query.distribution.sample(tolerance.magnitude, &mut rng))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // Nominal criterion after compensation too, so that the trials compare with it
+        let (_, nominal) = self.clone().compensate(query)?;
+        let trials = parallel_map(&trials, |errors| {
+            let mut system = self.clone();
+            for (tolerance, error) in query.tolerances.iter().zip(errors) {
+                tolerance.apply(&mut system, *error)?;
+            }
+
+            let (compensators, criterion) = system.compensate(query)?;
+
+            Ok(Trial {
+                errors: errors.clone(),
+                compensators,
+                criterion,
+            })
+        })
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Response {
+            criterion: query.criterion,
+            nominal,
+            trials,
+        })
+    }
+
+    /// Golden section searches of every compensator within its bounds and span, returning their
+    /// final values and the criterion.
+    fn compensate(&mut self, query: &Query) -> anyhow::Result<(Vec<f32>, f32)> {
+        let criterion = &query.criterion;
+        let cost = |system: &System| {
+            let value = criterion.evaluate(system, query.resolution);
+            let cost = if criterion.is_minimized() {
+                value
+            } else {
+                -value
+            };
+
+            if cost.is_finite() {
+                cost
+            } else {
+                f32::INFINITY
+            }
+        };
+
+        let ratio = 0.5 * (5f32.sqrt() - 1.0);
+        let passes = if query.compensators.len() > 1 {
+            PASSES
+        } else {
+            1
+        };
+        for _ in 0..passes {
+            for compensator in &query.compensators {
+                let start = compensator.value(self)?;
+                let (mut a, mut b) = compensator.range(start);
+
+                let mut probe = |value: f32| -> anyhow::Result<f32> {
+                    compensator.set(self, value)?;
+
+                    Ok(cost(self))
+                };
+
+                let mut c = b - ratio * (b - a);
+                let mut d = a + ratio * (b - a);
+                let (mut fc, mut fd) = (probe(c)?, probe(d)?);
+                for _ in 0..ITERATIONS {
+                    if fc < fd {
+                        (b, d, fd) = (d, c, fc);
+                        c = b - ratio * (b - a);
+                        fc = probe(c)?;
+                    } else {
+                        (a, c, fc) = (c, d, fd);
+                        d = a + ratio * (b - a);
+                        fd = probe(d)?;
+                    }
+                }
+
+                // Keep the starting value unless the search found better
+                let best = if fc < fd { c } else { d };
+                let start_cost = probe(start)?;
+                if fc.min(fd) < start_cost {
+                    compensator.set(self, best)?;
+                }
+            }
+        }
+
+        let values = query
+            .compensators
+            .iter()
+            .map(|compensator| compensator.value(self))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok((values, criterion.evaluate(self, query.resolution)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{optimization::Parameter, tolerance::Perturbation};
+
+    #[test]
+    fn samples_within_tolerance() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for distribution in [
+            Distribution::Uniform,
+            Distribution::Normal { sigmas: 2.0 },
+            Distribution::EndPoint,
+        ] {
+            for _ in 0..1000 {
+                let error = distribution.sample(0.2, &mut rng);
+                assert!(error.abs() <= 0.2, "{distribution:?}: {error}");
+                if distribution == Distribution::EndPoint {
+                    assert_eq!(error.abs(), 0.2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn percentiles_and_yield() {
+        let response = |criterion, values: &[f32]| Response {
+            criterion,
+            nominal: 0.0,
+            trials: values
+                .iter()
+                .map(|criterion| Trial {
+                    errors: Vec::new(),
+                    compensators: Vec::new(),
+                    criterion: *criterion,
+                })
+                .collect(),
+        };
+
+        // The failed trace counts against the yield only
+        let spot = response(Criterion::RmsSpot, &[0.1, 0.4, f32::NAN, 0.2, 0.3]);
+        assert_eq!(spot.percentile(0.0), 0.1);
+        assert_eq!(spot.percentile(0.5), 0.2);
+        assert_eq!(spot.percentile(0.9), 0.4);
+        assert_eq!(spot.yield_at(0.25), 0.4);
+        assert_eq!(spot.yield_at(1.0), 0.8);
+
+        // Larger is better for the MTF
+        let mtf = response(Criterion::Mtf { frequency: 50.0 }, &[0.6, 0.8, 0.5, 0.7]);
+        assert_eq!(mtf.percentile(0.25), 0.8);
+        assert_eq!(mtf.percentile(0.75), 0.6);
+        assert_eq!(mtf.yield_at(0.65), 0.5);
+    }
+
+    #[test]
+    fn back_focus_compensates_thickness_error() {
+        let system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        let tolerance = Tolerance::new(2, Perturbation::Thickness, 0.5);
+        let mut query = Query::new(Criterion::RmsSpot, vec![tolerance]);
+        let nominal = query.criterion.evaluate(&system, query.resolution);
+
+        let mut perturbed = tolerance.applied(&system, 0.5).unwrap();
+        let (_, uncompensated) = perturbed.clone().compensate(&query).unwrap();
+        assert!(uncompensated > 5.0 * nominal);
+
+        query.compensators = vec![Variable::new(2, Parameter::Thickness)];
+        let (compensators, criterion) = perturbed.compensate(&query).unwrap();
+        assert!((compensators[0] - 11.2).abs() < 0.05, "{compensators:?}");
+        assert!(
+            criterion <= 1.01 * nominal,
+            "{criterion} instead of {nominal}"
+        );
+    }
+}
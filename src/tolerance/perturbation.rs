@@ -0,0 +1,113 @@
+use crate::{
+    optimization::{Parameter, Variable},
+    system::System,
+};
+
+/// Manufacturing error of a surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Perturbation {
+    /// Radius of curvature, in lens units.
+    Radius,
+    /// Thickness following the surface.
+    Thickness,
+    /// Index of the medium following the surface.
+    Index,
+    /// Abbe number of the model glass following the surface.
+    Abbe,
+    /// Offset of the vertex along x.
+    DecenterX,
+    /// Offset of the vertex along y.
+    DecenterY,
+    /// Rotation about the x axis.
+    TiltX,
+    /// Rotation about the y axis.
+    TiltY,
+}
+
+impl Perturbation {
+    /// Name of the perturbation in reports.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Radius => "radius",
+            Self::Thickness => "thickness",
+            Self::Index => "index",
+            Self::Abbe => "abbe",
+            Self::DecenterX => "decenter x",
+            Self::DecenterY => "decenter y",
+            Self::TiltX => "tilt x",
+            Self::TiltY => "tilt y",
+        }
+    }
+}
+
+/// Largest expected manufacturing error of a surface parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Index of the perturbed surface.
+    pub surface: usize,
+    /// Parameter perturbed.
+    pub perturbation: Perturbation,
+    /// Deviation from the nominal value, in lens units, index units or radians.
+    pub magnitude: f32,
+}
+
+impl Tolerance {
+    /// Tolerance of `magnitude` on `perturbation` of `surface`.
+    pub fn new(surface: usize, perturbation: Perturbation, magnitude: f32) -> Self {
+        Self {
+            surface,
+            perturbation,
+            magnitude,
+        }
+    }
+
+    /// Offsets the parameter of `system` by `delta`.
+    pub fn apply(&self, system: &mut System, delta: f32) -> anyhow::Result<()> {
+        let offset = |system: &mut System, parameter| {
+            let variable = Variable::new(self.surface, parameter);
+            let value = variable.value(system)?;
+
+            variable.set(system, value + delta)
+        };
+
+        match self.perturbation {
+            Perturbation::Radius => {
+                let variable = Variable::new(self.surface, Parameter::Curvature);
+                let curvature = variable.value(system)?;
+                anyhow::ensure!(
+                    curvature != 0.0,
+                    "surface {} is plane, its radius can't be toleranced",
+                    self.surface
+                );
+
+                variable.set(system, 1.0 / (curvature.recip() + delta))
+            }
+            Perturbation::Thickness => offset(system, Parameter::Thickness),
+            Perturbation::Index => offset(system, Parameter::Index),
+            Perturbation::Abbe => offset(system, Parameter::Abbe),
+            _ => {
+                let surface = system
+                    .surfaces
+                    .get_mut(self.surface)
+                    .ok_or_else(|| anyhow::anyhow!("no surface {}", self.surface))?;
+
+                match self.perturbation {
+                    Perturbation::DecenterX => surface.decenter.x += delta,
+                    Perturbation::DecenterY => surface.decenter.y += delta,
+                    Perturbation::TiltX => surface.tilt.x += delta,
+                    _ => surface.tilt.y += delta,
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Copy of `system` with the parameter offset by `delta`.
+    pub fn applied(&self, system: &System, delta: f32) -> anyhow::Result<System> {
+        let mut system = system.clone();
+        self.apply(&mut system, delta)?;
+
+        Ok(system)
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+
+use super::{Criterion, Tolerance, parallel_map};
+use crate::system::System;
+
+/// Tolerances whose effect on the criterion is traced one at a time.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// Image quality measure the tolerances are judged by.
+    pub criterion: Criterion,
+    /// Tolerances, each applied at both ends.
+    pub tolerances: Vec<Tolerance>,
+    /// Number of pupil samples across the diameter.
+    pub resolution: u32,
+}
+
+/// Effect of one tolerance on the criterion.
+#[derive(Debug, Clone, Copy)]
+pub struct Sensitivity {
+    /// Tolerance applied.
+    pub tolerance: Tolerance,
+    /// Criterion with the parameter at the lower end of the tolerance.
+    pub minus: f32,
+    /// Criterion with the parameter at the upper end of the tolerance.
+    pub plus: f32,
+    /// Largest degradation from the nominal criterion of the two ends.
+    pub worst: f32,
+}
+
+/// Sensitivity of every tolerance.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// Image quality measure the tolerances are judged by.
+    pub criterion: Criterion,
+    /// Criterion of the nominal system.
+    pub nominal: f32,
+    /// Sorted from the largest degradation to the smallest.
+    pub sensitivities: Vec<Sensitivity>,
+}
+
+impl Response {
+    /// First `count` sensitivities, the largest degradations.
+    pub fn worst_offenders(&self, count: usize) -> &[Sensitivity] {
+        &self.sensitivities[..count.min(self.sensitivities.len())]
+    }
+
+    /// Root sum of squares of the degradations, estimating the combined effect of the
+    /// tolerances.
+    pub fn estimated_change(&self) -> f32 {
+        self.sensitivities
+            .iter()
+            .map(|sensitivity| sensitivity.worst.max(0.0).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    /// One row per tolerance, from the largest degradation to the smallest.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("surface,parameter,tolerance,minus,plus,worst\n");
+        for sensitivity in &self.sensitivities {
+            let tolerance = &sensitivity.tolerance;
+            csv += &format!(
+                "{},{},{},{},{},{}\n",
+                tolerance.surface,
+                tolerance.perturbation.label(),
+                tolerance.magnitude,
+                sensitivity.minus,
+                sensitivity.plus,
+                sensitivity.worst
+            );
+        }
+
+        csv
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Criterion: {:?}, nominal {:.6}",
+            self.criterion, self.nominal
+        )?;
+        writeln!(
+            f,
+            "{:>4} {:>6} {:<12} {:>12} {:>12} {:>12} {:>12}",
+            "rank", "surf", "parameter", "tolerance", "minus", "plus", "worst"
+        )?;
+
+        for (i, sensitivity) in self.sensitivities.iter().enumerate() {
+            let tolerance = &sensitivity.tolerance;
+            writeln!(
+                f,
+                "{:>4} {:>6} {:<12} {:>12.6} {:>12.6} {:>12.6} {:>12.6}",
+                i + 1,
+                tolerance.surface,
+                tolerance.perturbation.label(),
+                tolerance.magnitude,
+                sensitivity.minus,
+                sensitivity.plus,
+                sensitivity.worst
+            )?;
+        }
+
+        write!(f, "Estimated change (RSS): {:.6}", self.estimated_change())
+    }
+}
+
+impl System {
+    /// Evaluates the criterion with every parameter at both ends of its tolerance in turn, on
+    /// every available core.
+    pub fn sensitivity(&self, query: &Query) -> anyhow::Result<Response> {
+        self.all_configurations()?;
+        let systems = query
+            .tolerances
+            .iter()
+            .flat_map(|tolerance| {
+                [-tolerance.magnitude, tolerance.magnitude]
+                    .map(|delta| tolerance.applied(self, delta))
+            })
+            .chain([Ok(self.clone())])
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut values = parallel_map(&systems, |system| {
+            query.criterion.evaluate(system, query.resolution)
+        });
+        let nominal = values.pop().unwrap_or(f32::NAN);
+
+        let mut sensitivities = query
+            .tolerances
+            .iter()
+            .zip(values.chunks(2))
+            .map(|(tolerance, values)| Sensitivity {
+                tolerance: *tolerance,
+                minus: values[0],
+                plus: values[1],
+                worst: query
+                    .criterion
+                    .degradation(nominal, values[0])
+                    .max(query.criterion.degradation(nominal, values[1])),
+            })
+            .collect::<Vec<_>>();
+        sensitivities.sort_by(|a, b| b.worst.total_cmp(&a.worst));
+
+        Ok(Response {
+            criterion: query.criterion,
+            nominal,
+            sensitivities,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point2;
+
+    use super::*;
+    use crate::{compute::raytracing, tolerance::Perturbation};
+
+    fn tolerances() -> Vec<Tolerance> {
+        vec![
+            Tolerance::new(0, Perturbation::DecenterY, 0.01),
+            Tolerance::new(1, Perturbation::Index, 1e-3),
+            Tolerance::new(2, Perturbation::Thickness, 0.2),
+            Tolerance::new(1, Perturbation::TiltX, 1e-3),
+        ]
+    }
+
+    fn sensitivity(system: &System) -> Response {
+        system
+            .sensitivity(&Query {
+                criterion: Criterion::RmsSpot,
+                tolerances: tolerances(),
+                resolution: 32,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn ranks_focus_thickness_first() {
+        let mut system = System::load_zmx("prescriptions/doublet.zmx", &[]).unwrap();
+        let response = sensitivity(&system);
+        let focus = response.sensitivities[0];
+        assert_eq!(focus.tolerance, tolerances()[2], "{response}");
+        assert!(
+            response.sensitivities[1..]
+                .iter()
+                .all(|sensitivity| sensitivity.worst < 0.1 * focus.worst),
+            "{response}"
+        );
+
+        // Defocusing the uniformly filled pupil by 0.2 spreads a spot of RMS radius 0.2 u' / √2
+        let ray = system.field_ray(&system.fields[0], Point2::new(0.0, 1.0));
+        let traced = system.trace_cpu(&raytracing::Query { rays: vec![ray] });
+        let direction = traced.intersections[system.surfaces.len() - 1]
+            .ray
+            .direction;
+        let defocus = 0.2 * (direction.y / direction.z).abs() / 2f32.sqrt();
+        let expected = response.nominal.hypot(defocus);
+        let mean = 0.5 * (focus.minus + focus.plus);
+        assert!(
+            (mean - expected).abs() < 0.05 * expected,
+            "{mean} instead of {expected}"
+        );
+        assert!(focus.minus > response.nominal && focus.plus > response.nominal);
+
+        // Short of focus, only the longer thickness improves the spot
+        system.surfaces[2].thickness -= 0.3;
+        let response = sensitivity(&system);
+        let focus = response.sensitivities[0];
+        assert_eq!(focus.tolerance, tolerances()[2], "{response}");
+        assert!(
+            focus.plus < response.nominal && response.nominal < focus.minus,
+            "{response}"
+        );
+        assert_eq!(focus.worst, focus.minus - response.nominal, "{response}");
+    }
+}
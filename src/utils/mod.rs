@@ -1,3 +1,8 @@
+//! SVG rendering of layouts and plots.
+
+mod plot;
+
+pub use plot::*;
 use svg::Node;
 
 use crate::system;
@@ -6,14 +11,33 @@ const COLORS: &[&str] = &[
     "red", "orange", "yellow", "lime", "blue", "indigo", "violet",
 ];
 
+/// Returns the color used to draw curves of `wavelength`, in micrometres.
+pub fn wavelength_color(wavelength: f32) -> &'static str {
+    const EDGES: [f32; 6] = [0.62, 0.59, 0.57, 0.495, 0.45, 0.425];
+
+    EDGES
+        .iter()
+        .position(|edge| wavelength >= *edge)
+        .map_or(COLORS[COLORS.len() - 1], |index| COLORS[index])
+}
+
+/// Side view of a system in the y-z plane, drawn as SVG.
 pub struct View {
+    /// Document drawn into, sized by [`View::finish`].
     pub document: svg::Document,
     min_z: f32,
     max_z: f32,
     max_y: f32,
 }
 
+impl Default for View {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl View {
+    /// Empty view with the optical axis drawn.
     pub fn new() -> Self {
         Self {
             document: svg::Document::new()
@@ -42,6 +66,7 @@ impl View {
         }
     }
 
+    /// Draws the profile of `surface`, its vertex at `z`.
     pub fn draw_surface(&mut self, surface: &system::Surface, z: f32) {
         let radius = surface.curvature.recip();
 
@@ -49,7 +74,26 @@ impl View {
         self.max_z = self.max_z.max(z + surface.thickness);
         self.max_y = self.max_y.max(surface.semi_diameter);
 
-        if radius.is_finite() {
+        if radius.is_finite() && surface.conic != 0.0 {
+            let n = 64;
+            let points = (0..=n).map(|i| {
+                let y = surface.semi_diameter * (2.0 * i as f32 / n as f32 - 1.0);
+
+                (z + surface.z(nalgebra::Point2::new(0.0, y)), y)
+            });
+
+            let mut data = svg::node::element::path::Data::new();
+            for (i, point) in points.enumerate() {
+                data = if i == 0 {
+                    data.move_to(point)
+                } else {
+                    data.line_to(point)
+                };
+            }
+
+            self.document
+                .append(svg::node::element::Path::new().set("d", data).surface());
+        } else if radius.is_finite() {
             self.document.append(
                 svg::node::element::Circle::new()
                     .set("cx", z + radius)
@@ -102,6 +146,7 @@ impl View {
         }
     }
 
+    /// Draws the object plane.
     pub fn draw_object(&mut self, object: &system::Object) {
         self.min_z = self.min_z.min(-object.distance);
         self.max_z = self.max_z.max(-object.distance);
@@ -119,6 +164,7 @@ impl View {
         );
     }
 
+    /// Draws the segment of a ray reaching a surface, and the surface normal there.
     pub fn draw_intersection(&mut self, intersection: &system::Intersection) {
         let point = intersection.ray.origin + intersection.t * intersection.ray.direction;
 
@@ -128,7 +174,7 @@ impl View {
                 .set("y1", intersection.ray.origin.y)
                 .set("x2", point.z)
                 .set("y2", point.y)
-                .set("stroke", "cyan")
+                .set("stroke", wavelength_color(intersection.ray.wavelength))
                 .set("stroke-width", 0.025)
                 .set("stroke-linecap", "round"),
         );
@@ -142,12 +188,14 @@ impl View {
         );
     }
 
+    /// Draws the segments of every intersection.
     pub fn draw_intersections(&mut self, intersections: &[system::Intersection]) {
         for intersection in intersections {
             self.draw_intersection(intersection);
         }
     }
 
+    /// Draws a unit length of `ray` from its origin.
     pub fn draw_ray(&mut self, ray: &system::Ray) {
         self.document.append(
             svg::node::element::Line::new()
@@ -161,6 +209,7 @@ impl View {
         );
     }
 
+    /// Draws the object and every surface of `system`.
     pub fn draw_system(&mut self, system: &system::System) {
         self.draw_object(&system.object);
         let mut z = 0.0;
@@ -171,6 +220,7 @@ impl View {
         }
     }
 
+    /// Fits the view box around what was drawn.
     pub fn finish(&mut self) {
         let width = self.max_z - self.min_z;
         let height = 2.0 * self.max_y;
@@ -188,6 +238,7 @@ impl View {
         self.document.assign("height", 32.0 * height);
     }
 
+    /// Writes the document to an SVG file.
     pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), std::io::Error> {
         svg::save(path, &self.document)
     }
@@ -0,0 +1,457 @@
+use svg::Node;
+
+const WIDTH: f32 = 640.0;
+const HEIGHT: f32 = 480.0;
+const MARGIN: f32 = 64.0;
+const TICKS: usize = 5;
+
+/// Polyline of a plot.
+pub struct Series {
+    /// Legend entry of the series, none for unlisted ones.
+    pub label: Option<String>,
+    /// Points of the polyline, in data coordinates.
+    pub points: Vec<(f32, f32)>,
+    /// SVG color of the line.
+    pub color: &'static str,
+    /// Draws the line dashed.
+    pub dashed: bool,
+}
+
+/// Line chart rendered with the same dark theme as [`super::View`].
+pub struct Plot {
+    /// Title drawn above the chart.
+    pub title: String,
+    /// Label of the horizontal axis.
+    pub x_label: String,
+    /// Label of the vertical axis.
+    pub y_label: String,
+    /// Uses the same scale on both axes, e.g. for image plane plots.
+    pub equal_aspect: bool,
+    series: Vec<Series>,
+    markers: Vec<(f32, f32, &'static str)>,
+}
+
+impl Plot {
+    /// Empty plot with the given title and axis labels.
+    pub fn new(
+        title: impl Into<String>,
+        x_label: impl Into<String>,
+        y_label: impl Into<String>,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            x_label: x_label.into(),
+            y_label: y_label.into(),
+            equal_aspect: false,
+            series: Vec::new(),
+            markers: Vec::new(),
+        }
+    }
+
+    /// Adds a solid polyline with a legend entry.
+    pub fn line(&mut self, label: impl Into<String>, points: Vec<(f32, f32)>, color: &'static str) {
+        self.series.push(Series {
+            label: Some(label.into()),
+            points,
+            color,
+            dashed: false,
+        });
+    }
+
+    /// Adds a dashed polyline with a legend entry.
+    pub fn dashed_line(
+        &mut self,
+        label: impl Into<String>,
+        points: Vec<(f32, f32)>,
+        color: &'static str,
+    ) {
+        self.series.push(Series {
+            label: Some(label.into()),
+            points,
+            color,
+            dashed: true,
+        });
+    }
+
+    /// Adds a polyline without a legend entry.
+    pub fn add_series(&mut self, series: Series) {
+        self.series.push(series);
+    }
+
+    /// Adds a point marker.
+    pub fn marker(&mut self, x: f32, y: f32, color: &'static str) {
+        self.markers.push((x, y, color));
+    }
+
+    /// Renders the plot, the axes fitting the finite points.
+    pub fn finish(&self) -> svg::Document {
+        let points = self
+            .series
+            .iter()
+            .flat_map(|series| series.points.iter().copied())
+            .chain(self.markers.iter().map(|&(x, y, _)| (x, y)))
+            .filter(|(x, y)| x.is_finite() && y.is_finite());
+        let (mut x_range, mut y_range) = bounds(points);
+
+        if self.equal_aspect {
+            let scale = (x_range.span() / (WIDTH - 2.0 * MARGIN))
+                .max(y_range.span() / (HEIGHT - 2.0 * MARGIN));
+            x_range = x_range.resize(scale * (WIDTH - 2.0 * MARGIN));
+            y_range = y_range.resize(scale * (HEIGHT - 2.0 * MARGIN));
+        }
+
+        let frame = Frame { x_range, y_range };
+        let mut document = frame.document(&self.title, &self.x_label, &self.y_label);
+
+        for series in &self.series {
+            let data = series
+                .points
+                .iter()
+                .filter(|(x, y)| x.is_finite() && y.is_finite())
+                .map(|&(x, y)| format!("{:.2},{:.2}", frame.x(x), frame.y(y)))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let mut polyline = svg::node::element::Polyline::new()
+                .set("points", data)
+                .set("fill", "none")
+                .set("stroke", series.color)
+                .set("stroke-width", 1.5)
+                .set("stroke-linejoin", "round");
+
+            if series.dashed {
+                polyline.assign("stroke-dasharray", "6 4");
+            }
+
+            document.append(polyline);
+        }
+
+        for &(x, y, color) in &self.markers {
+            document.append(
+                svg::node::element::Circle::new()
+                    .set("cx", frame.x(x))
+                    .set("cy", frame.y(y))
+                    .set("r", 2.0)
+                    .set("fill", color),
+            );
+        }
+
+        let labels = self
+            .series
+            .iter()
+            .filter_map(|series| Some((series.label.as_deref()?, series.color)));
+        legend(&mut document, labels);
+
+        document
+    }
+
+    /// Renders the plot to an SVG file.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), std::io::Error> {
+        svg::save(path, &self.finish())
+    }
+}
+
+/// Grouped bar chart, one group per category.
+pub struct BarChart {
+    /// Title drawn above the chart.
+    pub title: String,
+    /// Label of the vertical axis.
+    pub y_label: String,
+    /// Labels of the bar groups.
+    pub categories: Vec<String>,
+    groups: Vec<(String, Vec<f32>, &'static str)>,
+}
+
+impl BarChart {
+    /// Empty chart with the given title, axis label and categories.
+    pub fn new(
+        title: impl Into<String>,
+        y_label: impl Into<String>,
+        categories: Vec<String>,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            y_label: y_label.into(),
+            categories,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Adds one bar per category, `values` being indexed like the categories.
+    pub fn bars(&mut self, label: impl Into<String>, values: Vec<f32>, color: &'static str) {
+        self.groups.push((label.into(), values, color));
+    }
+
+    /// Renders the chart, the vertical axis starting at zero.
+    pub fn finish(&self) -> svg::Document {
+        let n_categories = self.categories.len().max(1);
+        let values = self
+            .groups
+            .iter()
+            .flat_map(|(_, values, _)| values.iter().copied())
+            .filter(|value| value.is_finite())
+            .map(|value| (0.0, value))
+            .chain([(n_categories as f32, 0.0)]);
+        let (_, y_range) = bounds(values);
+
+        let frame = Frame {
+            x_range: Range {
+                min: 0.0,
+                max: n_categories as f32,
+            },
+            y_range,
+        };
+        let mut document = frame.document(&self.title, "", &self.y_label);
+
+        let slot = 0.8 / self.groups.len().max(1) as f32;
+        for (j, (_, values, color)) in self.groups.iter().enumerate() {
+            for (i, value) in values.iter().enumerate().filter(|(_, v)| v.is_finite()) {
+                let x0 = frame.x(i as f32 + 0.1 + j as f32 * slot);
+                let x1 = frame.x(i as f32 + 0.1 + (j + 1) as f32 * slot);
+                let (y0, y1) = (frame.y(0.0), frame.y(*value));
+
+                document.append(
+                    svg::node::element::Rectangle::new()
+                        .set("x", x0)
+                        .set("y", y0.min(y1))
+                        .set("width", x1 - x0)
+                        .set("height", (y1 - y0).abs())
+                        .set("fill", *color),
+                );
+            }
+        }
+
+        for (i, category) in self.categories.iter().enumerate() {
+            document.append(
+                text(
+                    category.as_str(),
+                    frame.x(i as f32 + 0.5),
+                    HEIGHT - MARGIN + 18.0,
+                )
+                .set("text-anchor", "middle"),
+            );
+        }
+
+        document.append(
+            svg::node::element::Line::new()
+                .set("x1", MARGIN)
+                .set("y1", frame.y(0.0))
+                .set("x2", WIDTH - MARGIN)
+                .set("y2", frame.y(0.0))
+                .set("stroke", "white")
+                .set("stroke-width", 1.0),
+        );
+
+        let labels = self
+            .groups
+            .iter()
+            .map(|(label, _, color)| (label.as_str(), *color));
+        legend(&mut document, labels);
+
+        document
+    }
+
+    /// Renders the chart to an SVG file.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), std::io::Error> {
+        svg::save(path, &self.finish())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    min: f32,
+    max: f32,
+}
+
+impl Range {
+    fn span(&self) -> f32 {
+        self.max - self.min
+    }
+
+    fn resize(&self, span: f32) -> Self {
+        let center = 0.5 * (self.min + self.max);
+
+        Self {
+            min: center - 0.5 * span,
+            max: center + 0.5 * span,
+        }
+    }
+
+    /// Returns evenly spaced tick values at a "nice" step (1, 2 or 5 times a power of ten).
+    fn ticks(&self) -> Vec<f32> {
+        let raw = self.span() / TICKS as f32;
+        let magnitude = 10f32.powf(raw.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .into_iter()
+            .map(|factor| factor * magnitude)
+            .find(|step| *step >= raw)
+            .unwrap_or(raw);
+
+        let first = (self.min / step).ceil() as i32;
+        let last = (self.max / step).floor() as i32;
+
+        (first..=last).map(|i| i as f32 * step).collect()
+    }
+}
+
+fn bounds(points: impl Iterator<Item = (f32, f32)>) -> (Range, Range) {
+    let (mut x, mut y) = (
+        Range {
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        },
+        Range {
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        },
+    );
+
+    for (px, py) in points {
+        x = Range {
+            min: x.min.min(px),
+            max: x.max.max(px),
+        };
+        y = Range {
+            min: y.min.min(py),
+            max: y.max.max(py),
+        };
+    }
+
+    (pad(x), pad(y))
+}
+
+/// Widens degenerate or empty ranges so they can still be drawn.
+fn pad(range: Range) -> Range {
+    if !range.min.is_finite() || !range.max.is_finite() {
+        Range {
+            min: -1.0,
+            max: 1.0,
+        }
+    } else if range.span() <= f32::EPSILON * range.max.abs().max(1.0) {
+        let half = 0.5 * range.max.abs().max(1e-3);
+        Range {
+            min: range.min - half,
+            max: range.max + half,
+        }
+    } else {
+        let margin = 0.05 * range.span();
+        Range {
+            min: range.min - margin,
+            max: range.max + margin,
+        }
+    }
+}
+
+struct Frame {
+    x_range: Range,
+    y_range: Range,
+}
+
+impl Frame {
+    fn x(&self, x: f32) -> f32 {
+        MARGIN + (x - self.x_range.min) / self.x_range.span() * (WIDTH - 2.0 * MARGIN)
+    }
+
+    fn y(&self, y: f32) -> f32 {
+        HEIGHT - MARGIN - (y - self.y_range.min) / self.y_range.span() * (HEIGHT - 2.0 * MARGIN)
+    }
+
+    /// Creates the background, axes, ticks and labels.
+    fn document(&self, title: &str, x_label: &str, y_label: &str) -> svg::Document {
+        let mut document = svg::Document::new()
+            .set("viewBox", (0.0, 0.0, WIDTH, HEIGHT))
+            .set("width", WIDTH)
+            .set("height", HEIGHT)
+            .set("font-family", "sans-serif")
+            .set("font-size", 11)
+            .add(
+                svg::node::element::Rectangle::new()
+                    .set("fill", "black")
+                    .set("width", WIDTH)
+                    .set("height", HEIGHT),
+            )
+            .add(
+                svg::node::element::Rectangle::new()
+                    .set("x", MARGIN)
+                    .set("y", MARGIN)
+                    .set("width", WIDTH - 2.0 * MARGIN)
+                    .set("height", HEIGHT - 2.0 * MARGIN)
+                    .set("fill", "none")
+                    .set("stroke", "white")
+                    .set("stroke-width", 1.0),
+            )
+            .add(text(title, WIDTH / 2.0, MARGIN / 2.0).set("text-anchor", "middle"))
+            .add(text(x_label, WIDTH / 2.0, HEIGHT - 16.0).set("text-anchor", "middle"))
+            .add(
+                text(y_label, 16.0, HEIGHT / 2.0)
+                    .set("text-anchor", "middle")
+                    .set("transform", format!("rotate(-90 16 {})", HEIGHT / 2.0)),
+            );
+
+        if !x_label.is_empty() {
+            for tick in self.x_range.ticks() {
+                let x = self.x(tick);
+                document.append(grid_line(x, MARGIN, x, HEIGHT - MARGIN));
+                document.append(
+                    text(&format_tick(tick), x, HEIGHT - MARGIN + 16.0)
+                        .set("text-anchor", "middle"),
+                );
+            }
+        }
+
+        for tick in self.y_range.ticks() {
+            let y = self.y(tick);
+            document.append(grid_line(MARGIN, y, WIDTH - MARGIN, y));
+            document
+                .append(text(&format_tick(tick), MARGIN - 6.0, y + 4.0).set("text-anchor", "end"));
+        }
+
+        document
+    }
+}
+
+fn legend<'a>(document: &mut svg::Document, labels: impl Iterator<Item = (&'a str, &'static str)>) {
+    for (i, (label, color)) in labels.enumerate() {
+        let y = MARGIN + 14.0 + 14.0 * i as f32;
+
+        document.append(
+            svg::node::element::Line::new()
+                .set("x1", WIDTH - MARGIN - 96.0)
+                .set("y1", y - 4.0)
+                .set("x2", WIDTH - MARGIN - 80.0)
+                .set("y2", y - 4.0)
+                .set("stroke", color)
+                .set("stroke-width", 2.0),
+        );
+        document.append(text(label, WIDTH - MARGIN - 76.0, y));
+    }
+}
+
+fn grid_line(x1: f32, y1: f32, x2: f32, y2: f32) -> svg::node::element::Line {
+    svg::node::element::Line::new()
+        .set("x1", x1)
+        .set("y1", y1)
+        .set("x2", x2)
+        .set("y2", y2)
+        .set("stroke", "gray")
+        .set("stroke-width", 0.5)
+        .set("opacity", 0.5)
+}
+
+fn text(content: &str, x: f32, y: f32) -> svg::node::element::Text {
+    svg::node::element::Text::new(content)
+        .set("x", x)
+        .set("y", y)
+        .set("fill", "white")
+}
+
+fn format_tick(value: f32) -> String {
+    let value = if value.abs() < 1e-9 { 0.0 } else { value };
+
+    if value != 0.0 && (value.abs() < 1e-2 || value.abs() >= 1e4) {
+        format!("{value:.1e}")
+    } else {
+        let text = format!("{value:.4}");
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}